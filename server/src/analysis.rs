@@ -0,0 +1,13 @@
+pub mod components;
+pub mod damage_spreading;
+pub mod difference;
+pub mod geometry;
+pub mod minkowski;
+pub mod order_parameter;
+pub mod segregation;
+pub mod stability;
+pub mod structure_factor;
+pub mod topology;
+
+mod cubical_complex;
+mod periodic_union_find;
\ No newline at end of file
@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+
+use crate::appdata::dim3d::automata::automaton::CellularAutomaton3D;
+
+use super::difference::cell_types_of;
+use super::periodic_union_find::PeriodicUnionFind;
+
+//
+// Which neighbours of a cell count as connected to it
+//
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Connectivity {
+    // The 6 cells that share a face
+    #[default]
+    #[serde(rename = "6", alias = "face")]
+    Face,
+    // The 18 cells that share a face or an edge
+    #[serde(rename = "18", alias = "edge")]
+    Edge,
+    // The 26 cells that share a face, an edge or a corner
+    #[serde(rename = "26", alias = "corner")]
+    Corner
+}
+
+impl Connectivity {
+
+    //
+    // Half of the neighbour offsets: the other half are their opposites, and every pair of
+    // neighbouring cells only has to be visited once.
+    //
+    pub fn forward_offsets(&self) -> Vec<[i64; 3]> {
+        let max_nonzero = match self {
+            Connectivity::Face => 1,
+            Connectivity::Edge => 2,
+            Connectivity::Corner => 3
+        };
+
+        let mut offsets = vec![];
+
+        for dx in -1..=1i64 {
+            for dy in -1..=1i64 {
+                for dz in -1..=1i64 {
+                    let nonzero = [dx, dy, dz].iter().filter(|d| **d != 0).count();
+                    let forward = (dx, dy, dz) > (0, 0, 0);
+
+                    if nonzero > 0 && nonzero <= max_nonzero && forward {
+                        offsets.push([dx, dy, dz]);
+                    }
+                }
+            }
+        }
+
+        offsets
+    }
+
+}
+
+//
+// The connected components of one cell-type
+//
+#[derive(Clone, Serialize)]
+pub struct CellTypeComponents {
+    pub count: u32,
+    // histogram[i] is the number of components with a size (in cells) in [2^i, 2^(i+1))
+    pub size_histogram: Vec<u32>,
+    pub largest_size: u64,
+    // The part of all cells of this type that belongs to the largest component
+    pub largest_fraction: f32,
+    // Whether a component wraps around the periodic domain along x, y and z
+    pub percolates: [bool; 3]
+}
+
+#[derive(Clone, Serialize)]
+pub struct ComponentAnalysis {
+    pub iteration: u32,
+    pub connectivity: Connectivity,
+    pub cell_types: Vec<CellTypeComponents>
+}
+
+//
+// Label the connected components of every cell-type. Components are merged across the periodic boundaries.
+//
+pub fn find_components<A: CellularAutomaton3D + ?Sized>(automaton: &A, num_cell_types: usize, connectivity: Connectivity) -> ComponentAnalysis {
+    let size = automaton.size();
+
+    let cell_types = cell_types_of(automaton);
+
+    let union_find = PeriodicUnionFind::label(size, &connectivity.forward_offsets(), |a, b| cell_types[a] == cell_types[b]);
+
+    let num_bins = (size*size*size).ilog2() as usize + 1;
+
+    let mut result: Vec<CellTypeComponents> = (0..num_cell_types).map(|_| CellTypeComponents {
+        count: 0,
+        size_histogram: vec![0; num_bins],
+        largest_size: 0,
+        largest_fraction: 0.0,
+        percolates: [false; 3]
+    }).collect();
+
+    let mut volumes = vec![0u64; num_cell_types];
+
+    for cell in 0..cell_types.len() {
+        let cell_type = cell_types[cell] as usize;
+
+        volumes[cell_type] += 1;
+
+        if !union_find.is_root(cell) {
+            continue;
+        }
+
+        let component_size = union_find.component_size(cell);
+        let components = &mut result[cell_type];
+
+        components.count += 1;
+        components.size_histogram[component_size.ilog2() as usize] += 1;
+        components.largest_size = u64::max(components.largest_size, component_size);
+
+        // The component percolates along every axis that one of its loops winds around
+        for winding in union_find.windings(cell) {
+            for axis in 0..3 {
+                components.percolates[axis] |= winding[axis] != 0;
+            }
+        }
+    }
+
+    for (components, volume) in result.iter_mut().zip(volumes) {
+        if volume > 0 {
+            components.largest_fraction = components.largest_size as f32 / volume as f32;
+        }
+    }
+
+    ComponentAnalysis {
+        iteration: automaton.get_iteration_count(),
+        connectivity,
+        cell_types: result
+    }
+}
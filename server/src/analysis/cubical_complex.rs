@@ -0,0 +1,70 @@
+//
+// The number of cells of every dimension in the cubical complex formed by the union of all closed voxels
+//
+pub struct CellCounts {
+    pub vertices: i64,
+    pub edges: i64,
+    pub faces: i64,
+    pub cubes: i64
+}
+
+impl CellCounts {
+
+    pub fn euler_characteristic(&self) -> i64 {
+        self.vertices - self.edges + self.faces - self.cubes
+    }
+
+}
+
+//
+// Every lattice point is the lower corner of one vertex, three edges, three faces and one cube,
+// each of which belongs to the complex if one of the voxels around it does.
+// Voxel (x, y, z) spans [x, x+1] x [y, y+1] x [z, z+1], and the lattice wraps around.
+//
+pub fn count_cells(size: usize, inside: &[bool]) -> CellCounts {
+    let index = |x: usize, y: usize, z: usize| (x % size * size + y % size) * size + z % size;
+
+    // Whether one of the voxels at (x - dx, y - dy, z - dz) for the given offsets is inside
+    let any_inside = |x: usize, y: usize, z: usize, offsets: &[[usize; 3]]| {
+        offsets.iter().any(|[dx, dy, dz]| inside[index(x + size - dx, y + size - dy, z + size - dz)])
+    };
+
+    let mut vertices = 0;
+    let mut edges = 0;
+    let mut faces = 0;
+    let mut cubes = 0;
+
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                if inside[index(x, y, z)] {
+                    cubes += 1;
+                }
+
+                if any_inside(x, y, z, &[[0, 0, 0], [1, 0, 0], [0, 1, 0], [0, 0, 1], [1, 1, 0], [1, 0, 1], [0, 1, 1], [1, 1, 1]]) {
+                    vertices += 1;
+                }
+
+                // Edges along x, y and z are shared by the four voxels around them
+                for offsets in [
+                    [[0, 0, 0], [0, 1, 0], [0, 0, 1], [0, 1, 1]],
+                    [[0, 0, 0], [1, 0, 0], [0, 0, 1], [1, 0, 1]],
+                    [[0, 0, 0], [1, 0, 0], [0, 1, 0], [1, 1, 0]]
+                ] {
+                    if any_inside(x, y, z, &offsets) {
+                        edges += 1;
+                    }
+                }
+
+                // Faces normal to x, y and z are shared by the two voxels on either side
+                for offsets in [[[0, 0, 0], [1, 0, 0]], [[0, 0, 0], [0, 1, 0]], [[0, 0, 0], [0, 0, 1]]] {
+                    if any_inside(x, y, z, &offsets) {
+                        faces += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    CellCounts { vertices, edges, faces, cubes }
+}
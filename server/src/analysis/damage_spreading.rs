@@ -0,0 +1,123 @@
+use miette::{miette, Result};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+
+use crate::appdata::dim3d::automata::automaton::CellularAutomaton3D;
+
+//
+// The differences between the perturbed automaton and the original one at one iteration
+//
+#[derive(Clone, Serialize)]
+pub struct DamageSample {
+    pub iteration: u32,
+    // The number of cells with a different cell-type
+    pub hamming_distance: u64,
+    pub fraction: f32,
+    // The number of planes along x, y and z that contain a difference: the size of the damaged region on the torus
+    pub extent: [usize; 3],
+    // The root mean square of the (periodic) distance from every difference to the nearest perturbed cell
+    pub radius: f32
+}
+
+#[derive(Clone, Serialize)]
+pub struct DamageSpreading {
+    pub perturbed_cells: Vec<[usize; 3]>,
+    // The first sample is the perturbation itself
+    pub samples: Vec<DamageSample>
+}
+
+// The largest number of iterations and perturbed cells that a damage spreading experiment can be asked for
+pub const MAX_DAMAGE_ITERATIONS: u32 = 1000;
+pub const MAX_DAMAGED_CELLS: usize = 1000;
+
+//
+// Distinct random cells of a grid, reproducible through the seed
+//
+pub fn random_cells(size: usize, count: usize, seed: u64) -> Vec<[usize; 3]> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let count = count.min(size*size*size);
+
+    rand::seq::index::sample(&mut rng, size*size*size, count).iter()
+        .map(|i| [i / (size*size), (i / size) % size, i % size])
+        .collect()
+}
+
+//
+// Give each of the cells a different, random cell-type in a copy of the automaton, and run both for a number
+// of iterations in lockstep. Both keep running after they have converged, since a settled pattern with a few
+// perturbed cells usually counts as converged.
+//
+pub fn damage_spreading<A: CellularAutomaton3D + Clone>(automaton: &A, num_cell_types: usize, cells: &[[usize; 3]], iterations: u32, seed: u64) -> Result<DamageSpreading> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let mut original = automaton.clone();
+    let mut perturbed = automaton.clone();
+
+    // Only the states of the copies are compared, so they don't need to measure anything along the way
+    original.stop_recording();
+    perturbed.stop_recording();
+
+    // Every cell-type is replaced by one of the other cell-types
+    if num_cell_types > 1 {
+        for [x, y, z] in cells {
+            let cell_type = perturbed.get(*x, *y, *z) as usize;
+            let shift = rng.gen_range(1..num_cell_types);
+
+            perturbed.set(*x, *y, *z, ((cell_type + shift) % num_cell_types) as u32);
+        }
+    }
+
+    let mut samples = vec![measure_damage(&original, &perturbed, cells)];
+
+    for _ in 0..iterations {
+        let iteration = original.get_iteration_count();
+
+        original.force_iteration();
+        perturbed.force_iteration();
+
+        // Comparing a state with one that didn't advance would be meaningless
+        if original.get_iteration_count() != iteration + 1 || perturbed.get_iteration_count() != iteration + 1 {
+            return Err(miette!("The copies of the automaton didn't both advance beyond iteration {}", iteration));
+        }
+
+        samples.push(measure_damage(&original, &perturbed, cells));
+    }
+
+    Ok(DamageSpreading {
+        perturbed_cells: cells.to_vec(),
+        samples
+    })
+}
+
+fn measure_damage<A: CellularAutomaton3D>(original: &A, perturbed: &A, cells: &[[usize; 3]]) -> DamageSample {
+    let size = original.size();
+    let differences = original.differences(perturbed);
+
+    let mut planes = vec![[false; 3]; size];
+    let mut squared_distances = 0f64;
+
+    // The shortest distance between two coordinates on the torus
+    let periodic = |a: usize, b: usize| { let d = a.abs_diff(b); d.min(size - d) as f64 };
+
+    for position in &differences {
+        for axis in 0..3 {
+            planes[position[axis]][axis] = true;
+        }
+
+        squared_distances += cells.iter()
+            .map(|cell| (0..3).map(|axis| periodic(position[axis], cell[axis]).powi(2)).sum::<f64>())
+            .fold(f64::INFINITY, f64::min);
+    }
+
+    let extent = [0, 1, 2].map(|axis| planes.iter().filter(|p| p[axis]).count());
+
+    DamageSample {
+        iteration: perturbed.get_iteration_count(),
+        hamming_distance: differences.len() as u64,
+        fraction: differences.len() as f32 / (size*size*size) as f32,
+        extent,
+        radius: if differences.is_empty() || cells.is_empty() { 0.0 } else { (squared_distances / differences.len() as f64).sqrt() as f32 }
+    }
+}
@@ -0,0 +1,131 @@
+use miette::{miette, Result};
+use serde::Serialize;
+
+use crate::appdata::dim3d::automata::automaton::CellularAutomaton3D;
+
+//
+// The overlap of one cell-type in two states
+//
+#[derive(Clone, Serialize)]
+pub struct CellTypeOverlap {
+    // The number of cells with this type in both states, and in either of them
+    pub intersection: u64,
+    pub union: u64,
+    // The Jaccard index intersection / union, 1 if the cell-type occurs in neither state
+    pub iou: f32
+}
+
+//
+// The smallest axis-aligned box (inclusive) that contains all differences, without wrapping around the torus
+//
+#[derive(Clone, Serialize)]
+pub struct BoundingBox {
+    pub min: [usize; 3],
+    pub max: [usize; 3]
+}
+
+#[derive(Clone, Serialize)]
+pub struct StateDifference {
+    pub mismatches: u64,
+    pub mismatch_fraction: f32,
+    // confusion_matrix[a][b] is the number of cells with type a in the first state and type b in the second one
+    pub confusion_matrix: Vec<Vec<u64>>,
+    pub cell_types: Vec<CellTypeOverlap>,
+    // None if the states are identical
+    pub bounding_box: Option<BoundingBox>,
+    // 1 where the states differ and 0 elsewhere, in C order. Only included on request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub difference_volume: Option<Vec<u8>>
+}
+
+//
+// The cell-types of an automaton in C order, so that they can be compared with a snapshot
+//
+pub fn cell_types_of<A: CellularAutomaton3D + ?Sized>(automaton: &A) -> Vec<u32> {
+    let size = automaton.size();
+
+    let mut cell_types = vec![0u32; size*size*size];
+
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                cell_types[(x*size + y)*size + z] = automaton.get(x, y, z);
+            }
+        }
+    }
+
+    cell_types
+}
+
+//
+// The positions at which two states of the same size, both in C order, differ
+//
+pub fn differing_positions(size: usize, first: &[u32], second: &[u32]) -> Vec<[usize; 3]> {
+    first.iter().zip(second).enumerate()
+        .filter(|(_, (a, b))| a != b)
+        .map(|(i, _)| [i / (size*size), (i / size) % size, i % size])
+        .collect()
+}
+
+pub fn compare_automata<A: CellularAutomaton3D + ?Sized, B: CellularAutomaton3D + ?Sized>(first: &A, second: &B, include_volume: bool) -> Result<StateDifference> {
+    if first.size() != second.size() {
+        return Err(miette!("Can't compare automata of size {} and {}", first.size(), second.size()));
+    }
+
+    Ok(compare_cell_types(first.size(), &cell_types_of(first), &cell_types_of(second), include_volume))
+}
+
+//
+// Compare two states of the same size, both in C order. The number of cell-types follows from the highest
+// cell-type in either state, so that any two automata or snapshots can be compared.
+//
+pub fn compare_cell_types(size: usize, first: &[u32], second: &[u32], include_volume: bool) -> StateDifference {
+    let num_cell_types = first.iter().chain(second).max().map_or(0, |max| *max as usize + 1);
+
+    let mut confusion_matrix = vec![vec![0u64; num_cell_types]; num_cell_types];
+
+    for (a, b) in first.iter().zip(second) {
+        confusion_matrix[*a as usize][*b as usize] += 1;
+    }
+
+    let mut difference_volume = vec![0u8; if include_volume { size*size*size } else { 0 }];
+    let mut bounding_box: Option<BoundingBox> = None;
+
+    for position in differing_positions(size, first, second) {
+        if include_volume {
+            difference_volume[(position[0]*size + position[1])*size + position[2]] = 1;
+        }
+
+        let bounds = bounding_box.get_or_insert(BoundingBox { min: position, max: position });
+
+        for axis in 0..3 {
+            bounds.min[axis] = bounds.min[axis].min(position[axis]);
+            bounds.max[axis] = bounds.max[axis].max(position[axis]);
+        }
+    }
+
+    let cell_types = (0..num_cell_types).map(|t| {
+        let intersection = confusion_matrix[t][t];
+        let in_first: u64 = confusion_matrix[t].iter().sum();
+        let in_second: u64 = confusion_matrix.iter().map(|row| row[t]).sum();
+        let union = in_first + in_second - intersection;
+
+        CellTypeOverlap {
+            intersection,
+            union,
+            iou: if union == 0 { 1.0 } else { intersection as f32 / union as f32 }
+        }
+    }).collect();
+
+    let matches: u64 = (0..num_cell_types).map(|t| confusion_matrix[t][t]).sum();
+    let mismatches = first.len() as u64 - matches;
+
+    StateDifference {
+        mismatches,
+        mismatch_fraction: if first.is_empty() { 0.0 } else { mismatches as f32 / first.len() as f32 },
+        confusion_matrix,
+        cell_types,
+        bounding_box,
+        difference_volume: if include_volume { Some(difference_volume) } else { None }
+    }
+}
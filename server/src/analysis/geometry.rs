@@ -0,0 +1,75 @@
+use serde::Serialize;
+
+use crate::appdata::dim3d::automata::automaton::CellularAutomaton3D;
+use crate::meshgeneration::{cell_type_source::extract_periodic_cell_type_mesh, extractor::ExtractorKind};
+
+//
+// The geometry of one cell-type
+//
+#[derive(Clone, Serialize)]
+pub struct CellTypeGeometry {
+    // The number of cells of this type
+    pub volume: u64,
+    pub volume_fraction: f32,
+    // The area of the periodic marching cubes surface around these cells, in cells²
+    pub surface_area: f32,
+    pub surface_to_volume: f32
+}
+
+//
+// The geometry of all cell-types at one iteration
+//
+#[derive(Clone, Serialize)]
+pub struct GeometryMeasurement {
+    pub iteration: u32,
+    pub cell_types: Vec<CellTypeGeometry>,
+    // interface_matrix[a][b] is the number of faces that a cell of type a shares with a cell of type b.
+    // It's symmetric, and the diagonal counts the faces between cells of the same type.
+    pub interface_matrix: Vec<Vec<u64>>
+}
+
+pub fn measure_geometry<A: CellularAutomaton3D + ?Sized>(automaton: &A, num_cell_types: usize) -> GeometryMeasurement {
+    let size = automaton.size();
+
+    let mut volumes = vec![0u64; num_cell_types];
+    let mut interface_matrix = vec![vec![0u64; num_cell_types]; num_cell_types];
+
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                let a = automaton.get(x, y, z) as usize;
+
+                volumes[a] += 1;
+
+                // Every face is counted once, from the cell on its lower side. The grid wraps around.
+                for (nx, ny, nz) in [((x + 1) % size, y, z), (x, (y + 1) % size, z), (x, y, (z + 1) % size)] {
+                    let b = automaton.get(nx, ny, nz) as usize;
+
+                    interface_matrix[a][b] += 1;
+
+                    if a != b {
+                        interface_matrix[b][a] += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    let cell_types = (0..num_cell_types).map(|cell_type| {
+        let surface_area = extract_periodic_cell_type_mesh(automaton, cell_type as u32, ExtractorKind::MarchingCubes).area();
+        let volume = volumes[cell_type];
+
+        CellTypeGeometry {
+            volume,
+            volume_fraction: volume as f32 / (size*size*size) as f32,
+            surface_area,
+            surface_to_volume: if volume > 0 { surface_area / volume as f32 } else { 0.0 }
+        }
+    }).collect();
+
+    GeometryMeasurement {
+        iteration: automaton.get_iteration_count(),
+        cell_types,
+        interface_matrix
+    }
+}
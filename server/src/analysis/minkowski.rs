@@ -0,0 +1,73 @@
+use std::f32::consts::PI;
+
+use serde::Serialize;
+
+use crate::appdata::dim3d::automata::automaton::CellularAutomaton3D;
+
+use super::cubical_complex::count_cells;
+use super::difference::cell_types_of;
+
+//
+// The four Minkowski functionals of the union of all (closed) voxels of one cell-type.
+// They follow from the number of cubes, faces, edges and vertices of the cubical complex,
+// as in Michielsen and De Raedt (2001), with a lattice spacing of one cell:
+//   V = n3,  S = 2 n2 - 6 n3,  M = π (n1 - 2 n2 + 3 n3),  χ = n0 - n1 + n2 - n3
+//
+#[derive(Clone, Serialize)]
+pub struct MinkowskiFunctionals {
+    pub volume: f32,
+    pub surface_area: f32,
+    // The integral of the mean curvature (κ1 + κ2) / 2 over the surface
+    pub integrated_mean_curvature: f32,
+    pub euler_characteristic: f32
+}
+
+impl MinkowskiFunctionals {
+
+    //
+    // The densities of the functionals: their values per cell of the domain.
+    // These don't depend on the size of the automaton, which makes them comparable between experiments.
+    //
+    pub fn normalised(&self, num_cells: usize) -> MinkowskiFunctionals {
+        let n = num_cells as f32;
+
+        MinkowskiFunctionals {
+            volume: self.volume / n,
+            surface_area: self.surface_area / n,
+            integrated_mean_curvature: self.integrated_mean_curvature / n,
+            euler_characteristic: self.euler_characteristic / n
+        }
+    }
+
+}
+
+#[derive(Clone, Serialize)]
+pub struct MinkowskiMeasurement {
+    pub iteration: u32,
+    pub cell_types: Vec<MinkowskiFunctionals>,
+    pub normalised: Vec<MinkowskiFunctionals>
+}
+
+pub fn measure_minkowski_functionals<A: CellularAutomaton3D + ?Sized>(automaton: &A, num_cell_types: usize) -> MinkowskiMeasurement {
+    let size = automaton.size();
+
+    let cell_types = cell_types_of(automaton);
+
+    let functionals: Vec<MinkowskiFunctionals> = (0..num_cell_types).map(|cell_type| {
+        let inside: Vec<bool> = cell_types.iter().map(|c| *c == cell_type as u32).collect();
+        let counts = count_cells(size, &inside);
+
+        MinkowskiFunctionals {
+            volume: counts.cubes as f32,
+            surface_area: (2*counts.faces - 6*counts.cubes) as f32,
+            integrated_mean_curvature: PI * (counts.edges - 2*counts.faces + 3*counts.cubes) as f32,
+            euler_characteristic: counts.euler_characteristic() as f32
+        }
+    }).collect();
+
+    MinkowskiMeasurement {
+        iteration: automaton.get_iteration_count(),
+        normalised: functionals.iter().map(|f| f.normalised(size*size*size)).collect(),
+        cell_types: functionals
+    }
+}
@@ -0,0 +1,72 @@
+use rustfft::{FftDirection, FftPlanner, num_complex::Complex};
+use serde::Serialize;
+
+use crate::appdata::dim3d::automata::{automaton::CellularAutomaton3D, stencil::Neighbourhood};
+
+use super::difference::cell_types_of;
+use super::structure_factor::fft_3d;
+
+// The smallest step between the radii, which limits the number of spheres that are summed over
+pub const MIN_RADIUS_STEP: f32 = 0.25;
+
+//
+// The order parameter of every cell-type over spheres of increasing radius
+//
+#[derive(Clone, Serialize)]
+pub struct MultiscaleOrderParameter {
+    pub iteration: u32,
+    pub radii: Vec<f32>,
+    // epsilon[cell_type][i] is the order parameter of the cell-type over a sphere with radius radii[i]
+    pub epsilon: Vec<Vec<f32>>
+}
+
+//
+// The order parameter ε_i over a neighbourhood is the average of σ_i(c) σ_i(c + d) over all cells c and
+// neighbour offsets d, where σ_i is 1 for cells of type i and -1 otherwise. Summed over all cells, it's the
+// periodic autocorrelation of σ_i at d, which follows for every offset at once from the power spectrum.
+// Every radius then only costs a sum over its offsets.
+//
+pub fn multiscale_order_parameter<A: CellularAutomaton3D + ?Sized>(automaton: &A, num_cell_types: usize, radii: &[f32]) -> MultiscaleOrderParameter {
+    let size = automaton.size();
+    let num_cells = size*size*size;
+
+    let cell_types = cell_types_of(automaton);
+
+    let wrap = |c: i32| c.rem_euclid(size as i32) as usize;
+    let offsets: Vec<Vec<(i32, i32, i32)>> = radii.iter().map(|r| Neighbourhood::Sphere(*r).offsets()).collect();
+
+    let mut planner = FftPlanner::<f32>::new();
+
+    let epsilon = (0..num_cell_types).map(|cell_type| {
+        let mut field: Vec<Complex<f32>> = cell_types.iter()
+            .map(|c| Complex::new(if *c == cell_type as u32 { 1.0 } else { -1.0 }, 0.0))
+            .collect();
+
+        fft_3d(&mut planner, size, &mut field, FftDirection::Forward);
+
+        for value in field.iter_mut() {
+            *value = Complex::new(value.norm_sqr(), 0.0);
+        }
+
+        fft_3d(&mut planner, size, &mut field, FftDirection::Inverse);
+
+        // The unnormalised transforms leave a factor of size³ in the autocorrelation
+        let correlation = |(x, y, z): (i32, i32, i32)| field[(wrap(x)*size + wrap(y))*size + wrap(z)].re / num_cells as f32;
+
+        offsets.iter().map(|neighbours| {
+            if neighbours.is_empty() {
+                return 0.0;
+            }
+
+            let sum: f32 = neighbours.iter().map(|d| correlation(*d)).sum();
+
+            sum / (neighbours.len() * num_cells) as f32
+        }).collect()
+    }).collect();
+
+    MultiscaleOrderParameter {
+        iteration: automaton.get_iteration_count(),
+        radii: radii.to_vec(),
+        epsilon
+    }
+}
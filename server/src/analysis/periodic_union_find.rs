@@ -0,0 +1,149 @@
+//
+// A union-find structure over the cells of a periodic grid, stored in C order.
+// Besides the components, it tracks where every cell lies relative to its parent on the unrolled
+// (non-periodic) lattice. When two cells of the same component are connected with a displacement that
+// doesn't match their relative positions, the component contains a loop that winds around the torus.
+// The winding vectors of these loops are kept as a basis for every component.
+//
+pub struct PeriodicUnionFind {
+    pub size: usize,
+    parent: Vec<usize>,
+    offset: Vec<[i64; 3]>,
+    component_size: Vec<u64>,
+    windings: Vec<Vec<[i64; 3]>>
+}
+
+impl PeriodicUnionFind {
+
+    //
+    // Connect every cell to the cells at the given offsets (wrapping around the boundaries) if 'connected' holds.
+    // The offsets should only contain one of every pair of opposite offsets.
+    //
+    pub fn label(size: usize, offsets: &[[i64; 3]], connected: impl Fn(usize, usize) -> bool) -> Self {
+        let n = size*size*size;
+
+        let mut union_find = PeriodicUnionFind {
+            size,
+            parent: (0..n).collect(),
+            offset: vec![[0; 3]; n],
+            component_size: vec![1; n],
+            windings: vec![vec![]; n]
+        };
+
+        let index = |x: usize, y: usize, z: usize| (x*size + y)*size + z;
+        let wrap = |c: usize, d: i64| (c as i64 + d).rem_euclid(size as i64) as usize;
+
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    let cell = index(x, y, z);
+
+                    for offset in offsets {
+                        let neighbour = index(wrap(x, offset[0]), wrap(y, offset[1]), wrap(z, offset[2]));
+
+                        if connected(cell, neighbour) {
+                            union_find.union(cell, neighbour, *offset);
+                        }
+                    }
+                }
+            }
+        }
+
+        union_find
+    }
+
+    pub fn is_root(&self, cell: usize) -> bool {
+        self.parent[cell] == cell
+    }
+
+    // The number of cells in the component of a root
+    pub fn component_size(&self, root: usize) -> u64 {
+        self.component_size[root]
+    }
+
+    // A basis of the winding vectors (in periods along x, y and z) of the loops in the component of a root
+    pub fn windings(&self, root: usize) -> &[[i64; 3]] {
+        &self.windings[root]
+    }
+
+    // The root of a cell and the cell's position relative to it
+    fn find(&mut self, cell: usize) -> (usize, [i64; 3]) {
+        let mut path = vec![];
+        let mut root = cell;
+
+        while self.parent[root] != root {
+            path.push(root);
+            root = self.parent[root];
+        }
+
+        // Compress the path, starting closest to the root so that the parent's offset is already relative to the root
+        for c in path.into_iter().rev() {
+            let parent = self.parent[c];
+
+            if parent != root {
+                for axis in 0..3 {
+                    self.offset[c][axis] += self.offset[parent][axis];
+                }
+            }
+
+            self.parent[c] = root;
+        }
+
+        (root, if cell == root { [0; 3] } else { self.offset[cell] })
+    }
+
+    // Connect cell b to cell a, where b lies at 'step' from a on the unrolled lattice
+    fn union(&mut self, a: usize, b: usize, step: [i64; 3]) {
+        let (root_a, offset_a) = self.find(a);
+        let (root_b, offset_b) = self.find(b);
+
+        if root_a == root_b {
+            // The mismatch is always a whole number of periods
+            let winding = [0, 1, 2].map(|axis| (offset_a[axis] + step[axis] - offset_b[axis]) / self.size as i64);
+
+            add_to_basis(&mut self.windings[root_a], winding);
+
+            return;
+        }
+
+        // The position of root b relative to root a
+        let relative = [0, 1, 2].map(|axis| offset_a[axis] + step[axis] - offset_b[axis]);
+
+        // Union by size: the smaller tree is attached below the larger one
+        let (root, child, child_offset) = if self.component_size[root_a] >= self.component_size[root_b] {
+            (root_a, root_b, relative)
+        } else {
+            (root_b, root_a, relative.map(|c| -c))
+        };
+
+        self.parent[child] = root;
+        self.offset[child] = child_offset;
+        self.component_size[root] += self.component_size[child];
+
+        for winding in std::mem::take(&mut self.windings[child]) {
+            add_to_basis(&mut self.windings[root], winding);
+        }
+    }
+
+}
+
+//
+// Add a vector to a basis if it's linearly independent of the vectors already in it
+//
+pub fn add_to_basis(basis: &mut Vec<[i64; 3]>, v: [i64; 3]) {
+    let cross = |a: [i64; 3], b: [i64; 3]| [a[1]*b[2] - a[2]*b[1], a[2]*b[0] - a[0]*b[2], a[0]*b[1] - a[1]*b[0]];
+
+    let independent = match basis.len() {
+        0 => v != [0; 3],
+        1 => cross(basis[0], v) != [0; 3],
+        2 => {
+            let n = cross(basis[0], basis[1]);
+            n[0]*v[0] + n[1]*v[1] + n[2]*v[2] != 0
+        },
+        _ => false
+    };
+
+    if independent {
+        basis.push(v);
+    }
+}
@@ -0,0 +1,51 @@
+use crate::appdata::dim3d::automata::{automaton::CellularAutomaton3D, stencil::Neighbourhood};
+
+use super::difference::cell_types_of;
+
+//
+// The pairwise mixing of cell-types: matrix[a][b] is the number of times that a cell of type a has a neighbour
+// of type b, divided by the number expected when all cells were mixed randomly at the current volume fractions.
+// A value of 1 means that a and b are well-mixed, below 1 that they segregate and above 1 that they attract.
+// Pairs with a cell-type that doesn't occur have a value of 0. The matrix is symmetric.
+//
+pub fn segregation_matrix<A: CellularAutomaton3D + ?Sized>(automaton: &A, num_cell_types: usize, neighbourhood: Neighbourhood) -> Vec<Vec<f32>> {
+    let size = automaton.size();
+    let num_cells = size*size*size;
+
+    let cell_types: Vec<usize> = cell_types_of(automaton).iter().map(|c| *c as usize).collect();
+    let mut volumes = vec![0u64; num_cell_types];
+
+    for cell_type in &cell_types {
+        volumes[*cell_type] += 1;
+    }
+
+    let offsets = neighbourhood.offsets();
+    let wrap = |c: usize, d: i32| (c as i32 + d).rem_euclid(size as i32) as usize;
+
+    let mut counts = vec![vec![0u64; num_cell_types]; num_cell_types];
+
+    for x in 0..size {
+        for y in 0..size {
+            for z in 0..size {
+                let a = cell_types[(x*size + y)*size + z];
+
+                for (dx, dy, dz) in &offsets {
+                    let b = cell_types[(wrap(x, *dx)*size + wrap(y, *dy))*size + wrap(z, *dz)];
+                    counts[a][b] += 1;
+                }
+            }
+        }
+    }
+
+    // When mixed randomly, every one of the (cells * neighbours) pairs has type (a, b) with probability f_a * f_b
+    let pairs = (num_cells * offsets.len()) as f64;
+    let fractions: Vec<f64> = volumes.iter().map(|v| *v as f64 / num_cells as f64).collect();
+
+    (0..num_cell_types).map(|a| {
+        (0..num_cell_types).map(|b| {
+            let expected = pairs * fractions[a] * fractions[b];
+
+            if expected > 0.0 { (counts[a][b] as f64 / expected) as f32 } else { 0.0 }
+        }).collect()
+    }).collect()
+}
@@ -0,0 +1,122 @@
+use std::f32::consts::PI;
+
+use rustfft::{FftDirection, FftPlanner, num_complex::Complex};
+use serde::Serialize;
+
+use crate::appdata::dim3d::automata::automaton_gpu_n_chemicals::CAChemicalGroup;
+use crate::appdata::dim3d::automata::stencil::chemical_stencil;
+
+use super::structure_factor::{fft_3d, WavenumberShells};
+
+//
+// The linear stability of a homogeneous state under the kernel of one chemical group.
+// A perturbation with wave vector k changes the influence on every cell by the transform of the kernel at k,
+// W(k), times its amplitude. Perturbations for which W(k) is positive reinforce themselves and grow.
+//
+#[derive(Clone, Serialize)]
+pub struct KernelStability {
+    pub num_promoting_neighbours: usize,
+    pub num_demoting_neighbours: usize,
+    // The exact sum of the influences over the stencil, W(0)
+    pub kernel_integral: f32,
+    // W(k) averaged over every shell of wave vectors
+    pub dispersion: Vec<f32>,
+    // The wavenumber (in radians per cell) with the largest W(k), leaving out the homogeneous mode
+    pub fastest_growing_wavenumber: f32,
+    pub growth: f32,
+    // The wavelength 2π / k of the fastest growing perturbation in cells, or 0 if the state is stable
+    pub expected_wavelength: f32,
+    // Whether a perturbation with a finite wavelength grows, and faster than the homogeneous mode:
+    // a pattern is expected to form instead of one cell-type taking over (or disappearing from) the domain
+    pub unstable: bool
+}
+
+#[derive(Clone, Serialize)]
+pub struct StabilityAnalysis {
+    // The wavenumber 2π n / size of every shell n, in radians per cell
+    pub wavenumbers: Vec<f32>,
+    pub chemicals: Vec<KernelStability>
+}
+
+pub fn analyse_stability(chemicals: &[CAChemicalGroup], size: usize) -> StabilityAnalysis {
+    let shells = WavenumberShells::new(size);
+    let mut planner = FftPlanner::<f32>::new();
+
+    let chemicals = chemicals.iter()
+        .map(|c| analyse_kernel(&shells, &mut planner, size, c.promote.range, c.promote.influence, c.demote.range, c.demote.influence))
+        .collect();
+
+    StabilityAnalysis {
+        wavenumbers: shells.wavenumbers(),
+        chemicals
+    }
+}
+
+//
+// The stability under a single promoting and demoting pair, such as the DC and UC of the two-state automata
+//
+pub fn analyse_single_kernel(promote_range: f32, promote_influence: f32, demote_range: f32, demote_influence: f32, size: usize) -> StabilityAnalysis {
+    let shells = WavenumberShells::new(size);
+    let mut planner = FftPlanner::<f32>::new();
+
+    let kernel = analyse_kernel(&shells, &mut planner, size, promote_range, promote_influence, demote_range, demote_influence);
+
+    StabilityAnalysis {
+        wavenumbers: shells.wavenumbers(),
+        chemicals: vec![kernel]
+    }
+}
+
+//
+// The kernel is laid out on a periodic grid of the automaton's size, exactly as the backends apply it:
+// neighbours that reach beyond half of the domain wrap around. Its transform is real, since the stencil is symmetric.
+//
+fn analyse_kernel(shells: &WavenumberShells, planner: &mut FftPlanner<f32>, size: usize, promote_range: f32, promote_influence: f32, demote_range: f32, demote_influence: f32) -> KernelStability {
+    let (promote, demote) = chemical_stencil(promote_range, demote_range);
+
+    let mut kernel = vec![Complex::new(0f32, 0f32); size*size*size];
+
+    let wrap = |c: i32| c.rem_euclid(size as i32) as usize;
+
+    for (neighbours, influence) in [(&promote, promote_influence), (&demote, demote_influence)] {
+        for (x, y, z) in neighbours {
+            kernel[(wrap(*x)*size + wrap(*y))*size + wrap(*z)].re += influence;
+        }
+    }
+
+    fft_3d(planner, size, &mut kernel, FftDirection::Forward);
+
+    let dispersion = shells.radial_average(kernel.iter().map(|value| value.re));
+
+    // The fastest growing perturbation, refined with a parabola through the neighbouring shells
+    let d = &dispersion;
+    let peak = (1..d.len()).max_by(|a, b| d[*a].total_cmp(&d[*b])).unwrap_or(0);
+
+    let mut position = peak as f32;
+    let mut growth = d[peak];
+
+    if peak > 1 && peak + 1 < d.len() {
+        let curvature = d[peak - 1] - 2.0 * d[peak] + d[peak + 1];
+
+        if curvature < 0.0 {
+            let offset = 0.5 * (d[peak - 1] - d[peak + 1]) / curvature;
+
+            position += offset;
+            growth -= 0.25 * (d[peak - 1] - d[peak + 1]) * offset;
+        }
+    }
+
+    let fastest_growing_wavenumber = 2.0 * PI * position / size as f32;
+    let unstable = growth > 0.0 && growth > dispersion[0];
+
+    KernelStability {
+        num_promoting_neighbours: promote.len(),
+        num_demoting_neighbours: demote.len(),
+        kernel_integral: promote.len() as f32 * promote_influence + demote.len() as f32 * demote_influence,
+        dispersion,
+        fastest_growing_wavenumber,
+        growth,
+        expected_wavelength: if unstable { 2.0 * PI / fastest_growing_wavenumber } else { 0.0 },
+        unstable
+    }
+}
@@ -0,0 +1,199 @@
+use std::f32::consts::PI;
+
+use rustfft::{FftDirection, FftPlanner, num_complex::Complex};
+use serde::Serialize;
+
+use crate::appdata::dim3d::automata::automaton::CellularAutomaton3D;
+
+use super::difference::cell_types_of;
+
+//
+// The radially averaged structure factor of one cell-type: the power spectrum of its indicator field
+// (1 inside, 0 outside, with the mean subtracted), averaged over shells of equal wavenumber.
+// Shell n contains the wave vectors with a length closest to n periods per domain, up to the Nyquist limit.
+//
+#[derive(Clone, Serialize)]
+pub struct CellTypeStructureFactor {
+    // S(k) for every shell, normalised by the number of cells
+    pub structure_factor: Vec<f32>,
+    // The wavenumber of the peak of S(k), in radians per cell
+    pub dominant_wavenumber: f32,
+    // The wavelength 2π / k of the peak, in cells: directly comparable with the ranges of the chemicals
+    pub dominant_wavelength: f32,
+    // The full width at half maximum of the peak, in radians per cell
+    pub peak_width: f32
+}
+
+#[derive(Clone, Serialize)]
+pub struct StructureFactorMeasurement {
+    pub iteration: u32,
+    // The wavenumber 2π n / size of every shell n, in radians per cell
+    pub wavenumbers: Vec<f32>,
+    pub cell_types: Vec<CellTypeStructureFactor>
+}
+
+pub fn measure_structure_factor<A: CellularAutomaton3D + ?Sized>(automaton: &A, num_cell_types: usize) -> StructureFactorMeasurement {
+    let size = automaton.size();
+
+    let cell_types = cell_types_of(automaton);
+
+    let shells = WavenumberShells::new(size);
+
+    let mut planner = FftPlanner::<f32>::new();
+
+    let cell_types = (0..num_cell_types).map(|cell_type| {
+        let fraction = cell_types.iter().filter(|c| **c == cell_type as u32).count() as f32 / cell_types.len() as f32;
+
+        let mut field: Vec<Complex<f32>> = cell_types.iter()
+            .map(|c| Complex::new(if *c == cell_type as u32 { 1.0 - fraction } else { -fraction }, 0.0))
+            .collect();
+
+        fft_3d(&mut planner, size, &mut field, FftDirection::Forward);
+
+        let structure_factor = shells.radial_average(field.iter().map(|value| value.norm_sqr() / cell_types.len() as f32));
+
+        analyse_peak(structure_factor, size)
+    }).collect();
+
+    StructureFactorMeasurement {
+        iteration: automaton.get_iteration_count(),
+        wavenumbers: shells.wavenumbers(),
+        cell_types
+    }
+}
+
+//
+// Find the peak of S(k), leaving out shell 0, which only contains the (subtracted) mean.
+// Its position is refined with a parabola through the neighbouring shells, and its width is the distance
+// between the points left and right of the peak where S(k) drops below half of the maximum.
+// A cell-type that's uniform has no peak, in which case the wavenumber, wavelength and width are 0.
+//
+fn analyse_peak(structure_factor: Vec<f32>, size: usize) -> CellTypeStructureFactor {
+    let to_wavenumber = |n: f32| 2.0 * PI * n / size as f32;
+
+    let peak = (1..structure_factor.len()).max_by(|a, b| structure_factor[*a].total_cmp(&structure_factor[*b]));
+
+    let peak = match peak {
+        Some(peak) if structure_factor[peak] > 0.0 => peak,
+        _ => return CellTypeStructureFactor { structure_factor, dominant_wavenumber: 0.0, dominant_wavelength: 0.0, peak_width: 0.0 }
+    };
+
+    let s = &structure_factor;
+
+    let mut position = peak as f32;
+
+    if peak > 1 && peak + 1 < s.len() {
+        let curvature = s[peak - 1] - 2.0 * s[peak] + s[peak + 1];
+
+        if curvature < 0.0 {
+            position += 0.5 * (s[peak - 1] - s[peak + 1]) / curvature;
+        }
+    }
+
+    let half = s[peak] / 2.0;
+
+    // Interpolate linearly between the last shell above half the maximum and the first one below it
+    let crossing = |from: usize, to: usize| (half - s[from]) / (s[to] - s[from]) * (to as f32 - from as f32) + from as f32;
+
+    let left = (1..peak).rev().find(|n| s[*n] < half).map(|n| crossing(n + 1, n)).unwrap_or(1.0);
+    let right = (peak + 1..s.len()).find(|n| s[*n] < half).map(|n| crossing(n - 1, n)).unwrap_or((s.len() - 1) as f32);
+
+    let dominant_wavenumber = to_wavenumber(position);
+
+    CellTypeStructureFactor {
+        dominant_wavenumber,
+        dominant_wavelength: 2.0 * PI / dominant_wavenumber,
+        peak_width: to_wavenumber(right - left),
+        structure_factor
+    }
+}
+
+//
+// The wave vectors of a periodic grid, grouped into shells: shell n contains the wave vectors with a length
+// closest to n periods per domain, up to the Nyquist limit. The wave vectors are in the order of the FFT.
+//
+pub(super) struct WavenumberShells {
+    size: usize,
+    shells: Vec<Option<usize>>,
+    counts: Vec<u32>
+}
+
+impl WavenumberShells {
+
+    pub fn new(size: usize) -> Self {
+        let num_shells = size / 2 + 1;
+
+        let frequency = |i: usize| if i <= size / 2 { i as f32 } else { i as f32 - size as f32 };
+
+        let mut shells: Vec<Option<usize>> = vec![None; size*size*size];
+        let mut counts = vec![0u32; num_shells];
+
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    let n = (frequency(x).powi(2) + frequency(y).powi(2) + frequency(z).powi(2)).sqrt().round() as usize;
+
+                    if n < num_shells {
+                        shells[(x*size + y)*size + z] = Some(n);
+                        counts[n] += 1;
+                    }
+                }
+            }
+        }
+
+        WavenumberShells { size, shells, counts }
+    }
+
+    // The wavenumber 2π n / size of every shell n, in radians per cell
+    pub fn wavenumbers(&self) -> Vec<f32> {
+        (0..self.counts.len()).map(|n| 2.0 * PI * n as f32 / self.size as f32).collect()
+    }
+
+    // The average of the values of all wave vectors in every shell
+    pub fn radial_average(&self, values: impl Iterator<Item = f32>) -> Vec<f32> {
+        let mut average = vec![0f32; self.counts.len()];
+
+        for (value, shell) in values.zip(&self.shells) {
+            if let Some(n) = shell {
+                average[*n] += value;
+            }
+        }
+
+        for (a, count) in average.iter_mut().zip(&self.counts) {
+            *a /= *count as f32;
+        }
+
+        average
+    }
+
+}
+
+//
+// The in-place discrete Fourier transform of a field in C order, one axis at a time.
+// Neither direction is normalised: an inverse transform after a forward one multiplies the field by size³.
+//
+pub(super) fn fft_3d(planner: &mut FftPlanner<f32>, size: usize, field: &mut [Complex<f32>], direction: FftDirection) {
+    let fft = planner.plan_fft(size, direction);
+
+    // Along z, the lines are contiguous
+    fft.process(field);
+
+    let mut line = vec![Complex::new(0f32, 0f32); size];
+
+    for stride in [size, size*size] {
+        for start in 0..size*size {
+            // The first cell of every line along y (stride size) or x (stride size²)
+            let first = if stride == size { (start / size) * size * size + start % size } else { start };
+
+            for i in 0..size {
+                line[i] = field[first + i * stride];
+            }
+
+            fft.process(&mut line);
+
+            for i in 0..size {
+                field[first + i * stride] = line[i];
+            }
+        }
+    }
+}
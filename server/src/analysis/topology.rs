@@ -0,0 +1,89 @@
+use serde::Serialize;
+
+use crate::appdata::dim3d::automata::automaton::CellularAutomaton3D;
+
+use super::components::Connectivity;
+use super::cubical_complex::count_cells;
+use super::difference::cell_types_of;
+use super::periodic_union_find::{add_to_basis, PeriodicUnionFind};
+
+//
+// The topology of the union of all (closed) voxels of one cell-type, as a cubical complex on the 3-torus.
+// Betti numbers are over the rationals: β0 counts the components, β1 the tunnels and β2 the cavities,
+// together with sheets that wrap around the domain.
+//
+#[derive(Clone, Serialize)]
+pub struct CellTypeTopology {
+    pub euler_characteristic: i64,
+    pub betti_0: u64,
+    pub betti_1: u64,
+    pub betti_2: u64,
+    // Only 1 when the cell-type fills the whole domain, in which case its Betti numbers are those of the torus: 1, 3, 3, 1
+    pub betti_3: u64
+}
+
+#[derive(Clone, Serialize)]
+pub struct TopologyMeasurement {
+    pub iteration: u32,
+    pub cell_types: Vec<CellTypeTopology>
+}
+
+pub fn measure_topology<A: CellularAutomaton3D + ?Sized>(automaton: &A, num_cell_types: usize) -> TopologyMeasurement {
+    let size = automaton.size();
+
+    let cell_types = cell_types_of(automaton);
+
+    let cell_types = (0..num_cell_types).map(|cell_type| {
+        let inside: Vec<bool> = cell_types.iter().map(|c| *c == cell_type as u32).collect();
+        cell_type_topology(size, &inside)
+    }).collect();
+
+    TopologyMeasurement {
+        iteration: automaton.get_iteration_count(),
+        cell_types
+    }
+}
+
+//
+// The Euler characteristic is counted directly on the cubical complex. β0 follows from the 26-connected
+// components of the voxels, and β2 from the 6-connected components of the complement via duality:
+// the exact sequence of the pair (torus, complex) together with Lefschetz duality gives
+//   β2 = (components of the complement) - 1 + β3 + 3 - (rank of the windings of loops in the complement).
+// β1 then follows from the Euler characteristic.
+//
+fn cell_type_topology(size: usize, inside: &[bool]) -> CellTypeTopology {
+    let euler_characteristic = count_cells(size, inside).euler_characteristic();
+
+    let voxels = PeriodicUnionFind::label(size, &Connectivity::Corner.forward_offsets(), |a, b| inside[a] && inside[b]);
+    let complement = PeriodicUnionFind::label(size, &Connectivity::Face.forward_offsets(), |a, b| !inside[a] && !inside[b]);
+
+    let mut betti_0 = 0;
+    let mut complement_components = 0;
+    let mut complement_windings: Vec<[i64; 3]> = vec![];
+
+    for cell in 0..inside.len() {
+        if inside[cell] {
+            if voxels.is_root(cell) {
+                betti_0 += 1;
+            }
+        } else if complement.is_root(cell) {
+            complement_components += 1;
+
+            for winding in complement.windings(cell) {
+                add_to_basis(&mut complement_windings, *winding);
+            }
+        }
+    }
+
+    let betti_3 = if complement_components == 0 { 1 } else { 0 };
+    let betti_2 = complement_components + betti_3 + 2 - complement_windings.len() as i64;
+    let betti_1 = betti_0 + betti_2 - betti_3 - euler_characteristic;
+
+    CellTypeTopology {
+        euler_characteristic,
+        betti_0: betti_0 as u64,
+        betti_1: betti_1 as u64,
+        betti_2: betti_2 as u64,
+        betti_3: betti_3 as u64
+    }
+}
@@ -1 +1,4 @@
-pub mod dim3d;
\ No newline at end of file
+pub mod dim3d;
+pub mod jobs;
+pub mod palette;
+pub mod sessions;
\ No newline at end of file
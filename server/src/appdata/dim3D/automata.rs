@@ -1,4 +1,7 @@
 pub mod automaton;
 pub mod automaton_cpu;
 pub mod automaton_gpu;
-pub mod automaton_gpu_n_chemicals;
\ No newline at end of file
+pub mod automaton_gpu_n_chemicals;
+pub mod counter_rng;
+pub mod observer;
+pub mod stencil;
\ No newline at end of file
@@ -1,5 +1,9 @@
+use crate::analysis::difference::{cell_types_of, differing_positions};
 use crate::gltfgeneration::gltf_conversion::generate_gltf;
-use crate::gltfgeneration::gltf_generation::generate_large_gltf;
+use crate::volumeio::npy::{NpyArray, NpyData};
+use crate::meshgeneration::{mesh_format::{MeshFormat, encode_mesh}, mesh_options::MeshOptions, triangle_mesh::TriangleMesh, cell_type_source::extract_cell_type_mesh_with, postprocessing::postprocess};
+use crate::appdata::palette::cell_type_colour;
+use miette::Result;
 
 use super::automaton_cpu::MeshTriangle;
 
@@ -26,41 +30,23 @@ pub trait CellularAutomaton3D {
     }
     fn spread_chemicals_randomly(&mut self, chem: u32);
     fn run_iteration(&mut self);
+    // Run an iteration, even if the automaton would otherwise stop running because it has converged
+    fn force_iteration(&mut self) {
+        self.run_iteration();
+    }
+    // Stop taking measurements after every iteration, for copies of which only the state is used
+    fn stop_recording(&mut self) {}
     fn set_iteration_count(&mut self, iterations: u32);
     fn get_iteration_count(&self) -> u32;
-    fn compare(&self, other: &dyn CellularAutomaton3D) -> bool {
-        // If the automata are of different size, they can't be equal
-        if self.size() != other.size() {
-            return false;
-        }
-
-        // If the automata are at a different iteration-count, they can't be equal
-        if self.get_iteration_count() != other.get_iteration_count() {
-            return false;
-        }
-
-        // Otherwise, continue and check the values of every cell
-        for x in 0..self.size() {
-            for y in 0..self.size() {
-                for z in 0..self.size() {
-                    if self.get(x, y, z) != other.get(x, y, z) {
-                        return false;
-                    }
-                }
-            }
-        }
-
-        // No mismatches have been found, return true
-        true
+    // The positions at which the cell-types of two automata of the same size differ
+    fn differences(&self, other: &dyn CellularAutomaton3D) -> Vec<[usize; 3]> {
+        differing_positions(self.size(), &cell_types_of(self), &cell_types_of(other))
     }
 
     // Methods concerned with Marching Cubes
     fn mc_extract(&self, vertices: &mut Vec<f32>, indices: &mut Vec<u32>);
-    fn get_marching_cubes_mesh(&self) -> String {
-        // Create a vector that stores all the triangles that form the surface between the two chemicals.
-        let mut all_triangles: Vec<[f32; 3]> = vec![];
-
-        // Loop over all voxels in the cellular automaton
+    fn get_marching_cubes_mesh(&self) -> TriangleMesh {
+        // Extract the surface between the captured cell-type and all other cells
         let mut vertices: Vec<f32> = vec![];
         let mut indices: Vec<u32> = vec![];
 
@@ -70,50 +56,49 @@ pub trait CellularAutomaton3D {
 
         println!("Vertices and indices extracted:\n\tVertices: {}\n\tIndices: {}", vertices.len(), indices.len());
 
-        // 'vertices' contains (x, y, z) values in sequential manner
-        // 'indices' creates triangles by indexing three vertices sequentially
-        
-        // 1. Transform 'vertices' into (x,y,z) coordinates (group by 3)
-        if vertices.len() % 3 != 0 {
-            panic!("Marching Cubes: vertices array length not multiple of three");
-        }
-
-        let mut vertices_coords: Vec<[f32; 3]> = vec![];
-
-        for v in (0..vertices.len()).step_by(3) {
-            // v:   x
-            // v+1: y
-            // v+2: z
-            vertices_coords.push([vertices[v] * self.size() as f32, vertices[v+1] * self.size() as f32, vertices[v+2] * self.size() as f32]);
-        }
-
-        // 2. Transform 'indices' into triangles (group by three vertices)
-        if indices.len() % 3 != 0 {
-            panic!("Marching Cubes: indices array length not multiple of three");
+        // 'vertices' contains (x, y, z) values in sequential manner, sampled in the unit cube
+        // 'indices' creates triangles by indexing three vertices sequentially.
+        // The vertices are shared between triangles and are scaled to the size of the automaton.
+        TriangleMesh::from_flat(&vertices, indices, self.size() as f32)
+    }
+    fn get_marching_cubes_mesh_with(&self, options: &MeshOptions) -> Result<TriangleMesh> {
+        options.validate(self.size())?;
+
+        // Other extractors, periodic or smooth surfaces are extracted around the captured cell-type directly,
+        // since the Source implementations of the automata only sample the nearest cell in the unit cube.
+        if options.uses_automaton_source() {
+            Ok(postprocess(self.get_marching_cubes_mesh(), options))
+        } else {
+            extract_cell_type_mesh_with(self, self.captured_cell_type(), options)
         }
+    }
+    fn get_marching_cubes_mesh_as(&self, format: MeshFormat, options: &MeshOptions) -> Result<Vec<u8>> {
+        let mesh = self.get_marching_cubes_mesh_with(options)?;
 
-        for i in (0..indices.len()).step_by(3) {
-            // i:   vertex 1
-            // i+1: vertex 2
-            // i+2: vertex 3
-            all_triangles.push(vertices_coords[indices[i] as usize]);
-            all_triangles.push(vertices_coords[indices[i+1] as usize]);
-            all_triangles.push(vertices_coords[indices[i+2] as usize]);
-            //     MeshTriangle {
-            //         vertices: [
-            //             vertices_coords[indices[i] as usize],
-            //             vertices_coords[indices[i+1] as usize],
-            //             vertices_coords[indices[i+2] as usize]
-            //         ]
-            //     }
-            // );
-        }
+        encode_mesh(&mesh, cell_type_colour(self.captured_cell_type() as usize), format)
+    }
+    // The cell-type that is enclosed by the marching cubes surface
+    fn captured_cell_type(&self) -> u32 {
+        1
+    }
+    // The influence that the chemicals of a cell-type exerted on every cell during the last iteration,
+    // in C order. Only automata that keep track of their influences provide it.
+    fn influence_field(&self, _cell_type: u32) -> Option<Vec<f32>> {
+        None
+    }
 
-        if all_triangles.len() > 0 {
-            return generate_large_gltf(&all_triangles.as_slice()).unwrap();
+    // Export the cell-types as a (size, size, size) NumPy array in C order, so that
+    // array[x, y, z] holds the cell-type at (x, y, z).
+    fn get_npy_state(&self, uint16: bool) -> NpyArray {
+        let size = self.size();
+        let shape = vec![size, size, size];
+        let cell_types = cell_types_of(self);
+
+        if uint16 {
+            NpyArray::new(shape, NpyData::U16(cell_types.iter().map(|c| *c as u16).collect()))
+        } else {
+            NpyArray::new(shape, NpyData::U8(cell_types.iter().map(|c| *c as u8).collect()))
         }
-
-        String::from("{}")
     }
 
     fn state_fully_dominated(&self) -> bool {
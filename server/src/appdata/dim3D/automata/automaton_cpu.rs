@@ -5,6 +5,8 @@ use rand::prelude::*;
 
 use super::super::grid::CAGrid3D;
 use super::automaton::CellularAutomaton3D;
+use super::counter_rng::StochasticUpdate;
+use super::stencil::{EvaluationMode, fixed_point_influence, FIXED_POINT_ONE};
 use serde::{Serialize, Deserialize};
 
 use isosurface::marching_cubes::MarchingCubes;
@@ -27,7 +29,12 @@ pub struct CPUCellularAutomaton3D {
     pub dc_influence: f32,
     pub uc_range: f32,
     pub uc_influence: f32,
-    iteration_count: u32
+    iteration_count: u32,
+    #[serde(default)]
+    pub evaluation_mode: EvaluationMode,
+    // If set, cells only apply the rules with a probability during every iteration
+    #[serde(default)]
+    pub stochastic_update: Option<StochasticUpdate>
 }
 
 impl CPUCellularAutomaton3D {
@@ -40,13 +47,19 @@ impl CPUCellularAutomaton3D {
             dc_influence,
             uc_range,
             uc_influence,
-            iteration_count: 0
+            iteration_count: 0,
+            evaluation_mode: EvaluationMode::Float,
+            stochastic_update: None
         }
     }
 
     fn total_influence(&self, px: usize, py: usize, pz: usize) -> f32 {
         let mut sum: f32 = 0.0;
 
+        // The number of DC and UC neighbours, for the exact evaluation
+        let mut num_dc: u32 = 0;
+        let mut num_uc: u32 = 0;
+
         let size_i = self.size() as i32;
 
         // UC has a larger range than DC, so pull it up to the closest larger integer and use it as range
@@ -112,20 +125,26 @@ impl CPUCellularAutomaton3D {
                         if dist <= self.dc_range {
                             // Add the DC-influence to the sum
                             sum += self.dc_influence;
+                            num_dc += 1;
                         }
 
                         // Else: if this point falls within the range of Undifferentiated Cells
                         else if dist <= self.uc_range {
                             // Add the UC-influence to the sum
                             sum += self.uc_influence;
+                            num_uc += 1;
                         }
                     }
                     
                 }
             }
         }
-    
-        sum
+
+        match self.evaluation_mode {
+            EvaluationMode::Float => sum,
+            // The conversion keeps the sign, which is all that matters for the new state
+            EvaluationMode::Exact => fixed_point_influence(num_dc, self.dc_influence, num_uc, self.uc_influence) as f32 / FIXED_POINT_ONE
+        }
     }
 
     fn start_thread(automaton: CPUCellularAutomaton3D, computed_influences: Arc<Mutex<Vec<Vec<Vec<f32>>>>>, xmin: usize, xmax: usize) -> std::thread::JoinHandle<()> {
@@ -266,8 +285,15 @@ impl CellularAutomaton3D for CPUCellularAutomaton3D {
                 for z in 0..size {
                 
                     let influence = influence_results[x][y][z];
-        
-                    if influence > 0.0 {
+
+                    // The random number of every cell only depends on its position, like in the shaders
+                    let updates = self.stochastic_update.map_or(true, |stochastic| {
+                        stochastic.updates(self.iteration_count, (x + y*size + z*size*size) as u32)
+                    });
+
+                    if !updates {
+                        self.curr_generation.set(x, y, z, self.prev_generation.get(x, y, z));
+                    } else if influence > 0.0 {
                         self.curr_generation.set(x, y, z, 0);
                     } else if influence < 0.0 {
                         self.curr_generation.set(x, y, z, 1);
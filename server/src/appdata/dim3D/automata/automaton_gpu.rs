@@ -1,4 +1,4 @@
-use super::{automaton::CellularAutomaton3D, automaton_cpu::MeshTriangle};
+use super::{automaton::CellularAutomaton3D, automaton_cpu::MeshTriangle, counter_rng::StochasticUpdate, stencil::{chemical_stencil, kernel_integral, to_fixed_point, EvaluationMode}};
 
 use isosurface::{source::Source, marching_cubes::MarchingCubes};
 use serde::{Serialize, Deserialize};
@@ -11,7 +11,8 @@ use std::mem;
 
 use crate::{AUTOMATON_SIZE, routes::gpu_get};
 
-const AUTOMATON_SHADER_SRC: &str = include_str!("automaton_shader.metal");
+// The shader draws its random numbers with the counter-based generator
+const AUTOMATON_SHADER_SRC: &str = concat!(include_str!("counter_rng.metal"), include_str!("automaton_shader.metal"));
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct GPUCellularAutomaton3D {
@@ -20,7 +21,12 @@ pub struct GPUCellularAutomaton3D {
     pub dc_influence: f32,
     pub uc_range: f32,
     pub uc_influence: f32,
-    iteration_count: u32
+    iteration_count: u32,
+    #[serde(default)]
+    pub evaluation_mode: EvaluationMode,
+    // If set, cells only apply the rules with a probability during every iteration
+    #[serde(default)]
+    pub stochastic_update: Option<StochasticUpdate>
 }
 
 impl GPUCellularAutomaton3D {
@@ -32,10 +38,19 @@ impl GPUCellularAutomaton3D {
             dc_influence,
             uc_range,
             uc_influence,
-            iteration_count: 0
+            iteration_count: 0,
+            evaluation_mode: EvaluationMode::Float,
+            stochastic_update: None
         }
     }
 
+    //
+    // The net influence of all neighbours on a voxel that's completely surrounded by differentiated cells
+    //
+    pub fn kernel_integral(&self) -> f32 {
+        kernel_integral(self.dc_range, self.dc_influence, self.uc_range, self.uc_influence)
+    }
+
     // fn export(&self) -> [u8; AUTOMATON_SIZE*AUTOMATON_SIZE*AUTOMATON_SIZE] {
 
     //     let mut res = [0u8; AUTOMATON_SIZE*AUTOMATON_SIZE*AUTOMATON_SIZE];
@@ -151,7 +166,10 @@ impl CellularAutomaton3D for GPUCellularAutomaton3D {
             let command_queue = device.new_command_queue();
 
             let data = self.export();
-            let chemicals = [self.dc_range, self.dc_influence, self.uc_range, self.uc_influence];
+            // Cells that don't update stochastically always apply the rules
+            let update_probability = self.stochastic_update.map_or(1.0, |stochastic| stochastic.update_probability);
+
+            let chemicals = [self.dc_range, self.dc_influence, self.uc_range, self.uc_influence, update_probability];
 
             let buffer = device.new_buffer_with_data(
                 unsafe { mem::transmute(data.as_ptr()) },
@@ -178,44 +196,14 @@ impl CellularAutomaton3D for GPUCellularAutomaton3D {
             };
 
             // Computing relative neighbours: DC and UC
-            let mut dc_neighbours_x: Vec<i32> = vec![];
-            let mut dc_neighbours_y: Vec<i32> = vec![];
-            let mut dc_neighbours_z: Vec<i32> = vec![];
-            let mut uc_neighbours_x: Vec<i32> = vec![];
-            let mut uc_neighbours_y: Vec<i32> = vec![];
-            let mut uc_neighbours_z: Vec<i32> = vec![];
-
-            // UC has a larger range than DC, so pull it up to the closest larger integer and use it as range
-            let uc_range = f32::ceil(self.uc_range) as i32 + 2; // +1 as the x..y excludes y
-
-            for x in -uc_range..uc_range {
-                for y in -uc_range..uc_range {
-                    for z in -uc_range..uc_range {
-                        // Compute the distance from the point (0, 0, 0)
-                        let dist = f32::sqrt((x*x + y*y + z*z) as f32);
-
-                        // A voxel cannot be its own neighbour, so (0, 0, 0) must be excluded from the neighbour-pack
-                        if !(x == 0 && y == 0 && z == 0) {
-                            // If this point falls within the range of Differentiated Cells
-                            if dist <= self.dc_range {
-                                // Append it to the dc_neighbour pack
-                                dc_neighbours_x.push(x);
-                                dc_neighbours_y.push(y);
-                                dc_neighbours_z.push(z);
-                            }
-
-                            // Else: if this point falls within the range of Undifferentiated Cells
-                            else if dist <= self.uc_range {
-                                // Append it to the uc_neighbour pack
-                                uc_neighbours_x.push(x);
-                                uc_neighbours_y.push(y);
-                                uc_neighbours_z.push(z);
-                            }
-                        }
-                        
-                    }
-                }
-            }
+            let (dc_neighbours, uc_neighbours) = chemical_stencil(self.dc_range, self.uc_range);
+
+            let dc_neighbours_x: Vec<i32> = dc_neighbours.iter().map(|n| n.0).collect();
+            let dc_neighbours_y: Vec<i32> = dc_neighbours.iter().map(|n| n.1).collect();
+            let dc_neighbours_z: Vec<i32> = dc_neighbours.iter().map(|n| n.2).collect();
+            let uc_neighbours_x: Vec<i32> = uc_neighbours.iter().map(|n| n.0).collect();
+            let uc_neighbours_y: Vec<i32> = uc_neighbours.iter().map(|n| n.1).collect();
+            let uc_neighbours_z: Vec<i32> = uc_neighbours.iter().map(|n| n.2).collect();
 
             let arg_dc_neighbours_x = device.new_buffer_with_data(
                 unsafe { mem::transmute(dc_neighbours_x.as_slice().as_ptr()) },
@@ -255,10 +243,18 @@ impl CellularAutomaton3D for GPUCellularAutomaton3D {
 
             println!("Considering {} dc neighbours and {} uc neighbours", dc_neighbours_x.len(), uc_neighbours_x.len());
 
-            println!("The integral of influences over neighbours is {}", dc_neighbours_x.len() as f32 * self.dc_influence + uc_neighbours_x.len() as f32 * self.uc_influence);
+            println!("The integral of influences over neighbours is {}", self.kernel_integral());
+
+            // The size, the number of DC and UC neighbours, whether the influences are evaluated exactly,
+            // and whether the cells update stochastically: with which seed (low and high bits) during which iteration
+            let seed = self.stochastic_update.map_or(0, |stochastic| stochastic.seed);
 
             let arg_size_container = {
-                let data: [u32; 3] = [AUTOMATON_SIZE as u32, dc_neighbours_x.len() as u32, uc_neighbours_x.len() as u32];
+                let data: [u32; 8] = [
+                    AUTOMATON_SIZE as u32, dc_neighbours_x.len() as u32, uc_neighbours_x.len() as u32,
+                    (self.evaluation_mode == EvaluationMode::Exact) as u32,
+                    self.stochastic_update.is_some() as u32, seed as u32, (seed >> 32) as u32, self.iteration_count
+                ];
                 device.new_buffer_with_data(
                     unsafe { mem::transmute(data.as_ptr()) },
                     (data.len() * mem::size_of::<u32>()) as u64,
@@ -266,6 +262,16 @@ impl CellularAutomaton3D for GPUCellularAutomaton3D {
                 )
             };
 
+            // The fixed-point influences for the exact evaluation
+            let arg_fixed_point_influences = {
+                let data: [i32; 2] = [to_fixed_point(self.dc_influence), to_fixed_point(self.uc_influence)];
+                device.new_buffer_with_data(
+                    unsafe { mem::transmute(data.as_ptr()) },
+                    (data.len() * mem::size_of::<i32>()) as u64,
+                    MTLResourceOptions::CPUCacheModeDefaultCache
+                )
+            };
+
             let command_buffer = command_queue.new_command_buffer();
             let encoder = command_buffer.new_compute_command_encoder();
 
@@ -290,6 +296,7 @@ impl CellularAutomaton3D for GPUCellularAutomaton3D {
             argument_encoder.set_buffer(7, &arg_uc_neighbours_x, 0);
             argument_encoder.set_buffer(8, &arg_uc_neighbours_y, 0);
             argument_encoder.set_buffer(9, &arg_uc_neighbours_z, 0);
+            argument_encoder.set_buffer(10, &arg_fixed_point_influences, 0);
 
             let pipeline_state_descriptor = ComputePipelineDescriptor::new();
             pipeline_state_descriptor.set_compute_function(Some(&kernel));
@@ -313,6 +320,7 @@ impl CellularAutomaton3D for GPUCellularAutomaton3D {
             encoder.use_resource(&arg_uc_neighbours_x, MTLResourceUsage::Read);
             encoder.use_resource(&arg_uc_neighbours_y, MTLResourceUsage::Read);
             encoder.use_resource(&arg_uc_neighbours_z, MTLResourceUsage::Read);
+            encoder.use_resource(&arg_fixed_point_influences, MTLResourceUsage::Read);
 
             
             
@@ -1,4 +1,7 @@
 use super::automaton::CellularAutomaton3D;
+use super::counter_rng::StochasticUpdate;
+use super::observer::{Metric, MetricSample, MetricStore, Observer};
+use super::stencil::{chemical_stencil, to_fixed_point, EvaluationMode, Neighbourhood};
 
 use isosurface::{source::Source, marching_cubes::MarchingCubes};
 use serde::{Serialize, Deserialize};
@@ -9,10 +12,15 @@ use rand_chacha::ChaCha8Rng;
 use metal::*;
 use objc::rc::autoreleasepool;
 use std::mem;
+use std::sync::Arc;
 
 use crate::{AUTOMATON_SIZE, routes::gpu_get, K_MAX};
+use crate::analysis::geometry::{GeometryMeasurement, measure_geometry};
+use crate::analysis::segregation::segregation_matrix;
+use crate::analysis::structure_factor::{StructureFactorMeasurement, measure_structure_factor};
 
-const AUTOMATON_SHADER_SRC: &str = include_str!("automaton_n_chemicals_shader.metal");
+// The shader draws its random numbers with the counter-based generator
+const AUTOMATON_SHADER_SRC: &str = concat!(include_str!("counter_rng.metal"), include_str!("automaton_n_chemicals_shader.metal"));
 const ORDER_PARAM_SHADER_SRC: &str = include_str!("order_param_n_chemicals_shader.metal");
 
 
@@ -39,6 +47,35 @@ pub struct CAChemicalGroup {
 
 
 
+//
+// The cells that changed their type during one iteration
+//
+#[derive(Clone, Serialize)]
+pub struct FlipActivity {
+    pub iteration: u32,
+    pub flips: u64,
+    // flip_matrix[a][b] is the number of cells that changed from type a to type b. Its diagonal is 0.
+    pub flip_matrix: Vec<Vec<u64>>
+}
+
+
+
+//
+// The part of the automaton that an iteration changes, from which an iteration that failed halfway can be undone.
+// The histories are only appended to, so their lengths suffice.
+//
+pub struct Checkpoint {
+    grid: Vec<Vec<Vec<u8>>>,
+    iteration_count: u32,
+    converged: bool,
+    last_changed: Vec<u32>,
+    num_geometry: usize,
+    num_structure_factor: usize,
+    num_segregation: usize,
+    num_flip_activity: usize
+}
+
+
 //
 // This is the main struct that encapsulates the gpu-implementation of this generalisation
 //
@@ -48,8 +85,47 @@ pub struct GPUNChemicalsCellularAutomaton3D {
     pub chemicals: Vec<CAChemicalGroup>,
     iteration_count: u32,
     marching_cubes_chemical_capture: usize,
-    order_parameter: Vec<Vec<f32>>,
-    pub converged: bool
+    // The neighbours that every cell is compared with when computing the order parameter
+    #[serde(default)]
+    pub order_parameter_neighbourhood: Neighbourhood,
+    // How the influences of the chemicals on every cell are evaluated
+    #[serde(default)]
+    pub evaluation_mode: EvaluationMode,
+    // If set, cells only apply the rules with a probability during every iteration
+    #[serde(default)]
+    pub stochastic_update: Option<StochasticUpdate>,
+    pub converged: bool,
+    // The influence of every chemical group on every cell during the last iteration,
+    // organised as [cell0.influence0, cell0.influence1, ...] with cells in the order of 'export'.
+    #[serde(skip)]
+    influence_field: Vec<f32>,
+    // If set, the geometry of all cell-types is measured after every iteration
+    #[serde(skip)]
+    pub record_geometry: bool,
+    #[serde(skip)]
+    geometry: Vec<GeometryMeasurement>,
+    // If set, the structure factor of all cell-types is measured after every iteration
+    #[serde(skip)]
+    pub record_structure_factor: bool,
+    #[serde(skip)]
+    structure_factor: Vec<StructureFactorMeasurement>,
+    // If set, the pairwise segregation of the cell-types over the order parameter neighbourhood,
+    // a (K+1)x(K+1) matrix, is measured after every iteration
+    #[serde(skip)]
+    pub record_segregation: bool,
+    #[serde(skip)]
+    segregation: Vec<Vec<Vec<f32>>>,
+    // The cells that changed their type during every iteration, and the iteration at which every cell
+    // (in C order) changed last, or 0 if it hasn't changed since the state was set
+    #[serde(skip)]
+    flip_activity: Vec<FlipActivity>,
+    #[serde(skip)]
+    last_changed: Vec<u32>,
+    // The registered observers and the time series that they have measured
+    #[serde(skip)]
+    observers: Vec<Arc<dyn Observer>>,
+    #[serde(skip)]
+    metrics: MetricStore
 }
 
 
@@ -66,11 +142,53 @@ impl GPUNChemicalsCellularAutomaton3D {
             chemicals,
             iteration_count: 0,
             marching_cubes_chemical_capture: 0,
-            order_parameter: vec![],
-            converged: false
+            order_parameter_neighbourhood: Neighbourhood::Face,
+            evaluation_mode: EvaluationMode::Float,
+            stochastic_update: None,
+            converged: false,
+            influence_field: vec![],
+            record_geometry: false,
+            geometry: vec![],
+            record_structure_factor: false,
+            structure_factor: vec![],
+            record_segregation: false,
+            segregation: vec![],
+            flip_activity: vec![],
+            last_changed: vec![],
+            observers: vec![Metric::OrderParameter.observer(1)],
+            metrics: MetricStore::default()
+        }
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            grid: self.grid.clone(),
+            iteration_count: self.iteration_count,
+            converged: self.converged,
+            last_changed: self.last_changed.clone(),
+            num_geometry: self.geometry.len(),
+            num_structure_factor: self.structure_factor.len(),
+            num_segregation: self.segregation.len(),
+            num_flip_activity: self.flip_activity.len()
         }
     }
 
+    // Restore the automaton to a checkpoint, forgetting everything that was measured since and the influences of the last iteration
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        self.grid = checkpoint.grid;
+        self.iteration_count = checkpoint.iteration_count;
+        self.converged = checkpoint.converged;
+        self.last_changed = checkpoint.last_changed;
+
+        self.geometry.truncate(checkpoint.num_geometry);
+        self.structure_factor.truncate(checkpoint.num_structure_factor);
+        self.segregation.truncate(checkpoint.num_segregation);
+        self.flip_activity.truncate(checkpoint.num_flip_activity);
+        self.metrics.forget_after(checkpoint.iteration_count);
+
+        self.influence_field = vec![];
+    }
+
     //
     // The capture functions can be used to alter which chemical should be captured by
     // the marching cubes algorithm.
@@ -84,39 +202,126 @@ impl GPUNChemicalsCellularAutomaton3D {
         self.marching_cubes_chemical_capture
     }
 
-    pub fn insert_order_parameter_value(&mut self, val: Vec<f32>) {
-        // println!("Inserting order parameter {}", val);
-
-        self.order_parameter.push(val);
-    }
-
     pub fn get_order_parameters(&self) -> Vec<Vec<f32>> {
-        // In this class, the order parameters are organised as follows:
-        // self.order_parameter is a Vec<Vec<f32>> and contains a Vec<f32> for every iteration.
-        // Every iteration Vec<f32> contains (K+1) entries: K epsilons and the undif. epsilon
-        // This is a good format for collecting order parameters along the way, but not for 
+        // The order parameter is measured by its observer, which stores a Vec<f32> for every sampled iteration.
+        // Every sample contains (K+1) entries: K epsilons and the undif. epsilon
+        // This is a good format for collecting order parameters along the way, but not for
         // sharing them with other parts of the system.
 
         // Here, we'll therefore transform the order parameter into a Vec<Vec<f32>> that contains
-        // a Vec<f32> for every (K+1) cell-types. Each Vec<f32> then contains one f32 for every iteration.
+        // a Vec<f32> for every (K+1) cell-types. Each Vec<f32> then contains one f32 for every sample.
+
+        let samples = self.metrics.query(&Metric::OrderParameter.name(), 0, u32::MAX).unwrap_or_default();
+
+        (0..(self.chemicals.len() + 1)).map(|spec| {
+            samples.iter().map(|sample| sample.values.get(spec).copied().unwrap_or(0.0)).collect()
+        }).collect()
+    }
+
+    // The iterations at which the order parameter was sampled
+    pub fn get_order_parameter_iterations(&self) -> Vec<u32> {
+        let samples = self.metrics.query(&Metric::OrderParameter.name(), 0, u32::MAX).unwrap_or_default();
+
+        samples.iter().map(|sample| sample.iteration).collect()
+    }
+
+    // The segregation matrix of the current state, over the order parameter neighbourhood
+    pub fn get_segregation_matrix(&self) -> Vec<Vec<f32>> {
+        segregation_matrix(self, self.chemicals.len() + 1, self.order_parameter_neighbourhood)
+    }
+
+    //
+    // The observers, such as the order parameter, are measured at the iterations that they sample.
+    // The geometry, structure factor and segregation of the cell-types are measured if they're being recorded.
+    //
+
+    fn record_measurements(&mut self) {
+        self.observe();
+
+        if self.record_geometry {
+            let measurement = measure_geometry(self, self.chemicals.len() + 1);
+            self.geometry.push(measurement);
+        }
+
+        if self.record_structure_factor {
+            let measurement = measure_structure_factor(self, self.chemicals.len() + 1);
+            self.structure_factor.push(measurement);
+        }
+
+        if self.record_segregation {
+            let matrix = self.get_segregation_matrix();
+            self.segregation.push(matrix);
+        }
+    }
+
+    //
+    // Observers are identified by their name: adding an observer replaces any observer with the same name
+    //
+
+    pub fn add_observer(&mut self, observer: Arc<dyn Observer>) {
+        self.remove_observer(&observer.name());
+        self.observers.push(observer);
+    }
+
+    pub fn remove_observer(&mut self, name: &str) -> bool {
+        let num_observers = self.observers.len();
+        self.observers.retain(|o| o.name() != name);
+
+        self.observers.len() != num_observers
+    }
+
+    // Replace all observers, returning the previous ones
+    pub fn set_observers(&mut self, observers: Vec<Arc<dyn Observer>>) -> Vec<Arc<dyn Observer>> {
+        mem::replace(&mut self.observers, observers)
+    }
+
+    // The name and interval of every registered observer
+    pub fn get_observers(&self) -> Vec<(String, u32)> {
+        self.observers.iter().map(|o| (o.name(), o.interval())).collect()
+    }
+
+    pub fn get_metrics(&self) -> &MetricStore {
+        &self.metrics
+    }
 
-        let mut result: Vec<Vec<f32>> = vec![];
+    pub fn get_flip_count(&self) -> u64 {
+        self.flip_activity.last().map_or(0, |activity| activity.flips)
+    }
+
+    pub fn get_flip_activity(&self) -> &Vec<FlipActivity> {
+        &self.flip_activity
+    }
 
-        // Add K+1 empty vectors
-        for spec in 0..(self.chemicals.len() + 1) {
-            result.push(vec![]);
+    // The iteration at which every cell changed last, as a volume of shape (size, size, size) in C order
+    pub fn get_last_changed(&self) -> Vec<u32> {
+        if self.last_changed.is_empty() {
+            vec![0u32; AUTOMATON_SIZE*AUTOMATON_SIZE*AUTOMATON_SIZE]
+        } else {
+            self.last_changed.clone()
         }
+    }
 
-        // For each iteration
-        for iter in 0..self.order_parameter.len() {
-            // Go over all the species again
-            for spec in 0..(self.chemicals.len() + 1) {
-                // Append the next iteration for this species
-                result[spec].push(self.order_parameter[iter][spec]);
+    fn observe(&mut self) {
+        let iteration = self.iteration_count;
+
+        for observer in self.observers.clone() {
+            if iteration % observer.interval().max(1) == 0 {
+                let values = observer.observe(self);
+                self.metrics.record(observer.name(), MetricSample { iteration, values });
             }
         }
+    }
 
-        result
+    pub fn get_geometry_history(&self) -> &Vec<GeometryMeasurement> {
+        &self.geometry
+    }
+
+    pub fn get_structure_factor_history(&self) -> &Vec<StructureFactorMeasurement> {
+        &self.structure_factor
+    }
+
+    pub fn get_segregation_history(&self) -> &Vec<Vec<Vec<f32>>> {
+        &self.segregation
     }
 
     //
@@ -144,21 +349,39 @@ impl GPUNChemicalsCellularAutomaton3D {
         // Upon importing, check if the CA has converged
         let mut convergence_counter: u64 = 0;
 
+        // The imported data is the state after the iteration that is currently running
+        let iteration = self.iteration_count + 1;
+        let num_cell_types = self.chemicals.len() + 1;
+
+        let mut flip_matrix = vec![vec![0u64; num_cell_types]; num_cell_types];
+
+        if self.last_changed.is_empty() {
+            self.last_changed = vec![0u32; AUTOMATON_SIZE*AUTOMATON_SIZE*AUTOMATON_SIZE];
+        }
+
         for x in 0..AUTOMATON_SIZE {
             for y in 0..AUTOMATON_SIZE {
                 for z in 0..AUTOMATON_SIZE {
 
+                    let new_value = data[x + y*AUTOMATON_SIZE + z*AUTOMATON_SIZE*AUTOMATON_SIZE];
+
                     // If one of the cells in these generations differ, this CA has not converged.
-                    if self.grid[x][y][z] != data[x + y*AUTOMATON_SIZE + z*AUTOMATON_SIZE*AUTOMATON_SIZE] {
+                    if self.grid[x][y][z] != new_value {
                         convergence_counter += 1;
+
+                        // Keep track of the transition and when this cell changed
+                        flip_matrix[self.grid[x][y][z] as usize][new_value as usize] += 1;
+                        self.last_changed[(x*AUTOMATON_SIZE + y)*AUTOMATON_SIZE + z] = iteration;
                     }
 
                     // Import the data like normally
-                    self.grid[x][y][z] = data[x + y*AUTOMATON_SIZE + z*AUTOMATON_SIZE*AUTOMATON_SIZE];
+                    self.grid[x][y][z] = new_value;
                 }
             }
         }
 
+        self.flip_activity.push(FlipActivity { iteration, flips: convergence_counter, flip_matrix });
+
         let difference_percentage = convergence_counter as f32 / data.len() as f32;
 
         self.converged = difference_percentage <= 0.01;
@@ -166,6 +389,41 @@ impl GPUNChemicalsCellularAutomaton3D {
     }
 
 
+    //
+    // Replace the state by an externally provided grid of cell-types, such as an imported volume.
+    // The cell-types are indexed as [x][y][z] in row-major (C) order.
+    //
+
+    pub fn load_state(&mut self, cell_types: &[u32]) {
+
+        for x in 0..AUTOMATON_SIZE {
+            for y in 0..AUTOMATON_SIZE {
+                for z in 0..AUTOMATON_SIZE {
+                    self.grid[x][y][z] = cell_types[(x*AUTOMATON_SIZE + y)*AUTOMATON_SIZE + z] as u8;
+                }
+            }
+        }
+
+        // The imported state is the start of a new simulation
+        self.iteration_count = 0;
+
+        // Reset the measurements and take the first ones, forget the influences of the last iteration
+        self.segregation = vec![];
+        self.influence_field = vec![];
+        self.geometry = vec![];
+        self.structure_factor = vec![];
+        self.flip_activity = vec![];
+        self.last_changed = vec![];
+        self.metrics.clear();
+
+        self.record_measurements();
+
+        // Reset the convergence boolean
+        self.converged = false;
+
+    }
+
+
 
     //
     // Calculate the volume that is occupied by each of the cell-types
@@ -207,7 +465,9 @@ impl GPUNChemicalsCellularAutomaton3D {
     // COMPUTING THE ORDER-PARAMETERS
     //
 
-    fn compute_order_parameter(&mut self) {
+    // The order parameter of every cell-type over the order parameter neighbourhood, computed on the GPU.
+    // It's measured by the order parameter observer, only at the iterations that it samples.
+    pub fn compute_order_parameter(&self) -> Vec<f32> {
         autoreleasepool(|| {
 
             let device = Device::system_default().expect("no device found");
@@ -223,16 +483,23 @@ impl GPUNChemicalsCellularAutomaton3D {
             );
 
             let sum = {
-                let data: Vec<i8> = vec![0i8; AUTOMATON_SIZE*AUTOMATON_SIZE*AUTOMATON_SIZE * (K_MAX+1)];
+                let data: Vec<i32> = vec![0i32; AUTOMATON_SIZE*AUTOMATON_SIZE*AUTOMATON_SIZE * (K_MAX+1)];
                 device.new_buffer_with_data(
                     unsafe { mem::transmute(data.as_ptr()) },
-                    (data.len() * mem::size_of::<i8>()) as u64,
+                    (data.len() * mem::size_of::<i32>()) as u64,
                     MTLResourceOptions::CPUCacheModeDefaultCache,
                 )
             };
 
+            // The neighbours that every cell is compared with, organised as [dx0, dy0, dz0, dx1, ...]
+            let neighbours: Vec<i32> = self.order_parameter_neighbourhood.offsets().iter()
+                .flat_map(|n| [n.0, n.1, n.2])
+                .collect();
+
+            let num_neighbours = neighbours.len() / 3;
+
             // There's a couple of size parameters we need to pass over to the gpu
-            let size_container: Vec<u32> = vec![AUTOMATON_SIZE as u32, self.chemicals.len() as u32 + 1];
+            let size_container: Vec<u32> = vec![AUTOMATON_SIZE as u32, self.chemicals.len() as u32 + 1, num_neighbours as u32];
 
             let arg_size_container = {
                 let data = size_container.as_slice();
@@ -243,14 +510,6 @@ impl GPUNChemicalsCellularAutomaton3D {
                 )
             };
 
-            // There should lastly be a buffer that records the neighbours
-            let neighbours: Vec<i32> = vec![-1, 0, 0,
-                                            0, -1, 0,
-                                            0, 0, -1,
-                                            1, 0, 0,
-                                            0, 1, 0,
-                                            0, 0, 1];
-
             let arg_neighbours = {
                 let data = neighbours.as_slice();
                 device.new_buffer_with_data(
@@ -321,13 +580,13 @@ impl GPUNChemicalsCellularAutomaton3D {
 
 
             let mut result: Vec<f32> = vec![];
-            let result_cell_sums: Vec<i8>;
+            let result_cell_sums: Vec<i32>;
 
-            // Define the normalisation constant
-            let normalisation = 6.0 * (AUTOMATON_SIZE*AUTOMATON_SIZE*AUTOMATON_SIZE) as f32;
+            // Define the normalisation constant: every cell is compared with all of its neighbours
+            let normalisation = num_neighbours as f32 * (AUTOMATON_SIZE*AUTOMATON_SIZE*AUTOMATON_SIZE) as f32;
 
             // Extract the obtained sums in the 'result_cell_sums' container
-            let ptr = sum.contents() as *mut [i8; AUTOMATON_SIZE*AUTOMATON_SIZE*AUTOMATON_SIZE * (K_MAX+1)];
+            let ptr = sum.contents() as *mut [i32; AUTOMATON_SIZE*AUTOMATON_SIZE*AUTOMATON_SIZE * (K_MAX+1)];
             unsafe {
                 result_cell_sums = (*ptr).to_vec();
             }
@@ -342,9 +601,9 @@ impl GPUNChemicalsCellularAutomaton3D {
                 }
             }
 
-            self.insert_order_parameter_value(result);
+            result
 
-        });
+        })
 
     }
 
@@ -376,8 +635,14 @@ impl CellularAutomaton3D for GPUNChemicalsCellularAutomaton3D {
         // Reset the iteration count
         self.iteration_count = 0;
 
-        // Reset the order parameter
-        self.order_parameter = vec![];
+        // Reset the measurements and the influences of the last iteration
+        self.segregation = vec![];
+        self.influence_field = vec![];
+        self.geometry = vec![];
+        self.structure_factor = vec![];
+        self.flip_activity = vec![];
+        self.last_changed = vec![];
+        self.metrics.clear();
 
         // Reset the convergence boolean
         self.converged = false;
@@ -390,8 +655,14 @@ impl CellularAutomaton3D for GPUNChemicalsCellularAutomaton3D {
         // Reset the iteration count
         self.iteration_count = 0;
 
-        // Reset the order parameter
-        self.order_parameter = vec![];
+        // Reset the measurements and the influences of the last iteration
+        self.segregation = vec![];
+        self.influence_field = vec![];
+        self.geometry = vec![];
+        self.structure_factor = vec![];
+        self.flip_activity = vec![];
+        self.last_changed = vec![];
+        self.metrics.clear();
 
         // Reset the convergence boolean
         self.converged = false;
@@ -425,10 +696,16 @@ impl CellularAutomaton3D for GPUNChemicalsCellularAutomaton3D {
         // Set the number of iterations identical to 'other'
         self.set_iteration_count(other.get_iteration_count());
 
-        // Reset and recompute the order parameter
-        self.order_parameter = vec![];
+        // Reset the measurements and take the first ones, forget the influences of the last iteration
+        self.segregation = vec![];
+        self.influence_field = vec![];
+        self.geometry = vec![];
+        self.structure_factor = vec![];
+        self.flip_activity = vec![];
+        self.last_changed = vec![];
+        self.metrics.clear();
 
-        self.compute_order_parameter();
+        self.record_measurements();
 
         // Reset the convergence boolean
         self.converged = false;
@@ -453,10 +730,16 @@ impl CellularAutomaton3D for GPUNChemicalsCellularAutomaton3D {
         // Reset the iteration count
         self.iteration_count = 0;
 
-        // Reset the order parameter
-        self.order_parameter = vec![];
+        // Reset the measurements and the influences of the last iteration
+        self.segregation = vec![];
+        self.influence_field = vec![];
+        self.geometry = vec![];
+        self.structure_factor = vec![];
+        self.flip_activity = vec![];
+        self.last_changed = vec![];
+        self.metrics.clear();
 
-        self.compute_order_parameter();
+        self.record_measurements();
 
         // Reset the convergence boolean
         self.converged = false;
@@ -467,6 +750,11 @@ impl CellularAutomaton3D for GPUNChemicalsCellularAutomaton3D {
 
 
     //
+    fn force_iteration(&mut self) {
+        self.converged = false;
+        self.run_iteration();
+    }
+
     //
     // RUN ITERATION: This is where some parts had to be changed
     //
@@ -494,6 +782,12 @@ impl CellularAutomaton3D for GPUNChemicalsCellularAutomaton3D {
                 chemicals.push(c.demote.influence);
             }
 
+            // The fixed-point influences for the exact evaluation, in the same order as the chemicals
+            let fixed_point_influences: Vec<i32> = chemicals.iter().map(|influence| to_fixed_point(*influence)).collect();
+
+            // Followed by the probability with which cells apply the rules, which is 1 unless they update stochastically
+            chemicals.push(self.stochastic_update.map_or(1.0, |stochastic| stochastic.update_probability));
+
             let buffer = device.new_buffer_with_data(
                 unsafe { mem::transmute(data.as_ptr()) },
                 (data.len() * mem::size_of::<u8>()) as u64,
@@ -518,62 +812,10 @@ impl CellularAutomaton3D for GPUNChemicalsCellularAutomaton3D {
                 )
             };
 
-            // Computing relative neighbours: DC and UC
-            let mut neighbours_promote: Vec<Vec<(i32, i32, i32)>> = vec![];
-            let mut neighbours_demote: Vec<Vec<(i32, i32, i32)>> = vec![];
-
-            // Make enough room for neighbours that are connected to each of the chemicals
-            for c in &self.chemicals {
-                neighbours_promote.push(vec![]);
-                neighbours_demote.push(vec![]);
-            }
-
-            // Find the chemical with the largest range
-            let mut max_range = self.chemicals[0].promote.range;
-
-            for c in &self.chemicals {
-                if c.promote.range > max_range {
-                    max_range = c.promote.range;
-                }
-
-                if c.demote.range > max_range {
-                    max_range = c.demote.range;
-                }
-            }
-
-            let max_range_i = f32::ceil(max_range) as i32 + 2;
-
-            for x in -max_range_i..max_range_i {
-                for y in -max_range_i..max_range_i {
-                    for z in -max_range_i..max_range_i {
-                        // Compute the distance from the point (0, 0, 0)
-                        let dist = f32::sqrt((x*x + y*y + z*z) as f32);
-
-                        // A voxel cannot be its own neighbour, so (0, 0, 0) must be excluded from the neighbour-pack
-                        if !(x == 0 && y == 0 && z == 0) {
-
-                            // Loop over all the chemicals to determine whether it falls into its promotion
-                            // or demotion range
-                            for i in 0..self.chemicals.len() {
-
-                                let c = &self.chemicals[i];
-
-                                // If this point falls within the range of this promotion chemical
-                                if dist <= c.promote.range {
-                                    neighbours_promote[i].push((x, y, z));
-                                }
-
-                                // Else: if this point falls within the range of this demotion chemical
-                                else if dist <= c.demote.range {
-                                    neighbours_demote[i].push((x, y, z));
-                                }
-
-                            }
-                        }
-                        
-                    }
-                }
-            }
+            // Computing relative neighbours: the promoting and demoting neighbours of each of the chemicals
+            let (neighbours_promote, neighbours_demote): (Vec<Vec<(i32, i32, i32)>>, Vec<Vec<(i32, i32, i32)>>) = self.chemicals.iter()
+                .map(|c| chemical_stencil(c.promote.range, c.demote.range))
+                .unzip();
 
 
             //
@@ -638,6 +880,12 @@ impl CellularAutomaton3D for GPUNChemicalsCellularAutomaton3D {
                 MTLResourceOptions::CPUCacheModeDefaultCache
             );
 
+            // The shader records the influence of every chemical group on every cell
+            let influence_field = device.new_buffer(
+                (data.len() * self.chemicals.len() * mem::size_of::<f32>()) as u64,
+                MTLResourceOptions::CPUCacheModeDefaultCache
+            );
+
             // println!("Considering {} dc neighbours and {} uc neighbours", dc_neighbours_x.len(), uc_neighbours_x.len());
 
             // println!("The integral of influences over neighbours is {}", dc_neighbours_x.len() as f32 * self.dc_influence + uc_neighbours_x.len() as f32 * self.uc_influence);
@@ -652,6 +900,16 @@ impl CellularAutomaton3D for GPUNChemicalsCellularAutomaton3D {
                 size_container.push(neighbours_demote[i].len() as u32);
             }
 
+            // And finally, whether the influences are evaluated exactly, and whether the cells update stochastically:
+            // with which seed (low and high bits) during which iteration
+            let seed = self.stochastic_update.map_or(0, |stochastic| stochastic.seed);
+
+            size_container.push((self.evaluation_mode == EvaluationMode::Exact) as u32);
+            size_container.push(self.stochastic_update.is_some() as u32);
+            size_container.push(seed as u32);
+            size_container.push((seed >> 32) as u32);
+            size_container.push(self.iteration_count);
+
             let arg_size_container = {
                 let data = size_container.as_slice();
                 device.new_buffer_with_data(
@@ -661,6 +919,15 @@ impl CellularAutomaton3D for GPUNChemicalsCellularAutomaton3D {
                 )
             };
 
+            let arg_fixed_point_influences = {
+                let data = fixed_point_influences.as_slice();
+                device.new_buffer_with_data(
+                    unsafe { mem::transmute(data.as_ptr()) },
+                    (data.len() * mem::size_of::<i32>()) as u64,
+                    MTLResourceOptions::CPUCacheModeDefaultCache
+                )
+            };
+
             let command_buffer = command_queue.new_command_buffer();
             let encoder = command_buffer.new_compute_command_encoder();
 
@@ -685,6 +952,8 @@ impl CellularAutomaton3D for GPUNChemicalsCellularAutomaton3D {
             argument_encoder.set_buffer(7, &arg_neighbours_demote_x, 0);
             argument_encoder.set_buffer(8, &arg_neighbours_demote_y, 0);
             argument_encoder.set_buffer(9, &arg_neighbours_demote_z, 0);
+            argument_encoder.set_buffer(10, &influence_field, 0);
+            argument_encoder.set_buffer(11, &arg_fixed_point_influences, 0);
 
             let pipeline_state_descriptor = ComputePipelineDescriptor::new();
             pipeline_state_descriptor.set_compute_function(Some(&kernel));
@@ -708,6 +977,8 @@ impl CellularAutomaton3D for GPUNChemicalsCellularAutomaton3D {
             encoder.use_resource(&arg_neighbours_demote_x, MTLResourceUsage::Read);
             encoder.use_resource(&arg_neighbours_demote_y, MTLResourceUsage::Read);
             encoder.use_resource(&arg_neighbours_demote_z, MTLResourceUsage::Read);
+            encoder.use_resource(&influence_field, MTLResourceUsage::Write);
+            encoder.use_resource(&arg_fixed_point_influences, MTLResourceUsage::Read);
 
             
             
@@ -736,12 +1007,16 @@ impl CellularAutomaton3D for GPUNChemicalsCellularAutomaton3D {
                 self.import((*ptr).to_vec());
             }
 
+            let ptr = influence_field.contents() as *const f32;
+            unsafe {
+                self.influence_field = std::slice::from_raw_parts(ptr, data.len() * self.chemicals.len()).to_vec();
+            }
+
         });
 
         self.iteration_count += 1;
 
-        // Compute the new order parameter and insert it into the array
-        self.compute_order_parameter();
+        self.record_measurements();
 
     }
 
@@ -749,6 +1024,14 @@ impl CellularAutomaton3D for GPUNChemicalsCellularAutomaton3D {
 
 
 
+    fn stop_recording(&mut self) {
+        self.record_geometry = false;
+        self.record_structure_factor = false;
+        self.record_segregation = false;
+        self.observers.clear();
+    }
+
+
     // Getting and setting the iteration count is the same as the original gpu implementation
     fn set_iteration_count(&mut self, iterations: u32) {
         self.iteration_count = iterations;
@@ -764,6 +1047,34 @@ impl CellularAutomaton3D for GPUNChemicalsCellularAutomaton3D {
         mc.extract(self, vertices, indices);
     }
 
+    // The surface is extracted around the captured chemical
+    fn captured_cell_type(&self) -> u32 {
+        self.marching_cubes_chemical_capture as u32
+    }
+
+    // The undifferentiated cell-type (K) has no chemicals, so it has no influence field either
+    fn influence_field(&self, cell_type: u32) -> Option<Vec<f32>> {
+        let num_chemicals = self.chemicals.len();
+
+        if cell_type as usize >= num_chemicals || self.influence_field.is_empty() {
+            return None;
+        }
+
+        // Reorder the influences of this chemical group from the export order into C order
+        let mut result = vec![0f32; AUTOMATON_SIZE*AUTOMATON_SIZE*AUTOMATON_SIZE];
+
+        for x in 0..AUTOMATON_SIZE {
+            for y in 0..AUTOMATON_SIZE {
+                for z in 0..AUTOMATON_SIZE {
+                    let cell = x + y*AUTOMATON_SIZE + z*AUTOMATON_SIZE*AUTOMATON_SIZE;
+                    result[(x*AUTOMATON_SIZE + y)*AUTOMATON_SIZE + z] = self.influence_field[cell*num_chemicals + cell_type as usize];
+                }
+            }
+        }
+
+        Some(result)
+    }
+
 
 
 }
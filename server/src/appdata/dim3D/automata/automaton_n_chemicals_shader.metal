@@ -13,6 +13,8 @@ struct SumInput {
     device int* arg_neighbours_demote_x;
     device int* arg_neighbours_demote_y;
     device int* arg_neighbours_demote_z;
+    device float* influence_field;
+    device int* arg_fixed_point_influences;
 };
 
 kernel void compute_iteration(device SumInput& input [[ buffer(0) ]],
@@ -22,7 +24,8 @@ kernel void compute_iteration(device SumInput& input [[ buffer(0) ]],
     int gid = ugid;
 
     // Structure of the size_container:
-    // [automaton size,     #chemicals,         chemical_group0.#neighbours_promote, chemical_group0.#neighbours_promote, ... ]
+    // [automaton size,     #chemicals,         chemical_group0.#neighbours_promote, chemical_group0.#neighbours_promote, ...,
+    //  exact evaluation,   stochastic update,  seed (low bits),    seed (high bits),   iteration]
 
     uint size = input.arg_size_container[0];
     int size_i = (int) size;
@@ -33,6 +36,16 @@ kernel void compute_iteration(device SumInput& input [[ buffer(0) ]],
     // Extract the number of chemicals in this simulation from the size_container (structure specified above)
     int num_chemicals = (int) input.arg_size_container[1];
 
+    // Whether the influences are evaluated exactly, from the neighbour counts and fixed-point influences
+    bool exact = input.arg_size_container[2 + num_chemicals*2] == 1;
+
+    // Whether the cells only apply the rules with a probability, which follows the influences in arg_chemicals
+    bool stochastic = input.arg_size_container[3 + num_chemicals*2] == 1;
+    uint seed_low = input.arg_size_container[4 + num_chemicals*2];
+    uint seed_high = input.arg_size_container[5 + num_chemicals*2];
+    uint iteration = input.arg_size_container[6 + num_chemicals*2];
+    float update_probability = input.arg_chemicals[num_chemicals*2];
+
     // Find x, y, z coordinates by using modulo calculations
     int z = gid / (size_i*size_i);
     int y = (gid % (size_i*size_i)) / size_i;
@@ -46,6 +59,10 @@ kernel void compute_iteration(device SumInput& input [[ buffer(0) ]],
     // There will be as many influences as there are chemical groups
     float influences[10] = { 0.0 };
 
+    // The number of promoting and demoting neighbours of every chemical group, for the exact evaluation
+    int num_promoting[10] = { 0 };
+    int num_demoting[10] = { 0 };
+
     
 
     // Loop over every chemical group
@@ -133,6 +150,7 @@ kernel void compute_iteration(device SumInput& input [[ buffer(0) ]],
             if (input.data[index] == i) {
                 // DC
                 influences[i] += promotor_influence;
+                num_promoting[i]++;
             }
 
         }
@@ -202,6 +220,7 @@ kernel void compute_iteration(device SumInput& input [[ buffer(0) ]],
             if (input.data[index] == i) {
                 // DC
                 influences[i] += demotor_influence;
+                num_demoting[i]++;
             }
 
         }
@@ -211,6 +230,24 @@ kernel void compute_iteration(device SumInput& input [[ buffer(0) ]],
 
 
 
+    // In the exact evaluation, the neighbour counts are weighed with the fixed-point influences (16 fractional bits)
+    long fixed_point_influences[10] = { 0 };
+
+    if (exact) {
+        for (int i = 0; i < num_chemicals; i++) {
+            fixed_point_influences[i] = (long) num_promoting[i] * input.arg_fixed_point_influences[i*2]
+                + (long) num_demoting[i] * input.arg_fixed_point_influences[i*2 + 1];
+            influences[i] = (float) fixed_point_influences[i] / 65536.0;
+        }
+    }
+
+    // Record the influences, so that they can be inspected after the iteration.
+    // They're organised as [cell0.influence0, cell0.influence1, ..., cell1.influence0, ...]
+    for (int i = 0; i < num_chemicals; i++) {
+        input.influence_field[gid*num_chemicals + i] = influences[i];
+    }
+
+
     //
     // All influences have been computed and we can start to determine which color this
     // cell should become.
@@ -223,19 +260,29 @@ kernel void compute_iteration(device SumInput& input [[ buffer(0) ]],
 
 
 
-    // 1. Compute the highest influence from the array of influences
+    // 1. Compute the highest influence from the array of influences.
+    // The exact evaluation compares the fixed-point influences, which can't round to the same value.
     float maximum_influence = influences[0];
+    long maximum_fixed_point_influence = fixed_point_influences[0];
     int maximum_influence_owner = 0;
 
     for (int i = 1; i < num_chemicals; i++) {
-        if (influences[i] > maximum_influence) {
+        if (exact ? fixed_point_influences[i] > maximum_fixed_point_influence : influences[i] > maximum_influence) {
             maximum_influence = influences[i];
+            maximum_fixed_point_influence = fixed_point_influences[i];
             maximum_influence_owner = i;
         }
     }
 
+    if (exact) {
+        maximum_influence = (float) maximum_fixed_point_influence;
+    }
+
 
-    if (maximum_influence > 0) {
+    if (stochastic && cell_uniform(seed_low, seed_high, iteration, (uint) gid) >= update_probability) {
+        // A cell that updates stochastically only applies the rules with the update probability
+        input.sum[gid] = input.data[gid];
+    } else if (maximum_influence > 0) {
         // Apply the first rule
 
         // Now, we have a maximum influence that's positive.
@@ -13,6 +13,7 @@ struct SumInput {
     device int* arg_uc_neighbours_x;
     device int* arg_uc_neighbours_y;
     device int* arg_uc_neighbours_z;
+    device int* arg_fixed_point_influences;
 };
 
 kernel void compute_iteration(device SumInput& input [[ buffer(0) ]],
@@ -27,6 +28,11 @@ kernel void compute_iteration(device SumInput& input [[ buffer(0) ]],
     int array_size_i = (int) array_size;
     uint dc_neighbours_len = input.arg_size_container[1];
     uint uc_neighbours_len = input.arg_size_container[2];
+    bool exact = input.arg_size_container[3] == 1;
+    bool stochastic = input.arg_size_container[4] == 1;
+    uint seed_low = input.arg_size_container[5];
+    uint seed_high = input.arg_size_container[6];
+    uint iteration = input.arg_size_container[7];
 
     int z = gid / (size_i*size_i);
     int y = (gid % (size_i*size_i)) / size_i;
@@ -36,10 +42,15 @@ kernel void compute_iteration(device SumInput& input [[ buffer(0) ]],
     float dc_influence = input.arg_chemicals[1];
     float uc_range = input.arg_chemicals[2];
     float uc_influence = input.arg_chemicals[3];
+    float update_probability = input.arg_chemicals[4];
 
     // Calculate the influence of neighbours on this voxel
     float influence_sum = 0.0;
 
+    // The number of DC and UC neighbours, for the exact evaluation
+    int num_dc = 0;
+    int num_uc = 0;
+
     for (uint i = 0; i < dc_neighbours_len; i++) {
         int dx = input.arg_dc_neighbours_x[i];
         int dy = input.arg_dc_neighbours_y[i];
@@ -95,6 +106,7 @@ kernel void compute_iteration(device SumInput& input [[ buffer(0) ]],
         if (input.data[index] == 0) {
             // DC
             influence_sum += dc_influence;
+            num_dc++;
         }
     }
 
@@ -153,11 +165,22 @@ kernel void compute_iteration(device SumInput& input [[ buffer(0) ]],
         if (input.data[index] == 0) {
             // UC
             influence_sum += uc_influence;
+            num_uc++;
         }
     }
 
+    // In the exact evaluation, the neighbour counts are weighed with the fixed-point influences instead.
+    // Only the sign matters, which the conversion keeps.
+    if (exact) {
+        long fixed_point_sum = (long) num_dc * input.arg_fixed_point_influences[0] + (long) num_uc * input.arg_fixed_point_influences[1];
+        influence_sum = (float) fixed_point_sum;
+    }
+
     // PRODUCTION CODE
-    if (influence_sum > 0.0) {
+    // A cell that updates stochastically only applies the rules with the update probability
+    if (stochastic && cell_uniform(seed_low, seed_high, iteration, (uint) gid) >= update_probability) {
+        input.sum[gid] = input.data[gid];
+    } else if (influence_sum > 0.0) {
         input.sum[gid] = 0;
     } else if (influence_sum < 0.0) {
         input.sum[gid] = 1;
@@ -0,0 +1,35 @@
+#include <metal_stdlib>
+
+using namespace metal;
+
+//
+// Philox4x32-10, exactly as in counter_rng.rs: random numbers that only depend on the key and the counter
+//
+
+uint4 philox4x32(uint4 counter, uint2 key) {
+    uint4 c = counter;
+    uint2 k = key;
+
+    for (int i = 0; i < 10; i++) {
+        if (i > 0) {
+            k = uint2(k.x + 0x9E3779B9u, k.y + 0xBB67AE85u);
+        }
+
+        uint hi0 = mulhi(0xD2511F53u, c.x);
+        uint lo0 = 0xD2511F53u * c.x;
+        uint hi1 = mulhi(0xCD9E8D57u, c.z);
+        uint lo1 = 0xCD9E8D57u * c.z;
+
+        c = uint4(hi1 ^ c.y ^ k.x, lo1, hi0 ^ c.w ^ k.y, lo0);
+    }
+
+    return c;
+}
+
+// A uniform random number in [0, 1) with 24 bits for a cell (x + y*size + z*size²) at an iteration
+float cell_uniform(uint seed_low, uint seed_high, uint iteration, uint cell) {
+    uint4 random = philox4x32(uint4(cell, iteration, 0, 0), uint2(seed_low, seed_high));
+
+    return (float) (random.x >> 8) / 16777216.0;
+}
+
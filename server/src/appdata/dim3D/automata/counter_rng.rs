@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+//
+// A counter-based random number generator: Philox4x32-10 (Salmon et al., "Parallel random numbers: as easy as 1, 2, 3").
+// Every random number is a pure function of its key and counter, so that it doesn't matter in which order, on which
+// thread or on which backend it is drawn. counter_rng.metal implements exactly the same functions for the shaders.
+//
+
+const PHILOX_M0: u32 = 0xD2511F53;
+const PHILOX_M1: u32 = 0xCD9E8D57;
+const PHILOX_W0: u32 = 0x9E3779B9;
+const PHILOX_W1: u32 = 0xBB67AE85;
+
+pub fn philox4x32(counter: [u32; 4], key: [u32; 2]) -> [u32; 4] {
+    let mut c = counter;
+    let mut k = key;
+
+    for round in 0..10 {
+        if round > 0 {
+            k = [k[0].wrapping_add(PHILOX_W0), k[1].wrapping_add(PHILOX_W1)];
+        }
+
+        let product0 = PHILOX_M0 as u64 * c[0] as u64;
+        let product1 = PHILOX_M1 as u64 * c[2] as u64;
+
+        c = [
+            (product1 >> 32) as u32 ^ c[1] ^ k[0],
+            product1 as u32,
+            (product0 >> 32) as u32 ^ c[3] ^ k[1],
+            product0 as u32
+        ];
+    }
+
+    c
+}
+
+//
+// The random number of a cell at an iteration. Cells are indexed as x + y*size + z*size², like the shaders do.
+//
+pub fn cell_random(seed: u64, iteration: u32, cell: u32) -> u32 {
+    philox4x32([cell, iteration, 0, 0], [seed as u32, (seed >> 32) as u32])[0]
+}
+
+//
+// A uniform random number in [0, 1) with 24 bits, which f32 represents exactly
+//
+pub fn cell_uniform(seed: u64, iteration: u32, cell: u32) -> f32 {
+    (cell_random(seed, iteration, cell) >> 8) as f32 / 16777216.0
+}
+
+//
+// A stochastic fate rule: during every iteration, a cell only applies the rules with a probability,
+// and keeps its type otherwise
+//
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct StochasticUpdate {
+    pub seed: u64,
+    pub update_probability: f32
+}
+
+impl StochasticUpdate {
+
+    pub fn updates(&self, iteration: u32, cell: u32) -> bool {
+        cell_uniform(self.seed, iteration, cell) < self.update_probability
+    }
+
+}
+
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    // The known-answer vectors of the reference implementation (Random123, kat_vectors)
+    #[test]
+    fn philox_known_answers() {
+        assert_eq!(philox4x32([0, 0, 0, 0], [0, 0]), [0x6627e8d5, 0xe169c58d, 0xbc57ac4c, 0x9b00dbd8]);
+        assert_eq!(philox4x32([u32::MAX; 4], [u32::MAX; 2]), [0x408f276d, 0x41c83b0e, 0xa20bc7c6, 0x6d5451fd]);
+        assert_eq!(
+            philox4x32([0x243f6a88, 0x85a308d3, 0x13198a2e, 0x03707344], [0xa4093822, 0x299f31d0]),
+            [0xd16cfe09, 0x94fdcceb, 0x5001e420, 0x24126ea1]
+        );
+    }
+
+    #[test]
+    fn uniform_numbers_lie_in_the_unit_interval() {
+        let numbers: Vec<f32> = (0..100000).map(|cell| cell_uniform(7, 3, cell)).collect();
+        let mean = numbers.iter().sum::<f32>() / numbers.len() as f32;
+
+        assert!(numbers.iter().all(|u| (0.0..1.0).contains(u)));
+        assert!((mean - 0.5).abs() < 0.01);
+    }
+
+}
@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::{Serialize, Deserialize};
+
+use super::automaton_gpu_n_chemicals::GPUNChemicalsCellularAutomaton3D;
+use crate::analysis::difference::cell_types_of;
+use crate::analysis::geometry::measure_geometry;
+use crate::analysis::structure_factor::measure_structure_factor;
+
+//
+// An observer measures one metric of the automaton, every 'interval' iterations (including iteration 0).
+// Its values are collected under its name in the metric store of the automaton.
+//
+pub trait Observer: Send + Sync {
+    fn name(&self) -> String;
+    fn interval(&self) -> u32;
+    fn observe(&self, automaton: &GPUNChemicalsCellularAutomaton3D) -> Vec<f32>;
+}
+
+//
+// The built-in metrics, named as in the metric store
+//
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Metric {
+    // The order parameter of every cell-type over the order parameter neighbourhood, as computed by the GPU
+    OrderParameter,
+    // The fraction of the cells of every cell-type
+    VolumeFractions,
+    // The number of cells that changed their type during the last iteration
+    FlipCount,
+    // The area of the surface around every cell-type, in cells²
+    InterfaceArea,
+    // The dominant wavelength of every cell-type, in cells
+    Wavelength
+}
+
+impl Metric {
+
+    pub fn name(&self) -> String {
+        String::from(match self {
+            Metric::OrderParameter => "order-parameter",
+            Metric::VolumeFractions => "volume-fractions",
+            Metric::FlipCount => "flip-count",
+            Metric::InterfaceArea => "interface-area",
+            Metric::Wavelength => "wavelength"
+        })
+    }
+
+    // An observer of this metric
+    pub fn observer(self, interval: u32) -> Arc<dyn Observer> {
+        Arc::new(ClosureObserver { name: self.name(), interval, measure: move |automaton: &GPUNChemicalsCellularAutomaton3D| self.measure(automaton) })
+    }
+
+    pub fn measure(&self, automaton: &GPUNChemicalsCellularAutomaton3D) -> Vec<f32> {
+        let num_cell_types = automaton.chemicals.len() + 1;
+
+        match self {
+            Metric::OrderParameter => automaton.compute_order_parameter(),
+            Metric::VolumeFractions => {
+                let cell_types = cell_types_of(automaton);
+                let mut fractions = vec![0f32; num_cell_types];
+
+                for cell_type in &cell_types {
+                    fractions[*cell_type as usize] += 1.0;
+                }
+
+                fractions.iter().map(|count| count / cell_types.len() as f32).collect()
+            },
+            Metric::FlipCount => vec![automaton.get_flip_count() as f32],
+            Metric::InterfaceArea => measure_geometry(automaton, num_cell_types).cell_types.iter().map(|c| c.surface_area).collect(),
+            Metric::Wavelength => measure_structure_factor(automaton, num_cell_types).cell_types.iter().map(|c| c.dominant_wavelength).collect()
+        }
+    }
+
+}
+
+//
+// Observes a metric that is computed by a closure, such as a custom one
+//
+pub struct ClosureObserver<F: Fn(&GPUNChemicalsCellularAutomaton3D) -> Vec<f32> + Send + Sync> {
+    pub name: String,
+    pub interval: u32,
+    pub measure: F
+}
+
+impl<F: Fn(&GPUNChemicalsCellularAutomaton3D) -> Vec<f32> + Send + Sync> Observer for ClosureObserver<F> {
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    fn observe(&self, automaton: &GPUNChemicalsCellularAutomaton3D) -> Vec<f32> {
+        (self.measure)(automaton)
+    }
+
+}
+
+#[derive(Clone, Serialize)]
+pub struct MetricSample {
+    pub iteration: u32,
+    pub values: Vec<f32>
+}
+
+//
+// The time series of every observed metric, by name. The samples of a metric are ordered by iteration.
+//
+#[derive(Clone, Default)]
+pub struct MetricStore {
+    series: BTreeMap<String, Vec<MetricSample>>
+}
+
+impl MetricStore {
+
+    pub fn record(&mut self, name: String, sample: MetricSample) {
+        self.series.entry(name).or_default().push(sample);
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.series.keys().cloned().collect()
+    }
+
+    // The last sample of every metric
+    pub fn latest(&self) -> BTreeMap<String, MetricSample> {
+        self.series.iter()
+            .filter_map(|(name, samples)| samples.last().map(|sample| (name.clone(), sample.clone())))
+            .collect()
+    }
+
+    // The samples of a metric with from <= iteration <= to, or None if the metric was never observed
+    pub fn query(&self, name: &str, from: u32, to: u32) -> Option<Vec<MetricSample>> {
+        self.series.get(name).map(|samples| {
+            samples.iter().filter(|s| s.iteration >= from && s.iteration <= to).cloned().collect()
+        })
+    }
+
+    // Remove the samples of all metrics after an iteration
+    pub fn forget_after(&mut self, iteration: u32) {
+        for samples in self.series.values_mut() {
+            samples.retain(|s| s.iteration <= iteration);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.series.clear();
+    }
+
+}
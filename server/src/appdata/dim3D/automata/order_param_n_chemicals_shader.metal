@@ -4,7 +4,7 @@ using namespace metal;
 
 struct SumInput {
     device uint8_t *data;
-    volatile device int8_t *sum;
+    volatile device int *sum;
     device uint* arg_size_container;
     device int* arg_neighbours;
 };
@@ -16,7 +16,7 @@ kernel void compute_iteration(device SumInput& input [[ buffer(0) ]],
     int gid = ugid;
 
     // Structure of the size_container:
-    // [automaton size,     #species]
+    // [automaton size,     #species,       #neighbours]
 
     uint size = input.arg_size_container[0];
     int size_i = (int) size;
@@ -27,13 +27,16 @@ kernel void compute_iteration(device SumInput& input [[ buffer(0) ]],
     // Extract the number of species in this simulation from the size_container (structure specified above)
     int num_species = (int) input.arg_size_container[1];
 
+    // The neighbours are organised as [dx0, dy0, dz0, dx1, dy1, dz1, ...]
+    int num_neighbours = (int) input.arg_size_container[2];
+
     // Find x, y, z coordinates by using modulo calculations
     int z = gid / (size_i*size_i);
     int y = (gid % (size_i*size_i)) / size_i;
     int x = (gid % (size_i*size_i)) % size_i;
 
 
-    for (int a = 0; a < 18; a += 3) {
+    for (int a = 0; a < 3*num_neighbours; a += 3) {
         int dx = input.arg_neighbours[a];
         int dy = input.arg_neighbours[a+1];
         int dz = input.arg_neighbours[a+2];
@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+
+//
+// The relative positions of the neighbours that a chemical group reaches, exactly as the GPU backends use them.
+// Promoting neighbours lie within the promotor's range; demoting neighbours lie outside of it, but within
+// the demotor's range. A voxel is never its own neighbour.
+//
+pub fn chemical_stencil(promote_range: f32, demote_range: f32) -> (Vec<(i32, i32, i32)>, Vec<(i32, i32, i32)>) {
+    let mut promote: Vec<(i32, i32, i32)> = vec![];
+    let mut demote: Vec<(i32, i32, i32)> = vec![];
+
+    let reach = f32::ceil(f32::max(promote_range, demote_range)) as i32;
+
+    for x in -reach..=reach {
+        for y in -reach..=reach {
+            for z in -reach..=reach {
+                // Compute the distance from the point (0, 0, 0)
+                let dist = f32::sqrt((x*x + y*y + z*z) as f32);
+
+                if x == 0 && y == 0 && z == 0 {
+                    continue;
+                }
+
+                if dist <= promote_range {
+                    promote.push((x, y, z));
+                } else if dist <= demote_range {
+                    demote.push((x, y, z));
+                }
+            }
+        }
+    }
+
+    (promote, demote)
+}
+
+//
+// The sum of the influences of all neighbours in the stencil: the net influence on a voxel
+// that is completely surrounded by cells of the chemical group's type
+//
+pub fn kernel_integral(promote_range: f32, promote_influence: f32, demote_range: f32, demote_influence: f32) -> f32 {
+    let (promote, demote) = chemical_stencil(promote_range, demote_range);
+
+    promote.len() as f32 * promote_influence + demote.len() as f32 * demote_influence
+}
+
+//
+// How the influences on a cell are evaluated.
+// Float sums the influences of the neighbours in f32, in an order that differs between the backends, so that
+// near-zero sums can resolve differently. Exact counts the neighbours of every kernel shell as integers and
+// weighs them with fixed-point influences, which gives bit-identical results on every backend.
+//
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EvaluationMode {
+    #[default]
+    Float,
+    Exact
+}
+
+// Fixed-point influences have 16 fractional bits
+pub const FIXED_POINT_ONE: f32 = 65536.0;
+
+pub fn to_fixed_point(influence: f32) -> i32 {
+    (influence * FIXED_POINT_ONE).round() as i32
+}
+
+//
+// The exact influence of a chemical group with the given number of promoting and demoting neighbours of its type,
+// in fixed point. The shaders compute exactly the same sum.
+//
+pub fn fixed_point_influence(num_promoting: u32, promote_influence: f32, num_demoting: u32, demote_influence: f32) -> i64 {
+    num_promoting as i64 * to_fixed_point(promote_influence) as i64 + num_demoting as i64 * to_fixed_point(demote_influence) as i64
+}
+
+//
+// The neighbourhood over which the order parameter compares cells
+//
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Neighbourhood {
+    // The 6 cells that share a face
+    #[default]
+    Face,
+    // The 18 cells that share a face or an edge
+    Edge,
+    // The 26 cells that share a face, an edge or a corner
+    Corner,
+    // All cells within the given distance (in cells)
+    Sphere(f32)
+}
+
+impl Neighbourhood {
+
+    pub fn radius(&self) -> f32 {
+        match self {
+            Neighbourhood::Face => 1.0,
+            Neighbourhood::Edge => f32::sqrt(2.0),
+            Neighbourhood::Corner => f32::sqrt(3.0),
+            Neighbourhood::Sphere(radius) => *radius
+        }
+    }
+
+    // Whether the neighbourhood fits in an automaton of the given size: the shaders wrap the
+    // offsets around the periodic boundaries only once, so the radius can be at most half the size
+    pub fn fits(&self, size: usize) -> bool {
+        let radius = self.radius();
+
+        radius.is_finite() && radius <= (size / 2) as f32
+    }
+
+    // The relative positions of all neighbours, leaving out the cell itself
+    pub fn offsets(&self) -> Vec<(i32, i32, i32)> {
+        let radius = self.radius();
+        let reach = f32::ceil(radius) as i32;
+
+        let mut offsets: Vec<(i32, i32, i32)> = vec![];
+
+        for x in -reach..=reach {
+            for y in -reach..=reach {
+                for z in -reach..=reach {
+                    let dist = f32::sqrt((x*x + y*y + z*z) as f32);
+
+                    if !(x == 0 && y == 0 && z == 0) && dist <= radius {
+                        offsets.push((x, y, z));
+                    }
+                }
+            }
+        }
+
+        offsets
+    }
+
+}
+
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn fixed_point_influence_of_a_surrounded_cell() {
+        // A cell surrounded by its own type has 6 face neighbours within a range of 1 and 12 edge neighbours up to 1.5
+        let (promote, demote) = chemical_stencil(1.0, 1.5);
+
+        assert_eq!((promote.len(), demote.len()), (6, 12));
+
+        // 1.0 is 65536 and -0.3 rounds to -19661: 6*65536 - 12*19661
+        assert_eq!(fixed_point_influence(6, 1.0, 12, -0.3), 157284);
+
+        // 0.1 rounds to 6554 and -0.05 to -3277, which cancel exactly, whatever order they're summed in
+        assert_eq!(to_fixed_point(0.1), 6554);
+        assert_eq!(to_fixed_point(-0.05), -3277);
+        assert_eq!(fixed_point_influence(6, 0.1, 12, -0.05), 0);
+    }
+
+}
@@ -0,0 +1,407 @@
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, mpsc::{channel, Sender}, atomic::{AtomicBool, AtomicU64, Ordering}};
+use std::thread;
+use std::time::Instant;
+
+use serde::{Serialize, Deserialize};
+
+use crate::CAAppData;
+use crate::appdata::sessions::Session;
+use crate::routes::batch::{BatchExperiment, INVALID_EXPERIMENT, batch_iterations, run_batch};
+use super::dim3d::automata::{automaton::CellularAutomaton3D, automaton_gpu_n_chemicals::GPUNChemicalsCellularAutomaton3D, observer::MetricSample};
+
+//
+// The long-running work that can be done in the background
+//
+#[derive(Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum JobRequest {
+    // Run iterations on the n-chemicals automaton of the session, like /nchem/run-iteration.
+    // The state is only locked during every single iteration, so that other requests are served in between.
+    RunIteration { num_iterations: u32 },
+    // Run a batch experiment, like /batch/run-experiment, on a copy of the n-chemicals automaton.
+    // The automaton of the session is left untouched.
+    Batch { experiment: BatchExperiment }
+}
+
+impl JobRequest {
+
+    fn kind(&self) -> String {
+        String::from(match self {
+            JobRequest::RunIteration { .. } => "run-iteration",
+            JobRequest::Batch { .. } => "batch"
+        })
+    }
+
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Running,
+    Completed,
+    Cancelled,
+    Failed
+}
+
+impl JobState {
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobState::Completed | JobState::Cancelled | JobState::Failed)
+    }
+
+}
+
+// The number of finished jobs that are kept, after which the oldest ones are removed
+const MAX_FINISHED_JOBS: usize = 100;
+
+#[derive(Clone, Serialize)]
+pub struct LatestMetrics {
+    pub iteration: u32,
+    // The last sample of every observed metric
+    pub metrics: BTreeMap<String, MetricSample>
+}
+
+#[derive(Clone, Serialize)]
+#[serde(untagged)]
+pub enum JobResult {
+    RunIteration { iteration: u32, duration: f32 },
+    // The content of the csv-file is kept, so that it can be downloaded even if the file is overwritten later on
+    Batch {
+        file_name: String,
+        duration: f32,
+        #[serde(skip)]
+        csv: Arc<Vec<u8>>
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct JobStatus {
+    pub id: u64,
+    // The session whose automaton the job runs on
+    pub session: String,
+    pub kind: String,
+    pub state: JobState,
+    // The number of iterations that have been run, out of the total number of iterations of the job
+    pub iterations_done: u64,
+    pub total_iterations: u64,
+    // The time since the job started and the estimated time until it finishes, in seconds
+    pub elapsed: f32,
+    pub eta: Option<f32>,
+    pub latest_metrics: Option<LatestMetrics>,
+    pub error: Option<String>,
+    #[serde(skip)]
+    pub result: Option<JobResult>,
+    #[serde(skip)]
+    started: Option<Instant>
+}
+
+struct Job {
+    // The token of the session, which tells it apart from earlier sessions with the same id
+    session: u64,
+    status: Mutex<JobStatus>,
+    cancelled: AtomicBool
+}
+
+impl Job {
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn in_session(&self, session: &Session) -> bool {
+        self.session == session.token()
+    }
+
+    // Register that an iteration has been run on the automaton
+    fn progress(&self, automaton: &GPUNChemicalsCellularAutomaton3D) {
+        let mut status = self.status.lock().unwrap();
+
+        status.iterations_done += 1;
+        status.elapsed = status.started.map_or(0.0, |started| started.elapsed().as_secs_f32());
+
+        let remaining = status.total_iterations.saturating_sub(status.iterations_done);
+        status.eta = Some(status.elapsed / status.iterations_done as f32 * remaining as f32);
+
+        status.latest_metrics = Some(LatestMetrics {
+            iteration: automaton.get_iteration_count(),
+            metrics: automaton.get_metrics().latest()
+        });
+    }
+
+    fn finish(&self, state: JobState, result: Option<JobResult>, error: Option<String>) {
+        let mut status = self.status.lock().unwrap();
+
+        status.state = state;
+        status.result = result;
+        status.error = error;
+        status.elapsed = status.started.map_or(0.0, |started| started.elapsed().as_secs_f32());
+
+        if state == JobState::Completed {
+            status.eta = Some(0.0);
+        }
+    }
+
+}
+
+//
+// Keeps track of all jobs. Every session has its own worker thread, which runs the jobs of the session one after the other,
+// so that the jobs of one session don't wait for those of another.
+//
+pub struct JobManager {
+    next_id: AtomicU64,
+    jobs: Mutex<HashMap<u64, Arc<Job>>>,
+    // The queue of the worker of every session, by session token
+    queues: Mutex<HashMap<u64, Sender<(Arc<Job>, JobRequest)>>>
+}
+
+impl Default for JobManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JobManager {
+
+    pub fn new() -> Self {
+        JobManager {
+            next_id: AtomicU64::new(1),
+            jobs: Mutex::new(HashMap::new()),
+            queues: Mutex::new(HashMap::new())
+        }
+    }
+
+    // Queue a job on the state of a session and return its id
+    pub fn submit(&self, session: &Session, request: JobRequest) -> Result<u64, String> {
+        let total_iterations = match &request {
+            JobRequest::RunIteration { num_iterations } => *num_iterations as u64,
+            JobRequest::Batch { experiment } => batch_iterations(experiment).ok_or(INVALID_EXPERIMENT)?
+        };
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+
+        let job = Arc::new(Job {
+            session: session.token(),
+            status: Mutex::new(JobStatus {
+                id,
+                session: session.id(),
+                kind: request.kind(),
+                state: JobState::Queued,
+                iterations_done: 0,
+                total_iterations,
+                elapsed: 0.0,
+                eta: None,
+                latest_metrics: None,
+                error: None,
+                result: None,
+                started: None
+            }),
+            cancelled: AtomicBool::new(false)
+        });
+
+        let mut jobs = self.jobs.lock().unwrap();
+
+        jobs.insert(id, job.clone());
+        prune(&mut jobs);
+
+        drop(jobs);
+
+        let mut queues = self.queues.lock().unwrap();
+        let queue = queues.entry(session.token()).or_insert_with(|| spawn_worker(session.shared()));
+
+        queue.send((job, request)).map_err(|e| e.to_string())?;
+
+        Ok(id)
+    }
+
+    //
+    // The jobs of a session are only visible from that session: the jobs of other sessions are treated as if they don't exist
+    //
+    pub fn status(&self, session: &Session, id: u64) -> Option<JobStatus> {
+        self.jobs.lock().unwrap().get(&id).filter(|job| job.in_session(session)).map(|job| job.status.lock().unwrap().clone())
+    }
+
+    pub fn list(&self, session: &Session) -> Vec<JobStatus> {
+        let mut statuses: Vec<JobStatus> = self.jobs.lock().unwrap().values()
+            .filter(|job| job.in_session(session))
+            .map(|job| job.status.lock().unwrap().clone())
+            .collect();
+        statuses.sort_by_key(|status| status.id);
+
+        statuses
+    }
+
+    // Remove a job that has finished. Returns false if it's still queued or running.
+    pub fn delete(&self, session: &Session, id: u64) -> Option<bool> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let finished = jobs.get(&id).filter(|job| job.in_session(session))?.status.lock().unwrap().state.is_finished();
+
+        if finished {
+            jobs.remove(&id);
+        }
+
+        Some(finished)
+    }
+
+    //
+    // Ask a job to stop. A queued job is cancelled immediately, a running one after its current iteration.
+    // Jobs that have already finished are left as they are.
+    //
+    pub fn cancel(&self, session: &Session, id: u64) -> Option<JobStatus> {
+        let jobs = self.jobs.lock().unwrap();
+        let job = jobs.get(&id).filter(|job| job.in_session(session))?;
+
+        job.cancelled.store(true, Ordering::SeqCst);
+
+        let mut status = job.status.lock().unwrap();
+
+        if status.state == JobState::Queued {
+            status.state = JobState::Cancelled;
+        }
+
+        Some(status.clone())
+    }
+
+    // Cancel all jobs of a session that is deleted and forget about them
+    pub fn remove_session(&self, session: &Session) {
+        let mut jobs = self.jobs.lock().unwrap();
+
+        for job in jobs.values().filter(|job| job.in_session(session)) {
+            job.cancelled.store(true, Ordering::SeqCst);
+        }
+
+        jobs.retain(|_, job| !job.in_session(session));
+
+        drop(jobs);
+
+        // The worker stops once it has skipped the cancelled jobs
+        self.queues.lock().unwrap().remove(&session.token());
+    }
+
+}
+
+//
+// Start a worker that runs the jobs that are sent to it on the state of a session, until its queue is dropped
+//
+fn spawn_worker(state: Arc<Mutex<CAAppData>>) -> Sender<(Arc<Job>, JobRequest)> {
+    let (sender, receiver) = channel::<(Arc<Job>, JobRequest)>();
+
+    thread::spawn(move || {
+        for (job, request) in receiver {
+            // Jobs that were cancelled while queued are skipped
+            if job.is_cancelled() {
+                continue;
+            }
+
+            {
+                let mut status = job.status.lock().unwrap();
+                status.state = JobState::Running;
+                status.started = Some(Instant::now());
+            }
+
+            // A job that panics fails, but the worker continues with the next one.
+            // Jobs catch panics themselves while they hold the lock of a session, so that it isn't poisoned.
+            let outcome = catch_unwind(AssertUnwindSafe(|| run_job(&state, &job, request)));
+
+            match outcome {
+                Ok(Ok(Some(result))) => job.finish(JobState::Completed, Some(result), None),
+                Ok(Ok(None)) => job.finish(JobState::Cancelled, None, None),
+                Ok(Err(e)) => job.finish(JobState::Failed, None, Some(e)),
+                Err(payload) => job.finish(JobState::Failed, None, Some(panic_message(payload.as_ref())))
+            }
+        }
+    });
+
+    sender
+}
+
+//
+// Run a job to completion. Returns None if it was cancelled along the way.
+//
+fn run_job(state: &Mutex<CAAppData>, job: &Job, request: JobRequest) -> Result<Option<JobResult>, String> {
+    let start = Instant::now();
+
+    match request {
+        JobRequest::RunIteration { num_iterations } => {
+            let mut iteration = 0;
+
+            for _ in 0..num_iterations {
+                if job.is_cancelled() {
+                    return Ok(None);
+                }
+
+                let mut state_mod = state.lock().unwrap();
+
+                // A panicking iteration would poison the state of the session for every other request,
+                // so it's caught while the lock is held and the automaton is rolled back to its state before the iteration
+                let checkpoint = state_mod.nchem_ca.checkpoint();
+                let failed_iteration = state_mod.nchem_ca.get_iteration_count() + 1;
+
+                if catch_unwind(AssertUnwindSafe(|| state_mod.nchem_ca.run_iteration())).is_err() {
+                    state_mod.nchem_ca.rollback(checkpoint);
+
+                    return Err(format!("Iteration {} panicked, the automaton was restored to its state before it", failed_iteration));
+                }
+
+                job.progress(&state_mod.nchem_ca);
+                iteration = state_mod.nchem_ca.get_iteration_count();
+
+                drop(state_mod);
+            }
+
+            Ok(Some(JobResult::RunIteration { iteration, duration: start.elapsed().as_secs_f32() }))
+        },
+        JobRequest::Batch { experiment } => {
+            let state_mod = state.lock().unwrap();
+            let mut automaton = state_mod.nchem_ca.clone();
+            drop(state_mod);
+
+            let file_name = run_batch(&mut automaton, &experiment, &mut |automaton| {
+                job.progress(automaton);
+                !job.is_cancelled()
+            })?;
+
+            match file_name {
+                Some(file_name) => {
+                    let csv = fs::read(&file_name).map_err(|e| format!("Error when reading file {}: {}", file_name, e))?;
+
+                    Ok(Some(JobResult::Batch { file_name, duration: start.elapsed().as_secs_f32(), csv: Arc::new(csv) }))
+                },
+                None => Ok(None)
+            }
+        }
+    }
+}
+
+//
+// The message of a panic, such as the failure of a batch attribute
+//
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match (payload.downcast_ref::<String>(), payload.downcast_ref::<&str>()) {
+        (Some(message), _) => format!("The job panicked: {}", message),
+        (None, Some(message)) => format!("The job panicked: {}", message),
+        (None, None) => String::from("The job panicked")
+    }
+}
+
+//
+// Remove the oldest finished jobs, so that at most MAX_FINISHED_JOBS of them are kept
+//
+fn prune(jobs: &mut HashMap<u64, Arc<Job>>) {
+    let mut finished: Vec<u64> = jobs.iter()
+        .filter(|(_, job)| job.status.lock().unwrap().state.is_finished())
+        .map(|(id, _)| *id)
+        .collect();
+
+    if finished.len() > MAX_FINISHED_JOBS {
+        finished.sort_unstable();
+
+        for id in &finished[..(finished.len() - MAX_FINISHED_JOBS)] {
+            jobs.remove(id);
+        }
+    }
+}
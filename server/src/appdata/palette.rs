@@ -0,0 +1,52 @@
+use miette::{miette, Result};
+
+//
+// Default colours of the cell-types. These are the same colours that the web client
+// uses for its chemical_colors, so that exported files look like the visualisation.
+//
+pub const CHEMICAL_COLOURS: [u32; 7] = [0xc2532b, 0x5bafd9, 0x7c1e79, 0x9862a5, 0x78eb7a, 0xe778eb, 0xcc4a4a];
+
+//
+// The colour of a cell-type as (r, g, b) bytes.
+// There are fewer colours than cell-types can exist (K_MAX + 1), so the palette repeats itself.
+//
+pub fn cell_type_colour(cell_type: usize) -> [u8; 3] {
+    hex_to_rgb(CHEMICAL_COLOURS[cell_type % CHEMICAL_COLOURS.len()])
+}
+
+pub fn hex_to_rgb(hex: u32) -> [u8; 3] {
+    [((hex >> 16) & 0xff) as u8, ((hex >> 8) & 0xff) as u8, (hex & 0xff) as u8]
+}
+
+//
+// Parse a palette given as comma-separated hexadecimal colours, e.g. "c2532b,5bafd9,#7c1e79"
+//
+pub fn parse_palette(palette: &str) -> Result<Vec<u32>> {
+    let mut colours: Vec<u32> = vec![];
+
+    for colour in palette.split(',') {
+        let hex = colour.trim().trim_start_matches('#');
+
+        match u32::from_str_radix(hex, 16) {
+            Ok(c) if hex.len() == 6 => colours.push(c),
+            _ => return Err(miette!("Invalid colour '{}', expected six hexadecimal digits", colour))
+        }
+    }
+
+    Ok(colours)
+}
+
+//
+// glTF expects linear colour factors, while the palette colours are sRGB
+//
+pub fn srgb_to_linear(rgb: [u8; 3]) -> [f32; 3] {
+    rgb.map(|c| {
+        let c = c as f32 / 255.0;
+
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    })
+}
@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+use std::future::{ready, Ready};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, atomic::{AtomicU64, Ordering}};
+
+use actix_web::{dev::Payload, error, web, FromRequest, HttpRequest};
+
+use crate::CAAppData;
+
+// The session that the routes without a /sessions/{session_id} prefix address
+pub const DEFAULT_SESSION: &str = "default";
+
+//
+// The named simulation sessions, each with its own automata, parameters and history
+//
+pub struct Sessions {
+    sessions: Mutex<BTreeMap<String, Session>>,
+    next_token: AtomicU64
+}
+
+impl Sessions {
+
+    pub fn new(default: CAAppData) -> Self {
+        let sessions = Sessions {
+            sessions: Mutex::new(BTreeMap::new()),
+            next_token: AtomicU64::new(0)
+        };
+
+        sessions.create(DEFAULT_SESSION, default);
+
+        sessions
+    }
+
+    pub fn get(&self, id: &str) -> Option<Session> {
+        self.sessions.lock().unwrap().get(id).cloned()
+    }
+
+    pub fn ids(&self) -> Vec<String> {
+        self.sessions.lock().unwrap().keys().cloned().collect()
+    }
+
+    // Add a session, unless there's already one with the same id
+    pub fn create(&self, id: &str, state: CAAppData) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+
+        if sessions.contains_key(id) {
+            return false;
+        }
+
+        let token = self.next_token.fetch_add(1, Ordering::SeqCst);
+
+        sessions.insert(String::from(id), Session { id: String::from(id), token, state: Arc::new(Mutex::new(state)) });
+
+        true
+    }
+
+    // Remove a session and return it. The default session can't be removed.
+    pub fn delete(&self, id: &str) -> Option<Session> {
+        if id == DEFAULT_SESSION {
+            return None;
+        }
+
+        self.sessions.lock().unwrap().remove(id)
+    }
+
+}
+
+//
+// The state of the session that a request addresses: the one in its /sessions/{session_id} prefix, or the default one.
+// It dereferences to the Mutex<CAAppData> of that session, so that routes can lock it directly.
+// Every session that is created gets a new token, so that a session that replaces a deleted one with the same id
+// can be told apart from it.
+//
+#[derive(Clone)]
+pub struct Session {
+    id: String,
+    token: u64,
+    state: Arc<Mutex<CAAppData>>
+}
+
+impl Session {
+
+    pub fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    pub fn token(&self) -> u64 {
+        self.token
+    }
+
+    pub fn shared(&self) -> Arc<Mutex<CAAppData>> {
+        self.state.clone()
+    }
+
+}
+
+impl Deref for Session {
+    type Target = Mutex<CAAppData>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.state
+    }
+}
+
+impl FromRequest for Session {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let id = req.match_info().get("session_id").unwrap_or(DEFAULT_SESSION);
+
+        let session = req.app_data::<web::Data<Sessions>>()
+            .and_then(|sessions| sessions.get(id))
+            .ok_or_else(|| error::ErrorNotFound(format!("There is no session '{}'", id)));
+
+        ready(session)
+    }
+}
@@ -1,7 +1,7 @@
 /**
  * REF: THIS CODE WAS REFERENCED FROM PRIME, TU DELFT
- * 
- * 
+ *
+ *
  */
 
 
@@ -11,45 +11,28 @@ use std::mem;
 
 use base64::{Engine as _, engine::general_purpose as b64};
 use json::validation::Checked::Valid;
-use miette::{miette, IntoDiagnostic, Result};
+use miette::{IntoDiagnostic, Result};
+
+use crate::appdata::palette::srgb_to_linear;
+use crate::meshgeneration::triangle_mesh::TriangleMesh;
+
+use super::gltf_generation::to_glb;
 
 #[derive(Copy, Clone, Debug)]
 #[repr(C)]
 pub struct Vertex {
 	position: [f32; 3],
+	normal: [f32; 3],
 	color: [f32; 3],
 }
 
-#[derive(Copy, Clone)]
-struct BoundingCoords {
-	min: [f32; 3],
-	max: [f32; 3],
-}
-
-impl BoundingCoords {
-	/// Calculate bounding coordinates of a model, used for determining the clipping planes
-	fn from_points(points: &[Vertex]) -> Result<BoundingCoords> {
-		if points.is_empty() {
-			return Err(miette!("At least one point needs to be given to calculate bounding coordinates"));
-		}
-
-		let mut bounding_coords = BoundingCoords {
-			min: points[0].position,
-			max: points[0].position,
-		};
-
-		// Loop through the coordinates of all points, and update the minimum and maximum x, y, and z values
-		for point in points {
-			let p = point.position;
-			bounding_coords.min[0] = f32::min(bounding_coords.min[0], p[0]);
-			bounding_coords.min[1] = f32::min(bounding_coords.min[1], p[1]);
-			bounding_coords.min[2] = f32::min(bounding_coords.min[2], p[2]);
-			bounding_coords.max[0] = f32::max(bounding_coords.max[0], p[0]);
-			bounding_coords.max[1] = f32::max(bounding_coords.max[1], p[1]);
-			bounding_coords.max[2] = f32::max(bounding_coords.max[2], p[2]);
-		}
-		Ok(bounding_coords)
-	}
+//
+// One coloured object of a scene, e.g. the surface around the cells of one species
+//
+pub struct SceneNode<'a> {
+	pub name: String,
+	pub mesh: &'a TriangleMesh,
+	pub colour: [u8; 3],
 }
 
 fn to_padded_byte_vector<T>(vec: Vec<T>) -> Vec<u8> {
@@ -64,114 +47,217 @@ fn to_padded_byte_vector<T>(vec: Vec<T>) -> Vec<u8> {
 	new_vec
 }
 
-pub fn generate_gltf(triangle_vertices: &[([f32; 3], [f32; 3])]) -> Result<String> {
-	let triangle_vertices: Vec<Vertex> = triangle_vertices.iter().map(|v| Vertex { position: v.0, color: v.1 }).collect();
-	let triangle_vertices_len = triangle_vertices.len();
+pub fn generate_gltf(nodes: &[SceneNode]) -> Result<String> {
 
-	println!("Triangle vertices length: {}", triangle_vertices_len);
+	let (root, _) = scene_gltf_root(nodes, false);
 
-	let bounding_coords = BoundingCoords::from_points(&triangle_vertices)?;
+	json::serialize::to_string(&root).into_diagnostic()
 
-	let bin_content = to_padded_byte_vector(triangle_vertices);
-	let mut bin_content_b64 = String::from("data:application/octet-stream;base64,");
-	bin_content_b64.push_str(&b64::STANDARD.encode(bin_content));
+}
 
-	let buffer_length = (triangle_vertices_len * mem::size_of::<Vertex>()) as u32;
-	let buffer = json::Buffer {
-		byte_length: buffer_length,
-		extensions: Default::default(),
-		extras: Default::default(),
-		uri: Some(bin_content_b64),
-	};
-	let buffer_view = json::buffer::View {
-		buffer: json::Index::new(0),
-		byte_length: buffer.byte_length,
-		byte_offset: None,
-		byte_stride: Some(mem::size_of::<Vertex>() as u32),
-		extensions: Default::default(),
-		extras: Default::default(),
-		target: Some(Valid(json::buffer::Target::ArrayBuffer)),
-	};
-	let positions = json::Accessor {
-		buffer_view: Some(json::Index::new(0)),
-		byte_offset: 0,
-		count: triangle_vertices_len as u32,
-		component_type: Valid(json::accessor::GenericComponentType(json::accessor::ComponentType::F32)),
-		extensions: Default::default(),
-		extras: Default::default(),
-		type_: Valid(json::accessor::Type::Vec3),
-		min: Some(json::Value::from(Vec::from(bounding_coords.min))),
-		max: Some(json::Value::from(Vec::from(bounding_coords.max))),
-		normalized: false,
-		sparse: None,
-	};
-	let colors = json::Accessor {
-		buffer_view: Some(json::Index::new(0)),
-		byte_offset: (3 * mem::size_of::<f32>()) as u32,
-		count: triangle_vertices_len as u32,
-		component_type: Valid(json::accessor::GenericComponentType(json::accessor::ComponentType::F32)),
-		extensions: Default::default(),
-		extras: Default::default(),
-		type_: Valid(json::accessor::Type::Vec3),
-		min: None,
-		max: None,
-		normalized: false,
-		sparse: None,
-	};
-
-	let mut material = json::Material::default();
-	material.pbr_metallic_roughness.metallic_factor.0 = 0.3;
-
-	let primitive = json::mesh::Primitive {
-		attributes: {
-			let mut map = std::collections::HashMap::new();
-			map.insert(Valid(json::mesh::Semantic::Positions), json::Index::new(0));
-			map.insert(Valid(json::mesh::Semantic::Colors(0)), json::Index::new(1));
-			map
-		},
-		extensions: Default::default(),
-		extras: Default::default(),
-		indices: None,
-		material: Some(json::Index::new(0)),
-		mode: Valid(json::mesh::Mode::Triangles),
-		targets: None,
-	};
+pub fn generate_glb(nodes: &[SceneNode]) -> Result<Vec<u8>> {
 
-	let mesh = json::Mesh {
-		extensions: Default::default(),
-		extras: Default::default(),
-		primitives: vec![primitive],
-		weights: None,
-	};
+	let (root, bin) = scene_gltf_root(nodes, true);
 
-	let node = json::Node {
-		camera: None,
-		children: None,
-		extensions: Default::default(),
-		extras: Default::default(),
-		matrix: None,
-		mesh: Some(json::Index::new(0)),
-		rotation: None,
-		scale: None,
-		translation: None,
-		skin: None,
-		weights: None,
-	};
-
-	let root = json::Root {
-		accessors: vec![positions, colors],
-		buffers: vec![buffer],
-		buffer_views: vec![buffer_view],
-		meshes: vec![mesh],
-		nodes: vec![node],
-		scenes: vec![json::Scene {
+	to_glb(&root, bin)
+
+}
+
+//
+// Build a glTF scene with one named mesh, node and material per scene node.
+// Every node stores its interleaved vertices (position, normal, colour) and its indices in one shared buffer.
+// Nodes with an empty mesh are left out, so the scene may end up without any nodes.
+//
+fn scene_gltf_root(nodes: &[SceneNode], binary: bool) -> (json::Root, Vec<u8>) {
+
+	let mut root = json::Root::default();
+	let mut bin: Vec<u8> = vec![];
+	let mut scene_nodes: Vec<json::Index<json::Node>> = vec![];
+
+	for node in nodes.iter().filter(|n| !n.mesh.is_empty()) {
+
+		// glTF expects both the vertex colours and the material colours in linear space
+		let colour = srgb_to_linear(node.colour);
+
+		let vertices: Vec<Vertex> = node.mesh.positions.iter().zip(node.mesh.normals.iter())
+			.map(|(p, n)| Vertex { position: *p, normal: *n, color: colour })
+			.collect();
+
+		let num_vertices = vertices.len();
+		let num_indices = node.mesh.indices.len();
+
+		let (min, max) = node.mesh.bounds();
+
+		// Append the vertices and indices of this node to the buffer
+		let vertex_offset = bin.len();
+		bin.extend_from_slice(&to_padded_byte_vector(vertices));
+
+		let index_offset = bin.len();
+		bin.extend_from_slice(&to_padded_byte_vector(node.mesh.indices.clone()));
+
+		let vertex_view = json::Index::new(root.buffer_views.len() as u32);
+		root.buffer_views.push(json::buffer::View {
+			buffer: json::Index::new(0),
+			byte_length: (num_vertices * mem::size_of::<Vertex>()) as u32,
+			byte_offset: Some(vertex_offset as u32),
+			byte_stride: Some(mem::size_of::<Vertex>() as u32),
 			extensions: Default::default(),
 			extras: Default::default(),
-			nodes: vec![json::Index::new(0)],
-		}],
-		materials: vec![material],
-		..Default::default()
-	};
+			name: None,
+			target: Some(Valid(json::buffer::Target::ArrayBuffer)),
+		});
 
-	json::serialize::to_string(&root).into_diagnostic()
-}
\ No newline at end of file
+		let index_view = json::Index::new(root.buffer_views.len() as u32);
+		root.buffer_views.push(json::buffer::View {
+			buffer: json::Index::new(0),
+			byte_length: (num_indices * mem::size_of::<u32>()) as u32,
+			byte_offset: Some(index_offset as u32),
+			byte_stride: None,
+			extensions: Default::default(),
+			extras: Default::default(),
+			name: None,
+			target: Some(Valid(json::buffer::Target::ElementArrayBuffer)),
+		});
+
+		let positions = json::Index::new(root.accessors.len() as u32);
+		root.accessors.push(json::Accessor {
+			buffer_view: Some(vertex_view),
+			byte_offset: 0,
+			count: num_vertices as u32,
+			component_type: Valid(json::accessor::GenericComponentType(json::accessor::ComponentType::F32)),
+			extensions: Default::default(),
+			extras: Default::default(),
+			type_: Valid(json::accessor::Type::Vec3),
+			min: Some(json::Value::from(Vec::from(min))),
+			max: Some(json::Value::from(Vec::from(max))),
+			name: None,
+			normalized: false,
+			sparse: None,
+		});
+
+		let normals = json::Index::new(root.accessors.len() as u32);
+		root.accessors.push(json::Accessor {
+			buffer_view: Some(vertex_view),
+			byte_offset: (3 * mem::size_of::<f32>()) as u32,
+			count: num_vertices as u32,
+			component_type: Valid(json::accessor::GenericComponentType(json::accessor::ComponentType::F32)),
+			extensions: Default::default(),
+			extras: Default::default(),
+			type_: Valid(json::accessor::Type::Vec3),
+			min: None,
+			max: None,
+			name: None,
+			normalized: false,
+			sparse: None,
+		});
+
+		let colors = json::Index::new(root.accessors.len() as u32);
+		root.accessors.push(json::Accessor {
+			buffer_view: Some(vertex_view),
+			byte_offset: (6 * mem::size_of::<f32>()) as u32,
+			count: num_vertices as u32,
+			component_type: Valid(json::accessor::GenericComponentType(json::accessor::ComponentType::F32)),
+			extensions: Default::default(),
+			extras: Default::default(),
+			type_: Valid(json::accessor::Type::Vec3),
+			min: None,
+			max: None,
+			name: None,
+			normalized: false,
+			sparse: None,
+		});
+
+		let indices = json::Index::new(root.accessors.len() as u32);
+		root.accessors.push(json::Accessor {
+			buffer_view: Some(index_view),
+			byte_offset: 0,
+			count: num_indices as u32,
+			component_type: Valid(json::accessor::GenericComponentType(json::accessor::ComponentType::U32)),
+			extensions: Default::default(),
+			extras: Default::default(),
+			type_: Valid(json::accessor::Type::Scalar),
+			min: None,
+			max: None,
+			name: None,
+			normalized: false,
+			sparse: None,
+		});
+
+		// The vertex colours are multiplied with the base colour, so the material itself stays white.
+		// It's still named after the node, so that viewers list one material per species.
+		let mut material = json::Material::default();
+		material.name = Some(node.name.clone());
+		material.pbr_metallic_roughness.metallic_factor.0 = 0.3;
+		let material_index = json::Index::new(root.materials.len() as u32);
+		root.materials.push(material);
+
+		let primitive = json::mesh::Primitive {
+			attributes: {
+				let mut map = std::collections::HashMap::new();
+				map.insert(Valid(json::mesh::Semantic::Positions), positions);
+				map.insert(Valid(json::mesh::Semantic::Normals), normals);
+				map.insert(Valid(json::mesh::Semantic::Colors(0)), colors);
+				map
+			},
+			extensions: Default::default(),
+			extras: Default::default(),
+			indices: Some(indices),
+			material: Some(material_index),
+			mode: Valid(json::mesh::Mode::Triangles),
+			targets: None,
+		};
+
+		let mesh = json::Index::new(root.meshes.len() as u32);
+		root.meshes.push(json::Mesh {
+			extensions: Default::default(),
+			extras: Default::default(),
+			name: Some(node.name.clone()),
+			primitives: vec![primitive],
+			weights: None,
+		});
+
+		scene_nodes.push(json::Index::new(root.nodes.len() as u32));
+		root.nodes.push(json::Node {
+			camera: None,
+			children: None,
+			extensions: Default::default(),
+			extras: Default::default(),
+			matrix: None,
+			mesh: Some(mesh),
+			name: Some(node.name.clone()),
+			rotation: None,
+			scale: None,
+			translation: None,
+			skin: None,
+			weights: None,
+		});
+
+	}
+
+	// glTF doesn't allow empty buffers, so there is only a buffer if there is any data
+	if !bin.is_empty() {
+		root.buffers.push(json::Buffer {
+			byte_length: bin.len() as u32,
+			extensions: Default::default(),
+			extras: Default::default(),
+			name: None,
+			uri: if binary {
+				None
+			} else {
+				let mut bin_content_b64 = String::from("data:application/octet-stream;base64,");
+				bin_content_b64.push_str(&b64::STANDARD.encode(&bin));
+				Some(bin_content_b64)
+			},
+		});
+	}
+
+	root.scenes.push(json::Scene {
+		extensions: Default::default(),
+		extras: Default::default(),
+		name: None,
+		nodes: scene_nodes,
+	});
+
+	(root, bin)
+
+}
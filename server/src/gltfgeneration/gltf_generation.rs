@@ -6,42 +6,13 @@ use base64::{Engine as _, engine::general_purpose as b64};
 use json::validation::Checked::Valid;
 use miette::{miette, IntoDiagnostic, Result};
 
+use crate::meshgeneration::triangle_mesh::TriangleMesh;
+
 #[derive(Copy, Clone, Debug)]
 #[repr(C)]
 pub struct Vertex {
-	position: [f32; 3]
-}
-
-#[derive(Copy, Clone)]
-struct BoundingCoords {
-	min: [f32; 3],
-	max: [f32; 3],
-}
-
-impl BoundingCoords {
-	/// Calculate bounding coordinates of a model, used for determining the clipping planes
-	fn from_points(points: &[Vertex]) -> Result<BoundingCoords> {
-		if points.is_empty() {
-			return Err(miette!("At least one point needs to be given to calculate bounding coordinates"));
-		}
-
-		let mut bounding_coords = BoundingCoords {
-			min: points[0].position,
-			max: points[0].position,
-		};
-
-		// Loop through the coordinates of all points, and update the minimum and maximum x, y, and z values
-		for point in points {
-			let p = point.position;
-			bounding_coords.min[0] = f32::min(bounding_coords.min[0], p[0]);
-			bounding_coords.min[1] = f32::min(bounding_coords.min[1], p[1]);
-			bounding_coords.min[2] = f32::min(bounding_coords.min[2], p[2]);
-			bounding_coords.max[0] = f32::max(bounding_coords.max[0], p[0]);
-			bounding_coords.max[1] = f32::max(bounding_coords.max[1], p[1]);
-			bounding_coords.max[2] = f32::max(bounding_coords.max[2], p[2]);
-		}
-		Ok(bounding_coords)
-	}
+	position: [f32; 3],
+	normal: [f32; 3]
 }
 
 fn to_padded_byte_vector<T>(vec: Vec<T>) -> Vec<u8> {
@@ -56,120 +27,196 @@ fn to_padded_byte_vector<T>(vec: Vec<T>) -> Vec<u8> {
 	new_vec
 }
 
-pub fn generate_large_gltf(input_vertices: &[[f32;3]]) -> Result<String> {
+pub fn generate_mesh_gltf(mesh: &TriangleMesh) -> Result<String> {
 
-    let vertices_per_buffer: usize = 60000;
+    let (root, _) = mesh_gltf_root(mesh, false)?;
 
-    //
-    // CREATE N VECTORS OF VERTICES
-    //
+	json::serialize::to_string(&root).into_diagnostic()
 
-    let mut vertices: Vec<Vec<Vertex>> = vec![];
+}
 
-    let mut working_vector = 0;
+//
+// Binary glTF (.glb): the same scene, but with the vertex and index data in one binary chunk
+// instead of a base64-encoded data URI.
+//
+pub fn generate_mesh_glb(mesh: &TriangleMesh) -> Result<Vec<u8>> {
 
-    for v in 0..input_vertices.len() {
+    let (root, bin) = mesh_gltf_root(mesh, true)?;
 
-        if v % vertices_per_buffer == 0 {
-            // We've reached either v=0 or v is a multiple of 60.000
-            vertices.push(vec![]);
-            working_vector = vertices.len() - 1;
-        }
+    to_glb(&root, bin)
+
+}
 
-        // Add the vertex at this index to the current working_vector
-        vertices[working_vector].push(Vertex {position: input_vertices[v]});
+//
+// Pack a glTF root and its binary buffer into the .glb container format:
+// a 12-byte header followed by a JSON chunk and a BIN chunk, both padded to four bytes.
+//
+pub fn to_glb(root: &json::Root, mut bin: Vec<u8>) -> Result<Vec<u8>> {
 
+    let mut json_content = json::serialize::to_vec(root).into_diagnostic()?;
+
+    // The JSON chunk is padded with spaces, the BIN chunk with zeros
+    while json_content.len() % 4 != 0 {
+        json_content.push(b' ');
     }
 
-    //
-    // CREATE N BOUNDING COORDINATES, THAT MATCH THE VECTORS OF VERTICES ABOVE
-    //
+    while bin.len() % 4 != 0 {
+        bin.push(0);
+    }
 
-    let mut bounding_coords: Vec<BoundingCoords> = vec![];
+    // The BIN chunk is left out entirely if there is no binary data
+    let bin_chunk_length = if bin.is_empty() { 0 } else { 8 + bin.len() };
+    let total_length = 12 + 8 + json_content.len() + bin_chunk_length;
 
-    for v in &vertices {
-        bounding_coords.push(BoundingCoords::from_points(v)?);
+    let mut glb: Vec<u8> = Vec::with_capacity(total_length);
+
+    // Header: magic, version and total length
+    glb.extend_from_slice(b"glTF");
+    glb.extend_from_slice(&2u32.to_le_bytes());
+    glb.extend_from_slice(&(total_length as u32).to_le_bytes());
+
+    // JSON chunk
+    glb.extend_from_slice(&(json_content.len() as u32).to_le_bytes());
+    glb.extend_from_slice(b"JSON");
+    glb.extend_from_slice(&json_content);
+
+    // BIN chunk
+    if !bin.is_empty() {
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&bin);
     }
 
-    //
-    // CREATE A PADDED BYTE VECTOR FOR EACH VECTOR OF VERTICES ABOVE
-    //
+    Ok(glb)
 
-    let mut bin_base64_contents: Vec<String> = vec![];
+}
 
-    for v in vertices.clone() {
-        let bin_content = to_padded_byte_vector(v);
-        let mut bin_content_b64 = String::from("data:application/octet-stream;base64,");
-        bin_content_b64.push_str(&b64::STANDARD.encode(bin_content));
+//
+// Build the glTF scene for an indexed mesh: one primitive with positions, normals and u32 indices.
+// Both the interleaved vertices and the indices are stored in one buffer. If 'binary' is set, this
+// buffer is returned alongside the root, otherwise it is embedded as a base64 data URI.
+//
+fn mesh_gltf_root(mesh: &TriangleMesh, binary: bool) -> Result<(json::Root, Vec<u8>)> {
 
-        // Push these base64 bin-contents to the vector
-        bin_base64_contents.push(bin_content_b64);
+    if mesh.is_empty() {
+        return Err(miette!("At least one triangle is needed to generate a glTF mesh"));
     }
 
     //
-    // CREATING N BUFFERS OF SIZE 60.000
+    // CREATE THE INTERLEAVED VERTICES AND THEIR BOUNDING COORDINATES
     //
 
-    let mut buffers: Vec<json::Buffer> = vec![];
+    let vertices: Vec<Vertex> = mesh.positions.iter().zip(mesh.normals.iter())
+        .map(|(p, n)| Vertex { position: *p, normal: *n })
+        .collect();
 
-    for i in 0..vertices.len() {
-        buffers.push(json::Buffer {
-            byte_length: (vertices[i].len() * mem::size_of::<Vertex>()) as u32,
-            extensions: Default::default(),
-            extras: Default::default(),
-            uri: Some(String::from(&bin_base64_contents[i]))
-        });
-    }
+    let num_vertices = vertices.len();
+    let num_indices = mesh.indices.len();
 
+    let (min, max) = mesh.bounds();
 
     //
-    // CREATING A BUFFER-VIEW FOR EACH OF THESE BUFFERS
+    // CREATE ONE BUFFER: FIRST THE VERTICES, THEN THE INDICES
     //
 
-    let mut buffer_views: Vec<json::buffer::View> = vec![];
-
-    // For each buffer
-    for buf in 0..buffers.len() {
-        // Add a view that references this buffer and push it to the array
-        buffer_views.push(json::buffer::View {
-            buffer: json::Index::new(buf as u32),
-            byte_length: buffers[buf].byte_length,
-            byte_offset: None,
-            byte_stride: Some(mem::size_of::<Vertex>() as u32),
-            extensions: Default::default(),
-            extras: Default::default(),
-            target: Some(Valid(json::buffer::Target::ArrayBuffer))
-        });
-    }
+    let vertex_bytes = to_padded_byte_vector(vertices);
+    let index_bytes = to_padded_byte_vector(mesh.indices.clone());
+
+    let vertex_bytes_length = num_vertices * mem::size_of::<Vertex>();
+    let index_bytes_length = num_indices * mem::size_of::<u32>();
+
+    let mut bin: Vec<u8> = vertex_bytes;
+    let index_offset = bin.len();
+    bin.extend_from_slice(&index_bytes);
+
+    let buffer = json::Buffer {
+        byte_length: bin.len() as u32,
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        uri: if binary {
+            None
+        } else {
+            let mut bin_content_b64 = String::from("data:application/octet-stream;base64,");
+            bin_content_b64.push_str(&b64::STANDARD.encode(&bin));
+            Some(bin_content_b64)
+        }
+    };
 
+    //
+    // CREATE A BUFFER-VIEW FOR THE VERTICES AND ONE FOR THE INDICES
+    //
+
+    let vertex_view = json::buffer::View {
+        buffer: json::Index::new(0),
+        byte_length: vertex_bytes_length as u32,
+        byte_offset: None,
+        byte_stride: Some(mem::size_of::<Vertex>() as u32),
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        target: Some(Valid(json::buffer::Target::ArrayBuffer))
+    };
+
+    let index_view = json::buffer::View {
+        buffer: json::Index::new(0),
+        byte_length: index_bytes_length as u32,
+        byte_offset: Some(index_offset as u32),
+        byte_stride: None,
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        target: Some(Valid(json::buffer::Target::ElementArrayBuffer))
+    };
 
     //
-    // CREATE AN ACCESSOR FOR EACH BUFFER THAT CONTAINS 60.000 POINTS
+    // CREATE ACCESSORS FOR THE POSITIONS, NORMALS AND INDICES
     //
 
-    let mut accessors: Vec<json::Accessor> = vec![];
-
-    // For each buffer-view, create an accessor
-    // includes:
-    // - The number of triangles in that buffer (count)
-    // - The min and max bounding coordinates
-
-    for bufview in 0..buffer_views.len() {
-        // Add an accessor
-        accessors.push(json::Accessor {
-            buffer_view: Some(json::Index::new(bufview as u32)),
-            byte_offset: 0,
-            count: vertices[bufview].len() as u32,
-            component_type: Valid(json::accessor::GenericComponentType(json::accessor::ComponentType::F32)),
-            extensions: Default::default(),
-            extras: Default::default(),
-            type_: Valid(json::accessor::Type::Vec3),
-            min: Some(json::Value::from(Vec::from(bounding_coords[bufview].min))),
-            max: Some(json::Value::from(Vec::from(bounding_coords[bufview].max))),
-            normalized: false,
-            sparse: None
-        });
-    }
+    let positions = json::Accessor {
+        buffer_view: Some(json::Index::new(0)),
+        byte_offset: 0,
+        count: num_vertices as u32,
+        component_type: Valid(json::accessor::GenericComponentType(json::accessor::ComponentType::F32)),
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        type_: Valid(json::accessor::Type::Vec3),
+        min: Some(json::Value::from(Vec::from(min))),
+        max: Some(json::Value::from(Vec::from(max))),
+        normalized: false,
+        sparse: None
+    };
+
+    let normals = json::Accessor {
+        buffer_view: Some(json::Index::new(0)),
+        byte_offset: (3 * mem::size_of::<f32>()) as u32,
+        count: num_vertices as u32,
+        component_type: Valid(json::accessor::GenericComponentType(json::accessor::ComponentType::F32)),
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        type_: Valid(json::accessor::Type::Vec3),
+        min: None,
+        max: None,
+        normalized: false,
+        sparse: None
+    };
+
+    let indices = json::Accessor {
+        buffer_view: Some(json::Index::new(1)),
+        byte_offset: 0,
+        count: num_indices as u32,
+        component_type: Valid(json::accessor::GenericComponentType(json::accessor::ComponentType::U32)),
+        extensions: Default::default(),
+        extras: Default::default(),
+        name: None,
+        type_: Valid(json::accessor::Type::Scalar),
+        min: None,
+        max: None,
+        normalized: false,
+        sparse: None
+    };
 
 
     //
@@ -181,35 +228,30 @@ pub fn generate_large_gltf(input_vertices: &[[f32;3]]) -> Result<String> {
 
 
     //
-    // CREATE PRIMITIVES FOR EACH ACCESSOR
+    // CREATE ONE INDEXED PRIMITIVE
     //
 
-    let mut primitives: Vec<json::mesh::Primitive> = vec![];
-
-    // For each accessor, create a primitive that references the
-    // right positions
-    for acc in 0..accessors.len() {
-        primitives.push(json::mesh::Primitive {
-            attributes: {
-                let mut map = std::collections::HashMap::new();
-                map.insert(Valid(json::mesh::Semantic::Positions), json::Index::new(acc as u32));
-                map
-            },
-            extensions: Default::default(),
-            extras: Default::default(),
-            indices: None,
-            material: Some(json::Index::new(0)),
-            mode: Valid(json::mesh::Mode::Triangles),
-            targets: None
-        });
-    }
-
-
-    // Create a mesh from all the generated primitives
-    let mesh = json::Mesh {
+    let primitive = json::mesh::Primitive {
+        attributes: {
+            let mut map = std::collections::HashMap::new();
+            map.insert(Valid(json::mesh::Semantic::Positions), json::Index::new(0));
+            map.insert(Valid(json::mesh::Semantic::Normals), json::Index::new(1));
+            map
+        },
+        extensions: Default::default(),
+        extras: Default::default(),
+        indices: Some(json::Index::new(2)),
+        material: Some(json::Index::new(0)),
+        mode: Valid(json::mesh::Mode::Triangles),
+        targets: None
+    };
+
+    // Create a mesh from the primitive
+    let gltf_mesh = json::Mesh {
 		extensions: Default::default(),
 		extras: Default::default(),
-		primitives,
+		name: None,
+		primitives: vec![primitive],
 		weights: None,
 	};
 
@@ -219,6 +261,7 @@ pub fn generate_large_gltf(input_vertices: &[[f32;3]]) -> Result<String> {
 		children: None,
 		extensions: Default::default(),
 		extras: Default::default(),
+		name: None,
 		matrix: None,
 		mesh: Some(json::Index::new(0)),
 		rotation: None,
@@ -230,20 +273,21 @@ pub fn generate_large_gltf(input_vertices: &[[f32;3]]) -> Result<String> {
 
     // Create the root that stores all information
 	let root = json::Root {
-		accessors,
-		buffers,
-		buffer_views,
-		meshes: vec![mesh],
+		accessors: vec![positions, normals, indices],
+		buffers: vec![buffer],
+		buffer_views: vec![vertex_view, index_view],
+		meshes: vec![gltf_mesh],
 		nodes: vec![node],
 		scenes: vec![json::Scene {
 			extensions: Default::default(),
 			extras: Default::default(),
+			name: None,
 			nodes: vec![json::Index::new(0)],
 		}],
 		materials: vec![material],
 		..Default::default()
 	};
 
-	json::serialize::to_string(&root).into_diagnostic()
+	Ok((root, bin))
 
 }
\ No newline at end of file
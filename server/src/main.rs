@@ -1,15 +1,20 @@
+mod analysis;
 mod appdata;
 mod routes;
 mod gltfgeneration;
+mod meshgeneration;
+mod volumeio;
 
-use std::{sync::Mutex, time::Instant};
+use std::time::Instant;
 
 use actix_cors::Cors;
 use actix_web::{App, HttpServer, web};
-use routes::{debug_routes::*, cpu_get::*, cpu_post::*, gpu_get::*, gpu_post::*, nchem_get::*, nchem_post::*, general_get::*, general_post::*, batch::*, benchmarks::{compare_cpu_gpu::{benchmarks_compare_cpu_gpu, benchmarks_compare_cpu_gpu_catch_up}, gpu_shader_increment::benchmarks_gpu_shader_increment}};
+use routes::{debug_routes::*, cpu_get::*, cpu_post::*, gpu_get::*, gpu_post::*, nchem_get::*, nchem_post::*, general_get::*, general_post::*, batch::*, jobs::*, sessions::*, benchmarks::{compare_cpu_gpu::{benchmarks_compare_cpu_gpu, benchmarks_compare_cpu_gpu_catch_up}, gpu_shader_increment::benchmarks_gpu_shader_increment}};
 use appdata::dim3d::automata::{automaton_cpu::CPUCellularAutomaton3D, automaton::CellularAutomaton3D};
 use appdata::dim3d::automata::automaton_gpu::GPUCellularAutomaton3D;
 use appdata::dim3d::automata::automaton_gpu_n_chemicals::{GPUNChemicalsCellularAutomaton3D, CAChemicalGroup, CAChemical};
+use appdata::jobs::JobManager;
+use appdata::sessions::Sessions;
 
 use serde::{Serialize, Deserialize};
 
@@ -33,6 +38,88 @@ impl CAAppData {
     }
 }
 
+//
+// The routes of a session, which are registered both without prefix (for the default session) and under /sessions/{session_id}
+//
+fn configure_routes(cfg: &mut web::ServiceConfig) {
+    cfg
+        .service(performance_check)
+        .service(grid3d)
+        .service(cpu_get_current_state)
+        .service(cpu_get_current_state_triangles)
+        .service(cpu_get_iterations)
+        .service(cpu_post_initialise)
+        .service(cpu_post_clear_all_voxels)
+        .service(cpu_post_spread_chemicals_randomly)
+        .service(cpu_post_run_iteration)
+        .service(gpu_get_current_state)
+        .service(gpu_get_current_state_triangles)
+        .service(gpu_get_iterations)
+        .service(gpu_get_stability_analysis)
+        .service(gpu_post_initialise)
+        .service(gpu_post_clear_all_voxels)
+        .service(gpu_post_spread_chemicals_randomly)
+        .service(gpu_post_run_iteration)
+        .service(nchem_get_current_state)
+        .service(nchem_get_current_state_triangles)
+        .service(nchem_get_current_state_npy)
+        .service(nchem_get_current_state_npz)
+        .service(nchem_get_last_changed_npy)
+        .service(nchem_get_current_state_vox)
+        .service(nchem_get_current_state_species_scene)
+        .service(nchem_get_geometry)
+        .service(nchem_get_geometry_history)
+        .service(nchem_get_components)
+        .service(nchem_get_topology)
+        .service(nchem_get_minkowski_functionals)
+        .service(nchem_get_structure_factor)
+        .service(nchem_get_structure_factor_history)
+        .service(nchem_get_stability_analysis)
+        .service(nchem_get_iterations)
+        .service(nchem_get_chemical_capture)
+        .service(nchem_get_order_parameter)
+        .service(nchem_get_multiscale_order_parameter)
+        .service(nchem_get_segregation_matrix)
+        .service(nchem_get_segregation_history)
+        .service(nchem_get_metrics)
+        .service(nchem_get_observers)
+        .service(nchem_get_flip_activity)
+        .service(nchem_get_damage_spreading)
+        .service(nchem_get_species_configuration)
+        .service(nchem_state_has_converged)
+        .service(nchem_post_initialise)
+        .service(nchem_post_clear_all_voxels)
+        .service(nchem_post_spread_chemicals_randomly)
+        .service(nchem_post_run_iteration)
+        .service(nchem_post_set_chemical_capture)
+        .service(nchem_set_species_configuration)
+        .service(nchem_post_set_state_npy)
+        .service(nchem_post_set_state_vox)
+        .service(nchem_post_compare_state_npy)
+        .service(nchem_post_set_geometry_recording)
+        .service(nchem_post_set_structure_factor_recording)
+        .service(nchem_post_set_segregation_recording)
+        .service(nchem_post_set_order_parameter_neighbourhood)
+        .service(nchem_post_add_observer)
+        .service(nchem_post_remove_observer)
+        .service(general_get_automaton_size)
+        .service(general_compare_automata)
+        .service(general_spread_chemicals_randomly)
+        .service(general_create_activator_patch)
+        .service(general_set_evaluation_mode)
+        .service(general_set_stochastic_update)
+        .service(benchmarks_compare_cpu_gpu)
+        .service(benchmarks_compare_cpu_gpu_catch_up)
+        .service(benchmarks_gpu_shader_increment)
+        .service(batch_run_experiment)
+        .service(jobs_post)
+        .service(jobs_get_all)
+        .service(jobs_get)
+        .service(jobs_get_result)
+        .service(jobs_delete)
+        .service(jobs_post_cancel);
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
 
@@ -144,48 +231,24 @@ async fn main() -> std::io::Result<()> {
     println!("Done!");
 
 
-    let app_state = web::Data::new(Mutex::new(ca_app_data));
+    // The state of the default session; other sessions are created through /sessions
+    let sessions = web::Data::new(Sessions::new(ca_app_data));
+
+    // Jobs run in the background, on the state of the session they were queued in
+    let job_manager = web::Data::new(JobManager::new());
 
     HttpServer::new(move || {
         App::new()
             .wrap(Cors::default().allow_any_origin().send_wildcard().allow_any_header().allow_any_method())
-            .app_data(app_state.clone())
-            .service(performance_check)
-            .service(grid3d)
-            .service(cpu_get_current_state)
-            .service(cpu_get_current_state_triangles)
-            .service(cpu_get_iterations)
-            .service(cpu_post_initialise)
-            .service(cpu_post_clear_all_voxels)
-            .service(cpu_post_spread_chemicals_randomly)
-            .service(cpu_post_run_iteration)
-            .service(gpu_get_current_state)
-            .service(gpu_get_current_state_triangles)
-            .service(gpu_get_iterations)
-            .service(gpu_post_initialise)
-            .service(gpu_post_clear_all_voxels)
-            .service(gpu_post_spread_chemicals_randomly)
-            .service(gpu_post_run_iteration)
-            .service(nchem_get_current_state)
-            .service(nchem_get_current_state_triangles)
-            .service(nchem_get_iterations)
-            .service(nchem_get_chemical_capture)
-            .service(nchem_get_order_parameter)
-            .service(nchem_get_species_configuration)
-            .service(nchem_state_has_converged)
-            .service(nchem_post_initialise)
-            .service(nchem_post_clear_all_voxels)
-            .service(nchem_post_spread_chemicals_randomly)
-            .service(nchem_post_run_iteration)
-            .service(nchem_post_set_chemical_capture)
-            .service(nchem_set_species_configuration)
-            .service(general_get_automaton_size)
-            .service(general_spread_chemicals_randomly)
-            .service(general_create_activator_patch)
-            .service(benchmarks_compare_cpu_gpu)
-            .service(benchmarks_compare_cpu_gpu_catch_up)
-            .service(benchmarks_gpu_shader_increment)
-            .service(batch_run_experiment)
+            .app_data(sessions.clone())
+            .app_data(job_manager.clone())
+            .app_data(web::PayloadConfig::new(64 * 1024 * 1024))
+            .service(sessions_post)
+            .service(sessions_get)
+            .service(sessions_delete)
+            // Every route addresses the default session, or the session in its prefix
+            .configure(configure_routes)
+            .service(web::scope("/sessions/{session_id}").configure(configure_routes))
             
     })
     .bind(("127.0.0.1", 7878))?
@@ -0,0 +1,14 @@
+pub mod triangle_mesh;
+pub mod mesh_options;
+pub mod scalar_field;
+pub mod extractor;
+pub mod surface_nets;
+pub mod greedy_voxels;
+pub mod cell_type_source;
+pub mod smoothing;
+pub mod decimation;
+pub mod postprocessing;
+pub mod mesh_format;
+pub mod stl;
+pub mod ply;
+pub mod obj;
\ No newline at end of file
@@ -0,0 +1,153 @@
+use isosurface::source::Source;
+
+use miette::{miette, Result};
+
+use crate::appdata::dim3d::automata::automaton::CellularAutomaton3D;
+
+use super::{extractor::{ExtractorKind, new_extractor}, mesh_options::MeshOptions, postprocessing::postprocess, scalar_field::{FieldSource, ScalarField, ScalarFieldKind}, triangle_mesh::TriangleMesh};
+
+//
+// A marching cubes source that encloses one cell-type of an automaton.
+// Unlike the Source implementations of the automata themselves, this doesn't depend on
+// server-wide state such as the captured chemical, so any cell-type can be meshed at any time.
+//
+pub struct CellTypeSource<'a, A: CellularAutomaton3D + ?Sized> {
+    pub automaton: &'a A,
+    pub cell_type: u32
+}
+
+impl<'a, A: CellularAutomaton3D + ?Sized> Source for CellTypeSource<'a, A> {
+    fn sample(&self, x: f32, y: f32, z: f32) -> f32 {
+        // Assignment: return negative values for 'inside' and positive for 'outside'.
+        // Cells of the enclosed cell-type are inside.
+
+        // Caution: the source will be sampled between (0, 0, 0) and (1, 1, 1)
+
+        let size = self.automaton.size();
+
+        let xindex = usize::min((x * (size - 1) as f32).round() as usize, size - 1);
+        let yindex = usize::min((y * (size - 1) as f32).round() as usize, size - 1);
+        let zindex = usize::min((z * (size - 1) as f32).round() as usize, size - 1);
+
+        if self.automaton.get(xindex, yindex, zindex) == self.cell_type {
+            -1.0
+        } else {
+            1.0
+        }
+    }
+}
+
+//
+// The same source, but sampled on a lattice of (size + 1) points per axis, of which the last one
+// wraps around to the first cell. This meshes the gap between the last and the first cell as well,
+// so that structures that cross a domain face continue on the opposite face.
+//
+pub struct PeriodicCellTypeSource<'a, A: CellularAutomaton3D + ?Sized> {
+    pub automaton: &'a A,
+    pub cell_type: u32
+}
+
+impl<'a, A: CellularAutomaton3D + ?Sized> Source for PeriodicCellTypeSource<'a, A> {
+    fn sample(&self, x: f32, y: f32, z: f32) -> f32 {
+        let size = self.automaton.size();
+
+        let xindex = (x * size as f32).round() as usize % size;
+        let yindex = (y * size as f32).round() as usize % size;
+        let zindex = (z * size as f32).round() as usize % size;
+
+        if self.automaton.get(xindex, yindex, zindex) == self.cell_type {
+            -1.0
+        } else {
+            1.0
+        }
+    }
+}
+
+//
+// Extract the surface of a source with the requested extractor. The source is sampled on a lattice
+// of 'resolution' points along every axis, and the vertices are scaled to the size of the automaton.
+//
+fn extract_source(source: &dyn Source, extractor: ExtractorKind, resolution: usize, periodic: bool, size: usize) -> TriangleMesh {
+    let mut vertices: Vec<f32> = vec![];
+    let mut indices: Vec<u32> = vec![];
+
+    new_extractor(extractor, resolution, periodic).extract(source, &mut vertices, &mut indices);
+
+    TriangleMesh::from_flat(&vertices, indices, size as f32)
+}
+
+//
+// Extract the surface around all cells of one cell-type
+//
+pub fn extract_cell_type_mesh<A: CellularAutomaton3D + ?Sized>(automaton: &A, cell_type: u32, extractor: ExtractorKind) -> TriangleMesh {
+    extract_source(&CellTypeSource { automaton, cell_type }, extractor, automaton.size(), false, automaton.size())
+}
+
+//
+// Extract the surface around all cells of one cell-type on the periodic domain.
+// Cell i lies at coordinate i, so the surface spans exactly one period of 'size' along every axis:
+// every vertex on a domain face has an image on the opposite face.
+//
+pub fn extract_periodic_cell_type_mesh<A: CellularAutomaton3D + ?Sized>(automaton: &A, cell_type: u32, extractor: ExtractorKind) -> TriangleMesh {
+    extract_source(&PeriodicCellTypeSource { automaton, cell_type }, extractor, automaton.size() + 1, true, automaton.size())
+}
+
+//
+// Extract the surface of a smooth scalar field of one cell-type
+//
+pub fn extract_field_mesh<A: CellularAutomaton3D + ?Sized>(automaton: &A, cell_type: u32, options: &MeshOptions) -> Result<TriangleMesh> {
+    let size = automaton.size();
+
+    let field = match options.field {
+        ScalarFieldKind::Indicator | ScalarFieldKind::Trilinear => ScalarField::indicator(automaton, cell_type),
+        ScalarFieldKind::Gaussian => {
+            // The radius has been checked by MeshOptions::validate
+            let radius = options.radius.unwrap_or(1.0);
+
+            ScalarField::indicator(automaton, cell_type).gaussian_blurred(radius)
+        },
+        ScalarFieldKind::Influence => match automaton.influence_field(cell_type) {
+            Some(values) => ScalarField::new(size, values),
+            None => return Err(miette!("There is no influence field for cell-type {}, run an iteration first", cell_type))
+        }
+    };
+
+    // A periodic lattice has an extra sample that coincides with the first one
+    let resolution = options.resolution.unwrap_or(if options.periodic { size + 1 } else { size });
+
+    let source = FieldSource {
+        field: &field,
+        iso_level: options.iso_level.unwrap_or(options.field.default_iso_level()),
+        trilinear: options.field != ScalarFieldKind::Indicator,
+        periodic: options.periodic
+    };
+
+    Ok(extract_source(&source, options.extractor, resolution, options.periodic, size))
+}
+
+//
+// Extract and post-process the surface around one cell-type as described by the mesh options.
+// Tiling only makes sense for a periodic surface, so it implies periodic extraction.
+//
+pub fn extract_cell_type_mesh_with<A: CellularAutomaton3D + ?Sized>(automaton: &A, cell_type: u32, options: &MeshOptions) -> Result<TriangleMesh> {
+    options.validate(automaton.size())?;
+
+    let options = MeshOptions { periodic: options.periodic || options.tile, ..*options };
+
+    let mesh = if !options.is_blocky() {
+        extract_field_mesh(automaton, cell_type, &options)?
+    } else if options.periodic {
+        extract_periodic_cell_type_mesh(automaton, cell_type, options.extractor)
+    } else {
+        extract_cell_type_mesh(automaton, cell_type, options.extractor)
+    };
+
+    // Post-processing happens before tiling, which keeps it cheap and the copies identical
+    let mesh = postprocess(mesh, &options);
+
+    if options.tile {
+        Ok(mesh.tiled(automaton.size() as f32, 2))
+    } else {
+        Ok(mesh)
+    }
+}
@@ -0,0 +1,378 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use super::triangle_mesh::TriangleMesh;
+
+//
+// The quadric of Garland and Heckbert (1997): the sum of the squared distances to a set of planes,
+// stored as the upper triangle of a symmetric 4x4 matrix:
+// [a², ab, ac, ad, b², bc, bd, c², cd, d²]
+//
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+
+    // The quadric of the plane ax + by + cz + d = 0, where (a, b, c) has unit length
+    fn from_plane(a: f64, b: f64, c: f64, d: f64) -> Self {
+        Quadric([a*a, a*b, a*c, a*d, b*b, b*c, b*d, c*c, c*d, d*d])
+    }
+
+    fn add(&self, other: &Quadric) -> Quadric {
+        let mut sum = self.0;
+
+        for i in 0..10 {
+            sum[i] += other.0[i];
+        }
+
+        Quadric(sum)
+    }
+
+    // The sum of the squared distances of p to all planes of this quadric
+    fn error(&self, p: [f64; 3]) -> f64 {
+        let [a2, ab, ac, ad, b2, bc, bd, c2, cd, d2] = self.0;
+        let [x, y, z] = p;
+
+        a2*x*x + 2.0*ab*x*y + 2.0*ac*x*z + 2.0*ad*x
+            + b2*y*y + 2.0*bc*y*z + 2.0*bd*y
+            + c2*z*z + 2.0*cd*z
+            + d2
+    }
+
+    // The position with the smallest error, if it's unique
+    fn optimal_position(&self) -> Option<[f64; 3]> {
+        let [a2, ab, ac, ad, b2, bc, bd, c2, cd, _] = self.0;
+
+        let det = |m: [[f64; 3]; 3]| {
+            m[0][0] * (m[1][1]*m[2][2] - m[1][2]*m[2][1])
+                - m[0][1] * (m[1][0]*m[2][2] - m[1][2]*m[2][0])
+                + m[0][2] * (m[1][0]*m[2][1] - m[1][1]*m[2][0])
+        };
+
+        let m = [[a2, ab, ac], [ab, b2, bc], [ac, bc, c2]];
+        let rhs = [-ad, -bd, -cd];
+
+        let d = det(m);
+
+        // Flat and cylindrical regions don't have a unique optimum
+        if d.abs() < 1e-9 {
+            return None;
+        }
+
+        // Cramer's rule
+        let mut p = [0f64; 3];
+
+        for axis in 0..3 {
+            let mut mi = m;
+
+            for row in 0..3 {
+                mi[row][axis] = rhs[row];
+            }
+
+            p[axis] = det(mi) / d;
+        }
+
+        Some(p)
+    }
+
+}
+
+//
+// A candidate edge collapse: 'remove' is merged into 'keep', which moves to 'position'.
+// The versions of both vertices at the time of creation tell whether the candidate is outdated.
+//
+struct Collapse {
+    cost: f64,
+    keep: usize,
+    remove: usize,
+    position: [f64; 3],
+    versions: (u32, u32)
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    // BinaryHeap is a max-heap, so the ordering is reversed to pop the cheapest collapse first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+struct Decimator {
+    positions: Vec<[f64; 3]>,
+    quadrics: Vec<Quadric>,
+    faces: Vec<[usize; 3]>,
+    alive: Vec<bool>,
+    vertex_faces: Vec<Vec<usize>>,
+    locked: Vec<bool>,
+    versions: Vec<u32>,
+    heap: BinaryHeap<Collapse>
+}
+
+impl Decimator {
+
+    fn new(mesh: &TriangleMesh) -> Self {
+        let positions: Vec<[f64; 3]> = mesh.positions.iter().map(|p| p.map(|c| c as f64)).collect();
+        let faces: Vec<[usize; 3]> = mesh.indices.chunks(3).map(|f| [f[0] as usize, f[1] as usize, f[2] as usize]).collect();
+
+        let mut quadrics = vec![Quadric::default(); positions.len()];
+        let mut vertex_faces: Vec<Vec<usize>> = vec![vec![]; positions.len()];
+
+        for (f, face) in faces.iter().enumerate() {
+            for v in face {
+                vertex_faces[*v].push(f);
+            }
+
+            // Every vertex starts with the planes of its adjacent triangles
+            if let Some([a, b, c]) = unit_normal(positions[face[0]], positions[face[1]], positions[face[2]]) {
+                let p = positions[face[0]];
+                let plane = Quadric::from_plane(a, b, c, -(a*p[0] + b*p[1] + c*p[2]));
+
+                for v in face {
+                    quadrics[*v] = quadrics[*v].add(&plane);
+                }
+            }
+        }
+
+        // Boundary vertices are never moved, so that periodic surfaces still line up with their images
+        let locked = mesh.boundary_vertices();
+
+        let mut decimator = Decimator {
+            versions: vec![0; positions.len()],
+            alive: vec![true; faces.len()],
+            positions,
+            quadrics,
+            faces,
+            vertex_faces,
+            locked,
+            heap: BinaryHeap::new()
+        };
+
+        for v in 0..decimator.positions.len() {
+            for n in decimator.neighbours(v) {
+                if v < n {
+                    decimator.push_candidate(v, n);
+                }
+            }
+        }
+
+        decimator
+    }
+
+    fn neighbours(&self, v: usize) -> Vec<usize> {
+        let mut neighbours: Vec<usize> = self.vertex_faces[v].iter()
+            .filter(|f| self.alive[**f])
+            .flat_map(|f| self.faces[*f])
+            .filter(|n| *n != v)
+            .collect();
+
+        neighbours.sort_unstable();
+        neighbours.dedup();
+
+        neighbours
+    }
+
+    fn push_candidate(&mut self, a: usize, b: usize) {
+        // A locked vertex can't move, so the other vertex collapses onto it
+        let (keep, remove) = match (self.locked[a], self.locked[b]) {
+            (true, true) => return,
+            (false, true) => (b, a),
+            _ => (a, b)
+        };
+
+        let quadric = self.quadrics[keep].add(&self.quadrics[remove]);
+
+        let pk = self.positions[keep];
+        let pr = self.positions[remove];
+        let midpoint = [(pk[0] + pr[0]) / 2.0, (pk[1] + pr[1]) / 2.0, (pk[2] + pr[2]) / 2.0];
+
+        let position = if self.locked[keep] {
+            pk
+        } else {
+            // The optimum is only trusted if it lies close to the edge, otherwise the best of the endpoints and midpoint is used
+            let edge_length = distance(pk, pr);
+
+            match quadric.optimal_position() {
+                Some(p) if distance(p, midpoint) <= edge_length => p,
+                _ => [pk, pr, midpoint].into_iter()
+                    .min_by(|p, q| quadric.error(*p).total_cmp(&quadric.error(*q)))
+                    .unwrap()
+            }
+        };
+
+        self.heap.push(Collapse {
+            cost: f64::max(quadric.error(position), 0.0),
+            keep,
+            remove,
+            position,
+            versions: (self.versions[keep], self.versions[remove])
+        });
+    }
+
+    //
+    // Whether collapsing the edge keeps the surface a manifold without folding triangles over
+    //
+    fn can_collapse(&self, keep: usize, remove: usize, position: [f64; 3]) -> bool {
+        let shared_faces = self.vertex_faces[remove].iter()
+            .filter(|f| self.alive[**f] && self.faces[**f].contains(&keep))
+            .count();
+
+        if shared_faces == 0 {
+            return false;
+        }
+
+        // Link condition: the vertices adjacent to both ends must be exactly the opposite corners of the shared triangles
+        let neighbours_keep = self.neighbours(keep);
+        let common = self.neighbours(remove).iter().filter(|n| neighbours_keep.binary_search(n).is_ok()).count();
+
+        if common != shared_faces {
+            return false;
+        }
+
+        // None of the remaining triangles may flip
+        for v in [keep, remove] {
+            for f in &self.vertex_faces[v] {
+                let face = self.faces[*f];
+
+                if !self.alive[*f] || (face.contains(&keep) && face.contains(&remove)) {
+                    continue;
+                }
+
+                let before = face.map(|i| self.positions[i]);
+                let after = face.map(|i| if i == keep || i == remove { position } else { self.positions[i] });
+
+                match (unit_normal(before[0], before[1], before[2]), unit_normal(after[0], after[1], after[2])) {
+                    (Some(n0), Some(n1)) if n0[0]*n1[0] + n0[1]*n1[1] + n0[2]*n1[2] > 0.2 => {},
+                    _ => return false
+                }
+            }
+        }
+
+        true
+    }
+
+    // Merge 'remove' into 'keep' and return the number of triangles that disappeared
+    fn collapse(&mut self, keep: usize, remove: usize, position: [f64; 3]) -> usize {
+        let mut removed = 0;
+
+        self.positions[keep] = position;
+        self.quadrics[keep] = self.quadrics[keep].add(&self.quadrics[remove]);
+
+        for f in std::mem::take(&mut self.vertex_faces[remove]) {
+            if !self.alive[f] {
+                continue;
+            }
+
+            if self.faces[f].contains(&keep) {
+                self.alive[f] = false;
+                removed += 1;
+            } else {
+                for v in self.faces[f].iter_mut() {
+                    if *v == remove {
+                        *v = keep;
+                    }
+                }
+
+                self.vertex_faces[keep].push(f);
+            }
+        }
+
+        let alive = &self.alive;
+        self.vertex_faces[keep].retain(|f| alive[*f]);
+
+        // All candidates that involve either vertex are outdated now
+        self.versions[keep] += 1;
+        self.versions[remove] += 1;
+
+        for n in self.neighbours(keep) {
+            self.push_candidate(keep, n);
+        }
+
+        removed
+    }
+
+}
+
+//
+// Decimate a mesh by collapsing its edges in order of increasing quadric error, until it has at most
+// 'target_triangles' triangles or the next collapse would exceed 'max_error'.
+// The error is the sum of squared distances (in cells²) to the planes of the original triangles.
+//
+pub fn decimate(mesh: &TriangleMesh, target_triangles: Option<usize>, max_error: Option<f32>) -> TriangleMesh {
+    let mut decimator = Decimator::new(mesh);
+    let mut num_triangles = mesh.num_triangles();
+
+    let target_triangles = target_triangles.unwrap_or(0);
+
+    while num_triangles > target_triangles {
+        let collapse = match decimator.heap.pop() {
+            Some(collapse) => collapse,
+            None => break
+        };
+
+        if (decimator.versions[collapse.keep], decimator.versions[collapse.remove]) != collapse.versions {
+            continue;
+        }
+
+        if let Some(max_error) = max_error {
+            if collapse.cost > max_error as f64 {
+                break;
+            }
+        }
+
+        if decimator.can_collapse(collapse.keep, collapse.remove, collapse.position) {
+            num_triangles -= decimator.collapse(collapse.keep, collapse.remove, collapse.position);
+        }
+    }
+
+    // Only keep the vertices that are still used by a triangle
+    let mut remap: Vec<Option<u32>> = vec![None; decimator.positions.len()];
+    let mut positions: Vec<[f32; 3]> = vec![];
+    let mut indices: Vec<u32> = vec![];
+
+    for (f, face) in decimator.faces.iter().enumerate() {
+        if !decimator.alive[f] {
+            continue;
+        }
+
+        for v in face {
+            let index = *remap[*v].get_or_insert_with(|| {
+                positions.push(decimator.positions[*v].map(|c| c as f32));
+                (positions.len() - 1) as u32
+            });
+
+            indices.push(index);
+        }
+    }
+
+    TriangleMesh::new(positions, indices)
+}
+
+fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+fn unit_normal(a: [f64; 3], b: [f64; 3], c: [f64; 3]) -> Option<[f64; 3]> {
+    let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    let n = [u[1]*v[2] - u[2]*v[1], u[2]*v[0] - u[0]*v[2], u[0]*v[1] - u[1]*v[0]];
+
+    let length = (n[0]*n[0] + n[1]*n[1] + n[2]*n[2]).sqrt();
+
+    if length > 1e-12 {
+        Some([n[0] / length, n[1] / length, n[2] / length])
+    } else {
+        None
+    }
+}
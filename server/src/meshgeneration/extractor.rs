@@ -0,0 +1,76 @@
+use isosurface::{source::Source, marching_cubes::MarchingCubes};
+use serde::Deserialize;
+
+use super::{greedy_voxels::GreedyVoxels, surface_nets::SurfaceNets};
+
+//
+// An algorithm that extracts the surface between the negative (inside) and positive (outside) samples of a source.
+// The source is sampled on a lattice of points that spans the unit cube, and the vertices are placed in the unit cube as well.
+//
+pub trait SurfaceExtractor {
+    fn extract(&mut self, source: &dyn Source, vertices: &mut Vec<f32>, indices: &mut Vec<u32>);
+}
+
+//
+// The extractors that can be requested
+//
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtractorKind {
+    #[default]
+    MarchingCubes,
+    SurfaceNets,
+    Greedy
+}
+
+//
+// Create an extractor that samples 'resolution' points along every axis.
+// If 'periodic' is set, the last sample along every axis coincides with the first.
+//
+pub fn new_extractor(kind: ExtractorKind, resolution: usize, periodic: bool) -> Box<dyn SurfaceExtractor> {
+    match kind {
+        ExtractorKind::MarchingCubes => Box::new(MarchingCubesExtractor { marching_cubes: MarchingCubes::new(resolution) }),
+        ExtractorKind::SurfaceNets => Box::new(SurfaceNets::new(resolution, periodic)),
+        ExtractorKind::Greedy => Box::new(GreedyVoxels::new(resolution, periodic))
+    }
+}
+
+//
+// Sample a source at every point of the lattice, in the order [(x*resolution + y)*resolution + z]
+//
+pub fn sample_lattice(source: &dyn Source, resolution: usize) -> Vec<f32> {
+    let step = 1.0 / (resolution - 1) as f32;
+    let mut samples = Vec::with_capacity(resolution*resolution*resolution);
+
+    for x in 0..resolution {
+        for y in 0..resolution {
+            for z in 0..resolution {
+                samples.push(source.sample(x as f32 * step, y as f32 * step, z as f32 * step));
+            }
+        }
+    }
+
+    samples
+}
+
+//
+// The marching cubes implementation of the isosurface crate
+//
+struct MarchingCubesExtractor {
+    marching_cubes: MarchingCubes
+}
+
+// The isosurface crate only accepts sized sources
+struct SizedSource<'a>(&'a dyn Source);
+
+impl<'a> Source for SizedSource<'a> {
+    fn sample(&self, x: f32, y: f32, z: f32) -> f32 {
+        self.0.sample(x, y, z)
+    }
+}
+
+impl SurfaceExtractor for MarchingCubesExtractor {
+    fn extract(&mut self, source: &dyn Source, vertices: &mut Vec<f32>, indices: &mut Vec<u32>) {
+        self.marching_cubes.extract(&SizedSource(source), vertices, indices);
+    }
+}
@@ -0,0 +1,125 @@
+use isosurface::source::Source;
+
+use super::extractor::{SurfaceExtractor, sample_lattice};
+
+//
+// Greedy voxel meshing: every inside sample is a cubic voxel, and the faces between inside and outside
+// voxels are merged into as few rectangles as possible. This renders the cells exactly, like the
+// boxes of the web client, but as one compact mesh.
+//
+pub struct GreedyVoxels {
+    resolution: usize,
+    periodic: bool
+}
+
+impl GreedyVoxels {
+
+    pub fn new(resolution: usize, periodic: bool) -> Self {
+        GreedyVoxels { resolution, periodic }
+    }
+
+}
+
+impl SurfaceExtractor for GreedyVoxels {
+    fn extract(&mut self, source: &dyn Source, vertices: &mut Vec<f32>, indices: &mut Vec<u32>) {
+        let n = self.resolution;
+        let samples = sample_lattice(source, n);
+
+        // On a periodic lattice, the last sample is a copy of the first, so it isn't a voxel of its own
+        let voxels = if self.periodic { n - 1 } else { n };
+
+        let inside = |p: [i64; 3]| {
+            let p = if self.periodic {
+                p.map(|c| c.rem_euclid(voxels as i64))
+            } else if p.iter().any(|c| *c < 0 || *c >= voxels as i64) {
+                // Outside of the lattice, everything is outside
+                return false;
+            } else {
+                p
+            };
+
+            samples[((p[0] as usize)*n + p[1] as usize)*n + p[2] as usize] < 0.0
+        };
+
+        let step = 1.0 / (n - 1) as f32;
+
+        for axis in 0..3 {
+            let (b, c) = ((axis + 1) % 3, (axis + 2) % 3);
+
+            for direction in [-1i64, 1] {
+                for slice in 0..voxels {
+                    // Mark the faces of this slice that separate an inside voxel from an outside neighbour
+                    let mut mask = vec![false; voxels*voxels];
+
+                    for i in 0..voxels {
+                        for j in 0..voxels {
+                            let mut p = [0i64; 3];
+                            p[axis] = slice as i64;
+                            p[b] = i as i64;
+                            p[c] = j as i64;
+
+                            let mut neighbour = p;
+                            neighbour[axis] += direction;
+
+                            mask[i*voxels + j] = inside(p) && !inside(neighbour);
+                        }
+                    }
+
+                    // Cover the marked faces with rectangles, growing each one first along c and then along b
+                    for i in 0..voxels {
+                        let mut j = 0;
+
+                        while j < voxels {
+                            if !mask[i*voxels + j] {
+                                j += 1;
+                                continue;
+                            }
+
+                            let mut width = 1;
+                            while j + width < voxels && mask[i*voxels + j + width] {
+                                width += 1;
+                            }
+
+                            let mut height = 1;
+                            while i + height < voxels && (j..j + width).all(|k| mask[(i + height)*voxels + k]) {
+                                height += 1;
+                            }
+
+                            for di in 0..height {
+                                for k in j..j + width {
+                                    mask[(i + di)*voxels + k] = false;
+                                }
+                            }
+
+                            // The face lies halfway between the voxel and its neighbour
+                            let plane = slice as f32 + 0.5 * direction as f32;
+                            let (b0, b1) = (i as f32 - 0.5, (i + height) as f32 - 0.5);
+                            let (c0, c1) = (j as f32 - 0.5, (j + width) as f32 - 0.5);
+
+                            let first = (vertices.len() / 3) as u32;
+
+                            // Counter-clockwise around the axis
+                            for (pb, pc) in [(b0, c0), (b1, c0), (b1, c1), (b0, c1)] {
+                                let mut v = [0f32; 3];
+                                v[axis] = plane;
+                                v[b] = pb;
+                                v[c] = pc;
+
+                                vertices.extend(v.map(|coordinate| coordinate * step));
+                            }
+
+                            // The face points away from the inside voxel
+                            if direction > 0 {
+                                indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+                            } else {
+                                indices.extend([first, first + 2, first + 1, first, first + 3, first + 2]);
+                            }
+
+                            j += width;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
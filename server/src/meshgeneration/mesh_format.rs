@@ -0,0 +1,70 @@
+use gltf_json as json;
+
+use miette::Result;
+use serde::Deserialize;
+
+use crate::gltfgeneration::gltf_generation::{generate_mesh_gltf, generate_mesh_glb, to_glb};
+
+use super::{obj::generate_obj, ply::generate_ply, stl::generate_stl, triangle_mesh::TriangleMesh};
+
+//
+// The file formats in which a mesh can be requested
+//
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MeshFormat {
+    Gltf,
+    Glb,
+    Stl,
+    Ply,
+    Obj
+}
+
+impl MeshFormat {
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            MeshFormat::Gltf => "model/gltf+json",
+            MeshFormat::Glb => "model/gltf-binary",
+            MeshFormat::Stl => "model/stl",
+            MeshFormat::Ply => "application/ply",
+            MeshFormat::Obj => "model/obj"
+        }
+    }
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            MeshFormat::Gltf => "mesh.gltf",
+            MeshFormat::Glb => "mesh.glb",
+            MeshFormat::Stl => "mesh.stl",
+            MeshFormat::Ply => "mesh.ply",
+            MeshFormat::Obj => "mesh.obj"
+        }
+    }
+
+}
+
+//
+// Encode an indexed mesh in the requested format.
+// Formats that support vertex colours (PLY) colour every vertex with 'colour'.
+//
+pub fn encode_mesh(mesh: &TriangleMesh, colour: [u8; 3], format: MeshFormat) -> Result<Vec<u8>> {
+
+    // glTF can't describe an empty mesh, so an empty document is returned instead
+    if mesh.is_empty() {
+        match format {
+            MeshFormat::Gltf => return Ok(String::from("{}").into_bytes()),
+            MeshFormat::Glb => return to_glb(&json::Root::default(), vec![]),
+            _ => {}
+        }
+    }
+
+    match format {
+        MeshFormat::Gltf => Ok(generate_mesh_gltf(mesh)?.into_bytes()),
+        MeshFormat::Glb => generate_mesh_glb(mesh),
+        MeshFormat::Stl => Ok(generate_stl(mesh)),
+        MeshFormat::Ply => Ok(generate_ply(mesh, &vec![colour; mesh.positions.len()])),
+        MeshFormat::Obj => Ok(generate_obj(mesh))
+    }
+
+}
@@ -0,0 +1,79 @@
+use miette::{miette, Result};
+
+use super::{extractor::ExtractorKind, scalar_field::ScalarFieldKind, smoothing::SmoothingKind};
+
+// The resolution is at most this many samples per cell (plus one for a periodic lattice)
+const MAX_SAMPLES_PER_CELL: usize = 2;
+
+// The most smoothing iterations that can be requested
+const MAX_SMOOTHING_ITERATIONS: usize = 500;
+
+//
+// Options that control how the surface of a cell-type is extracted
+//
+#[derive(Clone, Copy, Default)]
+pub struct MeshOptions {
+    // Respect the periodic boundaries of the automaton, so that surfaces aren't cut open at the domain faces
+    pub periodic: bool,
+    // Repeat the periodic surface 2x2x2 times, which shows how structures connect across the boundaries
+    pub tile: bool,
+    // The algorithm that extracts the surface
+    pub extractor: ExtractorKind,
+    // The scalar field that the surface is extracted from
+    pub field: ScalarFieldKind,
+    // The standard deviation of the Gaussian field, in cells
+    pub radius: Option<f32>,
+    // The value of the field at the surface. It defaults to a level that suits the field.
+    pub iso_level: Option<f32>,
+    // The number of samples along every axis. It defaults to one sample per cell.
+    pub resolution: Option<usize>,
+    // How the extracted mesh is smoothed, if at all
+    pub smoothing: Option<SmoothingKind>,
+    pub smoothing_iterations: Option<usize>,
+    // Decimate the smoothed mesh to at most this number of triangles
+    pub target_triangles: Option<usize>,
+    // Stop decimating once a collapse would exceed this quadric error (in cells²)
+    pub max_error: Option<f32>
+}
+
+impl MeshOptions {
+
+    // Whether the surface is extracted around the nearest cells, without a smooth field
+    pub fn is_blocky(&self) -> bool {
+        self.field == ScalarFieldKind::Indicator && self.iso_level.is_none() && self.resolution.is_none()
+    }
+
+    // Whether the Source implementation of an automaton itself can extract the surface, through mc_extract
+    pub fn uses_automaton_source(&self) -> bool {
+        self.is_blocky() && !self.periodic && !self.tile && self.extractor == ExtractorKind::MarchingCubes
+    }
+
+    // Check that the resolution, the radius of the Gaussian field and the number of smoothing iterations are within bounds
+    // for an automaton of the given size, since they determine how long the extraction takes and how much memory it needs
+    pub fn validate(&self, size: usize) -> Result<()> {
+        if let Some(radius) = self.radius {
+            let max_radius = (size / 2) as f32;
+
+            if !(radius > 0.0 && radius <= max_radius) {
+                return Err(miette!("The smoothing radius must be positive and at most {}, got {}", max_radius, radius));
+            }
+        }
+
+        if let Some(resolution) = self.resolution {
+            let max_resolution = MAX_SAMPLES_PER_CELL * size + 1;
+
+            if resolution < 2 || resolution > max_resolution {
+                return Err(miette!("The resolution must lie between 2 and {}, got {}", max_resolution, resolution));
+            }
+        }
+
+        if let Some(iterations) = self.smoothing_iterations {
+            if iterations > MAX_SMOOTHING_ITERATIONS {
+                return Err(miette!("At most {} smoothing iterations can be run, got {}", MAX_SMOOTHING_ITERATIONS, iterations));
+            }
+        }
+
+        Ok(())
+    }
+
+}
@@ -0,0 +1,31 @@
+use std::fmt::Write;
+
+use super::triangle_mesh::TriangleMesh;
+
+//
+// Wavefront OBJ with a normal for every vertex. OBJ indices start at 1, and
+// every vertex uses the normal with the same index.
+//
+pub fn generate_obj(mesh: &TriangleMesh) -> Vec<u8> {
+
+    let mut obj = String::from("# Exported by the 3D Cellular Automaton server\n");
+
+    for p in &mesh.positions {
+        writeln!(obj, "v {} {} {}", p[0], p[1], p[2]).unwrap();
+    }
+
+    for n in &mesh.normals {
+        writeln!(obj, "vn {} {} {}", n[0], n[1], n[2]).unwrap();
+    }
+
+    for f in 0..mesh.num_triangles() {
+        let a = mesh.indices[3*f] + 1;
+        let b = mesh.indices[3*f + 1] + 1;
+        let c = mesh.indices[3*f + 2] + 1;
+
+        writeln!(obj, "f {}//{} {}//{} {}//{}", a, a, b, b, c, c).unwrap();
+    }
+
+    obj.into_bytes()
+
+}
@@ -0,0 +1,41 @@
+use super::triangle_mesh::TriangleMesh;
+
+//
+// Binary little-endian PLY with a normal and a colour for every vertex.
+// 'colours' holds one colour per vertex of the mesh.
+//
+pub fn generate_ply(mesh: &TriangleMesh, colours: &[[u8; 3]]) -> Vec<u8> {
+
+    let num_vertices = mesh.positions.len();
+    let num_faces = mesh.num_triangles();
+
+    let header = format!(
+        "ply\nformat binary_little_endian 1.0\ncomment Exported by the 3D Cellular Automaton server\n\
+        element vertex {}\nproperty float x\nproperty float y\nproperty float z\n\
+        property float nx\nproperty float ny\nproperty float nz\n\
+        property uchar red\nproperty uchar green\nproperty uchar blue\n\
+        element face {}\nproperty list uchar uint vertex_indices\nend_header\n",
+        num_vertices,
+        num_faces
+    );
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(header.len() + num_vertices * 27 + num_faces * 13);
+    bytes.extend_from_slice(header.as_bytes());
+
+    for v in 0..num_vertices {
+        for value in mesh.positions[v].iter().chain(mesh.normals[v].iter()) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&colours[v]);
+    }
+
+    for f in 0..num_faces {
+        bytes.push(3);
+        for i in 0..3 {
+            bytes.extend_from_slice(&mesh.indices[3*f + i].to_le_bytes());
+        }
+    }
+
+    bytes
+
+}
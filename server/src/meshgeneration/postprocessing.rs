@@ -0,0 +1,29 @@
+use super::{decimation::decimate, mesh_options::MeshOptions, smoothing::smooth, triangle_mesh::TriangleMesh};
+
+// The number of smoothing iterations that is used when none is requested
+const DEFAULT_SMOOTHING_ITERATIONS: usize = 10;
+
+//
+// Smooth and decimate an extracted mesh as described by the mesh options
+//
+pub fn postprocess(mut mesh: TriangleMesh, options: &MeshOptions) -> TriangleMesh {
+    let decimation = options.target_triangles.is_some() || options.max_error.is_some();
+
+    if options.smoothing.is_none() && !decimation {
+        return mesh;
+    }
+
+    // Both stages need to know which triangles are adjacent, so copies of the same vertex are merged first
+    mesh.merge_coincident_vertices();
+    mesh.compute_normals();
+
+    if let Some(kind) = options.smoothing {
+        smooth(&mut mesh, kind, options.smoothing_iterations.unwrap_or(DEFAULT_SMOOTHING_ITERATIONS));
+    }
+
+    if decimation {
+        mesh = decimate(&mesh, options.target_triangles, options.max_error);
+    }
+
+    mesh
+}
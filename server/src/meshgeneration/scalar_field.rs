@@ -0,0 +1,179 @@
+use isosurface::source::Source;
+use serde::Deserialize;
+
+use crate::appdata::dim3d::automata::automaton::CellularAutomaton3D;
+
+//
+// The scalar fields from which a surface can be extracted
+//
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScalarFieldKind {
+    // 1 inside the cell-type and 0 outside, sampled at the nearest cell (blocky)
+    #[default]
+    Indicator,
+    // The same indicator, but interpolated trilinearly between the cells
+    Trilinear,
+    // The indicator blurred with a Gaussian kernel, interpolated trilinearly
+    Gaussian,
+    // The influence of the species' chemicals during the last iteration, interpolated trilinearly
+    Influence
+}
+
+impl ScalarFieldKind {
+
+    // The iso-level that is used when none is requested
+    pub fn default_iso_level(&self) -> f32 {
+        match self {
+            ScalarFieldKind::Influence => 0.0,
+            _ => 0.5
+        }
+    }
+
+}
+
+//
+// A scalar value for every cell of an automaton, stored in C order: values[(x*size + y)*size + z].
+// The field is periodic, just like the automata.
+//
+pub struct ScalarField {
+    pub size: usize,
+    pub values: Vec<f32>
+}
+
+impl ScalarField {
+
+    pub fn new(size: usize, values: Vec<f32>) -> Self {
+        if values.len() != size*size*size {
+            panic!("ScalarField: expected {} values, got {}", size*size*size, values.len());
+        }
+
+        ScalarField { size, values }
+    }
+
+    pub fn indicator<A: CellularAutomaton3D + ?Sized>(automaton: &A, cell_type: u32) -> Self {
+        let size = automaton.size();
+        let mut values = vec![0f32; size*size*size];
+
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    if automaton.get(x, y, z) == cell_type {
+                        values[(x*size + y)*size + z] = 1.0;
+                    }
+                }
+            }
+        }
+
+        ScalarField::new(size, values)
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> f32 {
+        self.values[((x % self.size)*self.size + y % self.size)*self.size + z % self.size]
+    }
+
+    //
+    // Convolve the field with a Gaussian of standard deviation 'radius' (in cells).
+    // The kernel is separable, so the field is blurred along one axis at a time,
+    // wrapping around the boundaries. It's truncated at three standard deviations.
+    //
+    pub fn gaussian_blurred(&self, radius: f32) -> ScalarField {
+        let reach = (3.0 * radius).ceil() as i64;
+
+        let mut kernel: Vec<f32> = (-reach..=reach).map(|d| (-((d*d) as f32) / (2.0 * radius * radius)).exp()).collect();
+        let total: f32 = kernel.iter().sum();
+        kernel.iter_mut().for_each(|k| *k /= total);
+
+        let size = self.size;
+        let mut values = self.values.clone();
+
+        for axis in 0..3 {
+            let mut blurred = vec![0f32; size*size*size];
+
+            for x in 0..size {
+                for y in 0..size {
+                    for z in 0..size {
+                        let mut sum = 0.0;
+
+                        for (k, weight) in kernel.iter().enumerate() {
+                            let offset = (k as i64 - reach).rem_euclid(size as i64) as usize;
+
+                            let mut p = [x, y, z];
+                            p[axis] = (p[axis] + offset) % size;
+
+                            sum += weight * values[(p[0]*size + p[1])*size + p[2]];
+                        }
+
+                        blurred[(x*size + y)*size + z] = sum;
+                    }
+                }
+            }
+
+            values = blurred;
+        }
+
+        ScalarField::new(size, values)
+    }
+
+    //
+    // Sample the field at a continuous position, given in cells. Cell (x, y, z) lies at position (x, y, z).
+    // Positions outside the domain wrap around if 'periodic' is set, and are clamped to the domain otherwise.
+    //
+    pub fn sample(&self, p: [f32; 3], trilinear: bool, periodic: bool) -> f32 {
+        let size = self.size as f32;
+
+        let p = p.map(|c| if periodic { c.rem_euclid(size) } else { c.clamp(0.0, size - 1.0) });
+
+        if !trilinear {
+            let [x, y, z] = p.map(|c| c.round() as usize);
+            return self.get(x, y, z);
+        }
+
+        let base = p.map(|c| c.floor());
+        let t = [p[0] - base[0], p[1] - base[1], p[2] - base[2]];
+
+        // Without periodicity, the last cell has no neighbour to interpolate with
+        let upper = |c: f32| if periodic { c as usize + 1 } else { usize::min(c as usize + 1, self.size - 1) };
+
+        let x = [base[0] as usize, upper(base[0])];
+        let y = [base[1] as usize, upper(base[1])];
+        let z = [base[2] as usize, upper(base[2])];
+
+        let mut value = 0.0;
+
+        for i in 0..2 {
+            for j in 0..2 {
+                for k in 0..2 {
+                    let weight = (if i == 0 { 1.0 - t[0] } else { t[0] })
+                        * (if j == 0 { 1.0 - t[1] } else { t[1] })
+                        * (if k == 0 { 1.0 - t[2] } else { t[2] });
+
+                    value += weight * self.get(x[i], y[j], z[k]);
+                }
+            }
+        }
+
+        value
+    }
+
+}
+
+//
+// A marching cubes source on a scalar field: everything above the iso-level is inside
+//
+pub struct FieldSource<'a> {
+    pub field: &'a ScalarField,
+    pub iso_level: f32,
+    pub trilinear: bool,
+    pub periodic: bool
+}
+
+impl<'a> Source for FieldSource<'a> {
+    fn sample(&self, x: f32, y: f32, z: f32) -> f32 {
+        // Caution: the source will be sampled between (0, 0, 0) and (1, 1, 1).
+        // A periodic source spans one full period, so (1, 1, 1) wraps around to the first cell.
+        let extent = if self.periodic { self.field.size as f32 } else { (self.field.size - 1) as f32 };
+
+        self.iso_level - self.field.sample([x * extent, y * extent, z * extent], self.trilinear, self.periodic)
+    }
+}
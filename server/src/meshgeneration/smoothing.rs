@@ -0,0 +1,62 @@
+use serde::Deserialize;
+
+use super::triangle_mesh::TriangleMesh;
+
+//
+// The ways in which a mesh can be smoothed
+//
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmoothingKind {
+    // Move every vertex towards the average of its neighbours. This shrinks the surface.
+    Laplacian,
+    // Alternate a shrinking and an inflating Laplacian step, which (approximately) preserves the volume
+    Taubin
+}
+
+// The factors of the shrinking and inflating steps, as proposed by Taubin (1995)
+const LAMBDA: f32 = 0.5;
+const MU: f32 = -0.53;
+
+pub fn smooth(mesh: &mut TriangleMesh, kind: SmoothingKind, iterations: usize) {
+    let neighbours = mesh.vertex_neighbours();
+
+    // Vertices on the boundary stay in place, so that periodic surfaces still line up with their images
+    let boundary = mesh.boundary_vertices();
+
+    for _ in 0..iterations {
+        laplacian_step(mesh, &neighbours, &boundary, LAMBDA);
+
+        if kind == SmoothingKind::Taubin {
+            laplacian_step(mesh, &neighbours, &boundary, MU);
+        }
+    }
+
+    mesh.compute_normals();
+}
+
+//
+// Move every vertex by 'factor' times the vector towards the average of its neighbours
+//
+fn laplacian_step(mesh: &mut TriangleMesh, neighbours: &[Vec<u32>], boundary: &[bool], factor: f32) {
+    let positions = mesh.positions.clone();
+
+    for (v, p) in mesh.positions.iter_mut().enumerate() {
+        if boundary[v] || neighbours[v].is_empty() {
+            continue;
+        }
+
+        let mut average = [0f32; 3];
+
+        for n in &neighbours[v] {
+            for axis in 0..3 {
+                average[axis] += positions[*n as usize][axis];
+            }
+        }
+
+        for axis in 0..3 {
+            average[axis] /= neighbours[v].len() as f32;
+            p[axis] += factor * (average[axis] - p[axis]);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use super::triangle_mesh::TriangleMesh;
+
+//
+// Binary STL: an 80-byte header, the number of triangles and for every triangle
+// its facet normal, its three vertices and a 2-byte attribute count.
+// STL has no notion of shared vertices, so every triangle is written out in full.
+//
+pub fn generate_stl(mesh: &TriangleMesh) -> Vec<u8> {
+
+    let num_triangles = mesh.num_triangles();
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(84 + num_triangles * 50);
+
+    // The header may contain anything, as long as it doesn't start with 'solid'
+    let mut header = [0u8; 80];
+    let title = b"Binary STL exported by the 3D Cellular Automaton server";
+    header[..title.len()].copy_from_slice(title);
+
+    bytes.extend_from_slice(&header);
+    bytes.extend_from_slice(&(num_triangles as u32).to_le_bytes());
+
+    for t in 0..num_triangles {
+        let [a, b, c] = mesh.triangle(t);
+
+        for value in facet_normal(a, b, c).iter().chain(a.iter()).chain(b.iter()).chain(c.iter()) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+
+        // Attribute byte count, unused
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+    }
+
+    bytes
+
+}
+
+//
+// The unit normal of triangle (a, b, c), following the right-hand rule
+//
+pub fn facet_normal(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> [f32; 3] {
+
+    let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+
+    let n = [u[1]*v[2] - u[2]*v[1], u[2]*v[0] - u[0]*v[2], u[0]*v[1] - u[1]*v[0]];
+    let length = (n[0]*n[0] + n[1]*n[1] + n[2]*n[2]).sqrt();
+
+    // Degenerate triangles get a zero normal
+    if length == 0.0 {
+        return [0.0, 0.0, 0.0];
+    }
+
+    [n[0] / length, n[1] / length, n[2] / length]
+
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use isosurface::source::Source;
+
+use super::extractor::{SurfaceExtractor, sample_lattice};
+
+//
+// Naive surface nets: every cube of the lattice that is crossed by the surface gets one vertex,
+// at the average of the points where the surface crosses its edges. Every lattice edge that is crossed
+// connects the vertices of the four cubes around it into a quad. Compared to marching cubes,
+// this gives fewer triangles that are shaped more evenly.
+//
+pub struct SurfaceNets {
+    resolution: usize,
+    periodic: bool
+}
+
+impl SurfaceNets {
+
+    pub fn new(resolution: usize, periodic: bool) -> Self {
+        SurfaceNets { resolution, periodic }
+    }
+
+}
+
+impl SurfaceExtractor for SurfaceNets {
+    fn extract(&mut self, source: &dyn Source, vertices: &mut Vec<f32>, indices: &mut Vec<u32>) {
+        let n = self.resolution;
+        let samples = sample_lattice(source, n);
+        let sample = |p: [usize; 3]| samples[(p[0]*n + p[1])*n + p[2]];
+
+        let step = 1.0 / (n - 1) as f32;
+
+        // On a periodic lattice, the last sample is a copy of the first. There is a cube between
+        // every two subsequent samples, so the cubes wrap around as well.
+        let cubes = n - 1;
+
+        // The vertex of every cube, indexed by its lowest corner
+        let mut cube_vertex = vec![u32::MAX; cubes*cubes*cubes];
+
+        for x in 0..cubes {
+            for y in 0..cubes {
+                for z in 0..cubes {
+                    let mut sum = [0f32; 3];
+                    let mut crossings = 0;
+
+                    // Visit the twelve edges of the cube: along every axis, the four edges starting at the other corners
+                    for axis in 0..3 {
+                        for corner in 0..4 {
+                            let mut a = [x, y, z];
+                            a[(axis + 1) % 3] += corner & 1;
+                            a[(axis + 2) % 3] += corner >> 1;
+
+                            let mut b = a;
+                            b[axis] += 1;
+
+                            let (va, vb) = (sample(a), sample(b));
+
+                            if (va < 0.0) != (vb < 0.0) {
+                                let t = va / (va - vb);
+
+                                for i in 0..3 {
+                                    sum[i] += a[i] as f32 + t * (b[i] as f32 - a[i] as f32);
+                                }
+
+                                crossings += 1;
+                            }
+                        }
+                    }
+
+                    if crossings > 0 {
+                        cube_vertex[(x*cubes + y)*cubes + z] = (vertices.len() / 3) as u32;
+
+                        for i in 0..3 {
+                            vertices.push(sum[i] / crossings as f32 * step);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Cubes that wrap around the lattice need a copy of their vertex, shifted by one period
+        let mut shifted_vertex: HashMap<(usize, [i64; 3]), u32> = HashMap::new();
+
+        // On a periodic lattice, the edges that start at the last sample are copies of those at the first
+        let edge_starts = if self.periodic { cubes } else { n };
+
+        // Connect the vertices around every crossed edge. Without periodicity, edges on the border
+        // of the lattice don't have four cubes around them, so the surface is open there.
+        for x in 0..edge_starts {
+            for y in 0..edge_starts {
+                for z in 0..edge_starts {
+                    let p = [x, y, z];
+
+                    for axis in 0..3 {
+                        let (b, c) = ((axis + 1) % 3, (axis + 2) % 3);
+
+                        if p[axis] + 1 >= n {
+                            continue;
+                        }
+
+                        if !self.periodic && (p[b] == 0 || p[c] == 0 || p[b] >= cubes || p[c] >= cubes) {
+                            continue;
+                        }
+
+                        let mut q = p;
+                        q[axis] += 1;
+
+                        let inside = sample(p) < 0.0;
+
+                        if inside == (sample(q) < 0.0) {
+                            continue;
+                        }
+
+                        // The vertex of the cube whose lowest corner lies 'db' and 'dc' samples below p
+                        let mut cube = |db: usize, dc: usize| {
+                            let mut corner = [p[0] as i64, p[1] as i64, p[2] as i64];
+                            corner[b] -= db as i64;
+                            corner[c] -= dc as i64;
+
+                            let shift = corner.map(|coordinate| coordinate.div_euclid(cubes as i64));
+                            let corner = corner.map(|coordinate| coordinate.rem_euclid(cubes as i64) as usize);
+
+                            let vertex = cube_vertex[(corner[0]*cubes + corner[1])*cubes + corner[2]];
+
+                            if shift == [0, 0, 0] {
+                                return vertex;
+                            }
+
+                            // One period spans the whole unit cube
+                            *shifted_vertex.entry((vertex as usize, shift)).or_insert_with(|| {
+                                for i in 0..3 {
+                                    vertices.push(vertices[3*vertex as usize + i] + shift[i] as f32);
+                                }
+
+                                (vertices.len() / 3 - 1) as u32
+                            })
+                        };
+
+                        // The four cubes around the edge, counter-clockwise around the axis
+                        let quad = [cube(1, 1), cube(0, 1), cube(0, 0), cube(1, 0)];
+
+                        // The surface faces away from the inside
+                        if inside {
+                            indices.extend([quad[0], quad[1], quad[2], quad[0], quad[2], quad[3]]);
+                        } else {
+                            indices.extend([quad[0], quad[2], quad[1], quad[0], quad[3], quad[2]]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
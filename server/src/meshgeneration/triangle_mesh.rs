@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+
+//
+// An indexed triangle mesh: vertices are shared between triangles and every three
+// subsequent indices form one triangle. Every vertex carries a smooth normal.
+//
+#[derive(Clone, Default)]
+pub struct TriangleMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub indices: Vec<u32>
+}
+
+impl TriangleMesh {
+
+    pub fn new(positions: Vec<[f32; 3]>, indices: Vec<u32>) -> Self {
+        let mut mesh = TriangleMesh {
+            positions,
+            normals: vec![],
+            indices
+        };
+
+        mesh.compute_normals();
+
+        mesh
+    }
+
+    //
+    // Construct a mesh from the flat (x, y, z, x, y, z, ...) vertex array that the extractors produce,
+    // scaling every coordinate with 'scale'.
+    //
+    pub fn from_flat(vertices: &[f32], indices: Vec<u32>, scale: f32) -> Self {
+        if vertices.len() % 3 != 0 {
+            panic!("Mesh: vertices array length not multiple of three");
+        }
+
+        if indices.len() % 3 != 0 {
+            panic!("Mesh: indices array length not multiple of three");
+        }
+
+        let positions: Vec<[f32; 3]> = vertices.chunks(3).map(|v| [v[0] * scale, v[1] * scale, v[2] * scale]).collect();
+
+        TriangleMesh::new(positions, indices)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn num_triangles(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn triangle(&self, t: usize) -> [[f32; 3]; 3] {
+        [
+            self.positions[self.indices[3*t] as usize],
+            self.positions[self.indices[3*t + 1] as usize],
+            self.positions[self.indices[3*t + 2] as usize]
+        ]
+    }
+
+    //
+    // Smooth per-vertex normals: the sum of the normals of all adjacent triangles, weighted by their area.
+    // The (unnormalised) cross product of two edges is exactly the area-weighted normal of a triangle.
+    //
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![[0f32; 3]; self.positions.len()];
+
+        for t in 0..self.num_triangles() {
+            let [a, b, c] = self.triangle(t);
+
+            let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+            let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+            let n = [u[1]*v[2] - u[2]*v[1], u[2]*v[0] - u[0]*v[2], u[0]*v[1] - u[1]*v[0]];
+
+            for i in 0..3 {
+                let vertex = self.indices[3*t + i] as usize;
+                normals[vertex][0] += n[0];
+                normals[vertex][1] += n[1];
+                normals[vertex][2] += n[2];
+            }
+        }
+
+        for n in normals.iter_mut() {
+            let length = (n[0]*n[0] + n[1]*n[1] + n[2]*n[2]).sqrt();
+
+            // Vertices that only belong to degenerate triangles keep a zero normal
+            if length > 0.0 {
+                n[0] /= length;
+                n[1] /= length;
+                n[2] /= length;
+            }
+        }
+
+        self.normals = normals;
+    }
+
+    //
+    // The total area of all triangles
+    //
+    pub fn area(&self) -> f32 {
+        (0..self.num_triangles()).map(|t| {
+            let [a, b, c] = self.triangle(t);
+
+            let u = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+            let v = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+            let n = [u[1]*v[2] - u[2]*v[1], u[2]*v[0] - u[0]*v[2], u[0]*v[1] - u[1]*v[0]];
+
+            (n[0]*n[0] + n[1]*n[1] + n[2]*n[2]).sqrt() / 2.0
+        }).sum()
+    }
+
+    //
+    // The minimum and maximum coordinates along every axis
+    //
+    pub fn bounds(&self) -> ([f32; 3], [f32; 3]) {
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+
+        for p in &self.positions {
+            for axis in 0..3 {
+                min[axis] = f32::min(min[axis], p[axis]);
+                max[axis] = f32::max(max[axis], p[axis]);
+            }
+        }
+
+        (min, max)
+    }
+
+    //
+    // For every vertex, the vertices it shares an edge with
+    //
+    pub fn vertex_neighbours(&self) -> Vec<Vec<u32>> {
+        let mut neighbours: Vec<Vec<u32>> = vec![vec![]; self.positions.len()];
+
+        for t in 0..self.num_triangles() {
+            for i in 0..3 {
+                let a = self.indices[3*t + i];
+                let b = self.indices[3*t + (i + 1) % 3];
+
+                neighbours[a as usize].push(b);
+                neighbours[b as usize].push(a);
+            }
+        }
+
+        for n in neighbours.iter_mut() {
+            n.sort_unstable();
+            n.dedup();
+        }
+
+        neighbours
+    }
+
+    //
+    // Whether every vertex lies on the boundary of the mesh: on an edge that belongs to one triangle only.
+    // Vertices on edges with more than two triangles are reported as well, since they aren't on a manifold surface.
+    //
+    pub fn boundary_vertices(&self) -> Vec<bool> {
+        let mut edges: HashMap<(u32, u32), u32> = HashMap::new();
+
+        for t in 0..self.num_triangles() {
+            for i in 0..3 {
+                let a = self.indices[3*t + i];
+                let b = self.indices[3*t + (i + 1) % 3];
+
+                *edges.entry((u32::min(a, b), u32::max(a, b))).or_insert(0) += 1;
+            }
+        }
+
+        let mut boundary = vec![false; self.positions.len()];
+
+        for ((a, b), count) in edges {
+            if count != 2 {
+                boundary[a as usize] = true;
+                boundary[b as usize] = true;
+            }
+        }
+
+        boundary
+    }
+
+    //
+    // Repeat a periodic mesh 'repeats' times along every axis. The copies are shifted by multiples of 'period'
+    // and the vertices on the seams are merged, so that the repeat forms one connected surface.
+    //
+    pub fn tiled(&self, period: f32, repeats: usize) -> TriangleMesh {
+        let mut positions: Vec<[f32; 3]> = Vec::with_capacity(self.positions.len() * repeats.pow(3));
+        let mut indices: Vec<u32> = Vec::with_capacity(self.indices.len() * repeats.pow(3));
+
+        for i in 0..repeats {
+            for j in 0..repeats {
+                for k in 0..repeats {
+                    let offset = [i as f32 * period, j as f32 * period, k as f32 * period];
+                    let first_index = positions.len() as u32;
+
+                    positions.extend(self.positions.iter().map(|p| [p[0] + offset[0], p[1] + offset[1], p[2] + offset[2]]));
+                    indices.extend(self.indices.iter().map(|index| index + first_index));
+                }
+            }
+        }
+
+        let mut mesh = TriangleMesh { positions, normals: vec![], indices };
+        mesh.merge_coincident_vertices();
+        mesh.compute_normals();
+
+        mesh
+    }
+
+    //
+    // Merge vertices that lie at the same position, up to rounding to 1/1024th of a cell.
+    // Copies of a vertex that were computed with a different offset therefore still end up merged.
+    // Normals have to be recomputed afterwards.
+    //
+    pub fn merge_coincident_vertices(&mut self) {
+        let mut merged: HashMap<[i64; 3], u32> = HashMap::new();
+        let mut positions: Vec<[f32; 3]> = vec![];
+        let mut remap: Vec<u32> = Vec::with_capacity(self.positions.len());
+
+        for p in &self.positions {
+            let key = p.map(|c| (c * 1024.0).round() as i64);
+
+            let index = *merged.entry(key).or_insert_with(|| {
+                positions.push(*p);
+                (positions.len() - 1) as u32
+            });
+
+            remap.push(index);
+        }
+
+        self.indices = self.indices.iter().map(|index| remap[*index as usize]).collect();
+        self.positions = positions;
+        self.normals = vec![];
+    }
+
+}
@@ -8,4 +8,6 @@ pub mod nchem_post;
 pub mod benchmarks;
 pub mod general_post;
 pub mod general_get;
-pub mod batch;
\ No newline at end of file
+pub mod batch;
+pub mod jobs;
+pub mod sessions;
\ No newline at end of file
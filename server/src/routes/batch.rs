@@ -1,8 +1,17 @@
-use actix_web::{post, web, Responder, Result};
+use actix_web::{post, web, error, Responder, Result};
 use serde::{Serialize, Deserialize};
-use std::{sync::Mutex, time::Instant, fs::File};
-
-use crate::{CAAppData, appdata::dim3d::automata::{automaton_gpu_n_chemicals::GPUNChemicalsCellularAutomaton3D, automaton::{CellularAutomaton3D, self}}};
+use std::{time::Instant, fs::File};
+
+use crate::analysis::components::{find_components, Connectivity};
+use crate::analysis::damage_spreading::{damage_spreading, random_cells, MAX_DAMAGE_ITERATIONS, MAX_DAMAGED_CELLS};
+use crate::analysis::geometry::measure_geometry;
+use crate::analysis::minkowski::measure_minkowski_functionals;
+use crate::analysis::stability::analyse_stability;
+use crate::analysis::structure_factor::measure_structure_factor;
+use crate::analysis::topology::measure_topology;
+use crate::{appdata::dim3d::automata::{automaton_gpu_n_chemicals::GPUNChemicalsCellularAutomaton3D, automaton::{CellularAutomaton3D, self}}};
+use crate::appdata::dim3d::automata::observer::Metric;
+use crate::appdata::sessions::Session;
 
 use std::io::prelude::*;
 use std::path::Path;
@@ -20,9 +29,43 @@ pub struct BatchEntry {
     step: f32
 }
 
+impl BatchEntry {
+
+    // The number of values from min to max (inclusive) in steps of 'step', or None if there are too many to visit
+    fn num_values(&self) -> Option<u64> {
+        let (min, max, step) = (self.min as f64, self.max as f64, self.step as f64);
+
+        if !(min.is_finite() && max.is_finite() && step.is_finite() && step > 0.0) {
+            return None;
+        }
+
+        if max < min {
+            return Some(0);
+        }
+
+        // A tolerance makes sure that max itself is included when it lies (up to the rounding of f32) on a step
+        let num_values = ((max - min) / step + 1e-6).floor() + 1.0;
+
+        if num_values > u32::MAX as f64 { None } else { Some(num_values as u64) }
+    }
+
+    fn value(&self, index: u64) -> f32 {
+        (self.min as f64 + index as f64 * self.step as f64) as f32
+    }
+
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct BatchExportEntry {
-    attribute: String
+    attribute: String,
+    // Only used by the "components" and "component-histogram" attributes, face connectivity by default
+    connectivity: Option<Connectivity>,
+    // Only used by the "damage-spreading" attribute: the number of perturbed cells (1 by default),
+    // the number of iterations over which the damage is followed (10 by default)
+    // and the seed of the perturbed cells and their cell-types (1 by default)
+    damaged_cells: Option<usize>,
+    damage_iterations: Option<u32>,
+    damage_seed: Option<u64>
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -40,11 +83,27 @@ pub struct BatchExperiment {
 
 
 
-fn run_experiment(automaton: &mut GPUNChemicalsCellularAutomaton3D, experiment: &BatchExperiment, file: &mut File) {
+//
+// Run the experiment, calling 'on_iteration' after every iteration. The experiment stops as soon as it returns false,
+// in which case false is returned as well.
+//
+fn run_experiment(automaton: &mut GPUNChemicalsCellularAutomaton3D, experiment: &BatchExperiment, file: &mut File, on_iteration: &mut dyn FnMut(&GPUNChemicalsCellularAutomaton3D) -> bool) -> bool {
 
     // Base case: there's no more variables to vary
     if experiment.entries.len() == 0 {
 
+        // The evolution of the geometry and wavelength can only be exported if it's recorded along the way
+        automaton.record_geometry = experiment.export_entries.iter().any(|e| e.attribute == "geometry-evolution");
+        automaton.record_structure_factor = experiment.export_entries.iter().any(|e| e.attribute == "wavelength-evolution");
+        automaton.record_segregation = false;
+
+        // Only the evolution of the order parameter needs an observer, sampling every iteration
+        if experiment.export_entries.iter().any(|e| e.attribute == "order-parameter-evolution") {
+            automaton.set_observers(vec![Metric::OrderParameter.observer(1)]);
+        } else {
+            automaton.set_observers(vec![]);
+        }
+
         // Start by spreading chemicals randomly
         automaton.spread_chemicals_randomly(automaton.chemicals.len() as u32 + 1);
 
@@ -54,6 +113,10 @@ fn run_experiment(automaton: &mut GPUNChemicalsCellularAutomaton3D, experiment:
         // Simply run the specified number of iterations
         for _ in 0..experiment.iterations {
             automaton.run_iteration();
+
+            if !on_iteration(automaton) {
+                return false;
+            }
         }
 
         // Record the simulation time
@@ -65,6 +128,8 @@ fn run_experiment(automaton: &mut GPUNChemicalsCellularAutomaton3D, experiment:
             write_results(automaton, experiment, duration.as_secs_f32(), file);
         }
 
+        true
+
     }
 
 
@@ -83,14 +148,13 @@ fn run_experiment(automaton: &mut GPUNChemicalsCellularAutomaton3D, experiment:
             exclude_fully_dominated: experiment.exclude_fully_dominated
         };
 
-        // We're going to work with a while loop.
-        // Start by registering the start-value from the 'varying' entry
-        let mut val = varying.min;
-
         println!("Varying between {} and {} with steps {}", varying.min, varying.max, varying.step);
 
-        // Continue iterating while 'val' is smaller than or equal to 'max'
-        while val <= varying.max {
+        // The values are computed from their index, so that rounding errors don't accumulate.
+        // The experiment has been checked by batch_iterations, so the number of values is known.
+        for index in 0..varying.num_values().unwrap_or(0) {
+
+            let val = varying.value(index);
 
             // 1. Update the species configuration, by applying this 'val' to the right chemical
             
@@ -121,20 +185,76 @@ fn run_experiment(automaton: &mut GPUNChemicalsCellularAutomaton3D, experiment:
 
 
             // 2. Make a recursive call to this method to possibly vary other variables and run the experiment
-            run_experiment(automaton, &recursive_experiment, file);
+            if !run_experiment(automaton, &recursive_experiment, file, on_iteration) {
+                return false;
+            }
+
+        }
 
+        true
 
+    }
+
+}
 
-            // Increase the value with 'step' for the next iteration
-            val += varying.step;
 
+// Why batch_iterations rejects an experiment
+pub const INVALID_EXPERIMENT: &str = "Every entry of the experiment should have a finite range and a positive step, and the experiment can't be too large";
+
+//
+// The total number of iterations that the experiment runs: the iterations of every combination of values.
+// None if one of the entries doesn't have a finite range and a positive step, or if the experiment is too large to run.
+//
+pub fn batch_iterations(experiment: &BatchExperiment) -> Option<u64> {
+    let mut num_runs: u64 = 1;
+
+    for entry in &experiment.entries {
+        num_runs = num_runs.checked_mul(entry.num_values()?)?;
+    }
+
+    // Every run follows the damage in two copies of the automaton, so those are limited as well
+    for export_entry in &experiment.export_entries {
+        if export_entry.damage_iterations.unwrap_or(10) > MAX_DAMAGE_ITERATIONS || export_entry.damaged_cells.unwrap_or(1) > MAX_DAMAGED_CELLS {
+            return None;
         }
+    }
+
+    num_runs.checked_mul(experiment.iterations as u64)
+}
 
 
-        
+//
+// Run the batch on an automaton, writing the results to the experiment's csv-file. Afterwards, recording is restored
+// to its original setting. Returns the name of the file, or None if the experiment was stopped by 'on_iteration'.
+//
+pub fn run_batch(automaton: &mut GPUNChemicalsCellularAutomaton3D, experiment: &BatchExperiment, on_iteration: &mut dyn FnMut(&GPUNChemicalsCellularAutomaton3D) -> bool) -> std::result::Result<Option<String>, String> {
 
+    if batch_iterations(experiment).is_none() {
+        return Err(String::from(INVALID_EXPERIMENT));
     }
 
+    // Create the specified file
+    let mut file_name = String::from(&experiment.file_name);
+    file_name.push_str(".csv");
+
+    let mut file = File::create(Path::new(&file_name)).map_err(|e| format!("Error when creating file {}: {}", file_name, e))?;
+
+    // The first row of the file will indicate the type of values in the column
+    write_types(automaton, experiment, &mut file);
+
+    let record_geometry = automaton.record_geometry;
+    let record_structure_factor = automaton.record_structure_factor;
+    let record_segregation = automaton.record_segregation;
+    let observers = automaton.set_observers(vec![]);
+
+    let finished = run_experiment(automaton, experiment, &mut file, on_iteration);
+
+    automaton.record_geometry = record_geometry;
+    automaton.record_structure_factor = record_structure_factor;
+    automaton.record_segregation = record_segregation;
+    automaton.set_observers(observers);
+
+    Ok(if finished { Some(file_name) } else { None })
 }
 
 
@@ -175,17 +295,27 @@ fn write_results(automaton: &GPUNChemicalsCellularAutomaton3D, experiment: &Batc
 
         } else if export_entry.attribute == "order-parameter" {
 
-            // Insert only the last value of the order parameter
-            let op = automaton.get_order_parameters();
-
-            for i in 0..(automaton.chemicals.len() + 1) {
-                line.push_str(op[op.len()-1][i].to_string().as_str());
+            // Insert the order parameter of the final state
+            for epsilon in Metric::OrderParameter.measure(automaton) {
+                line.push_str(epsilon.to_string().as_str());
                 line.push(';');
             }
 
+        } else if export_entry.attribute == "shape-descriptors" {
+
+            // The densities of the four Minkowski functionals of every cell-type
+            let minkowski = measure_minkowski_functionals(automaton, automaton.chemicals.len() + 1);
+
+            for functionals in &minkowski.normalised {
+                for value in [functionals.volume, functionals.surface_area, functionals.integrated_mean_curvature, functionals.euler_characteristic] {
+                    line.push_str(value.to_string().as_str());
+                    line.push(';');
+                }
+            }
+
         } else if export_entry.attribute == "order-parameter-evolution" {
 
-            // Insert only the last value of the order parameter
+            // Insert the order parameter of every iteration, which is observed during the experiment
             let ops = automaton.get_order_parameters();
 
             for op in ops {
@@ -225,6 +355,168 @@ fn write_results(automaton: &GPUNChemicalsCellularAutomaton3D, experiment: &Batc
                 line.push(';');
             }
             
+        } else if export_entry.attribute == "stability" {
+
+            // For every chemical: the exact kernel integral, whether a pattern is predicted and its expected wavelength
+            let stability = analyse_stability(&automaton.chemicals, automaton.size());
+
+            for kernel in &stability.chemicals {
+                line.push_str(kernel.kernel_integral.to_string().as_str());
+                line.push(';');
+
+                line.push_str(if kernel.unstable { "1" } else { "0" });
+                line.push(';');
+
+                line.push_str(kernel.expected_wavelength.to_string().as_str());
+                line.push(';');
+            }
+
+        } else if export_entry.attribute == "geometry" {
+
+            let geometry = measure_geometry(automaton, automaton.chemicals.len() + 1);
+
+            // For every cell-type: volume, surface area and surface-to-volume ratio
+            for cell_type in &geometry.cell_types {
+                line.push_str(cell_type.volume.to_string().as_str());
+                line.push(';');
+
+                line.push_str(cell_type.surface_area.to_string().as_str());
+                line.push(';');
+
+                line.push_str(cell_type.surface_to_volume.to_string().as_str());
+                line.push(';');
+            }
+
+        } else if export_entry.attribute == "geometry-evolution" {
+
+            // For every recorded iteration and every cell-type: volume, surface area and surface-to-volume ratio
+            for geometry in automaton.get_geometry_history() {
+                for cell_type in &geometry.cell_types {
+                    line.push_str(cell_type.volume.to_string().as_str());
+                    line.push(';');
+
+                    line.push_str(cell_type.surface_area.to_string().as_str());
+                    line.push(';');
+
+                    line.push_str(cell_type.surface_to_volume.to_string().as_str());
+                    line.push(';');
+                }
+            }
+
+        } else if export_entry.attribute == "components" {
+
+            let components = find_components(automaton, automaton.chemicals.len() + 1, export_entry.connectivity.unwrap_or_default());
+
+            // For every cell-type: the number of components, the largest fraction and whether it percolates along x, y and z
+            for cell_type in &components.cell_types {
+                line.push_str(cell_type.count.to_string().as_str());
+                line.push(';');
+
+                line.push_str(cell_type.largest_fraction.to_string().as_str());
+                line.push(';');
+
+                for percolates in cell_type.percolates {
+                    line.push_str(if percolates { "1" } else { "0" });
+                    line.push(';');
+                }
+            }
+
+        } else if export_entry.attribute == "component-histogram" {
+
+            let components = find_components(automaton, automaton.chemicals.len() + 1, export_entry.connectivity.unwrap_or_default());
+
+            for cell_type in &components.cell_types {
+                for count in &cell_type.size_histogram {
+                    line.push_str(count.to_string().as_str());
+                    line.push(';');
+                }
+            }
+
+        } else if export_entry.attribute == "topology" {
+
+            let topology = measure_topology(automaton, automaton.chemicals.len() + 1);
+
+            // For every cell-type: the Euler characteristic and the Betti numbers β0, β1 and β2
+            for cell_type in &topology.cell_types {
+                for value in [cell_type.euler_characteristic, cell_type.betti_0 as i64, cell_type.betti_1 as i64, cell_type.betti_2 as i64] {
+                    line.push_str(value.to_string().as_str());
+                    line.push(';');
+                }
+            }
+
+        } else if export_entry.attribute == "structure-factor" {
+
+            // The full radially averaged structure factor of every cell-type
+            let structure_factor = measure_structure_factor(automaton, automaton.chemicals.len() + 1);
+
+            for cell_type in &structure_factor.cell_types {
+                for value in &cell_type.structure_factor {
+                    line.push_str(value.to_string().as_str());
+                    line.push(';');
+                }
+            }
+
+        } else if export_entry.attribute == "wavelength" {
+
+            // For every cell-type: the dominant wavelength (in cells) and the width of the peak
+            let structure_factor = measure_structure_factor(automaton, automaton.chemicals.len() + 1);
+
+            for cell_type in &structure_factor.cell_types {
+                line.push_str(cell_type.dominant_wavelength.to_string().as_str());
+                line.push(';');
+
+                line.push_str(cell_type.peak_width.to_string().as_str());
+                line.push(';');
+            }
+
+        } else if export_entry.attribute == "wavelength-evolution" {
+
+            // For every recorded iteration: the dominant wavelength of every cell-type
+            for structure_factor in automaton.get_structure_factor_history() {
+                for cell_type in &structure_factor.cell_types {
+                    line.push_str(cell_type.dominant_wavelength.to_string().as_str());
+                    line.push(';');
+                }
+            }
+
+        } else if export_entry.attribute == "interface-matrix" {
+
+            let geometry = measure_geometry(automaton, automaton.chemicals.len() + 1);
+
+            // The (K+1) x (K+1) matrix, row by row
+            for row in &geometry.interface_matrix {
+                for faces in row {
+                    line.push_str(faces.to_string().as_str());
+                    line.push(';');
+                }
+            }
+
+        } else if export_entry.attribute == "damage-spreading" {
+
+            // Perturb a copy of the final state
+            let iterations = export_entry.damage_iterations.unwrap_or(10);
+            let seed = export_entry.damage_seed.unwrap_or(1);
+            let cells = random_cells(automaton.size(), export_entry.damaged_cells.unwrap_or(1), seed);
+
+            // The Hamming distance after every iteration
+            match damage_spreading(automaton, automaton.chemicals.len() + 1, &cells, iterations, seed) {
+                Ok(damage) => for sample in damage.samples.iter().skip(1) {
+                    line.push_str(sample.hamming_distance.to_string().as_str());
+                    line.push(';');
+                },
+                Err(e) => panic!("Error when following the damage spreading for {}: {}", experiment.file_name, e)
+            }
+
+        } else if export_entry.attribute == "segregation-matrix" {
+
+            // The (K+1) x (K+1) matrix of the final state, row by row
+            for row in automaton.get_segregation_matrix() {
+                for ratio in row {
+                    line.push_str(ratio.to_string().as_str());
+                    line.push(';');
+                }
+            }
+
         }
 
     }
@@ -244,6 +536,12 @@ fn write_results(automaton: &GPUNChemicalsCellularAutomaton3D, experiment: &Batc
 }
 
 
+//
+// The name of a cell-type in the column headers: its index, or "undif." for the undifferentiated cell-type
+//
+fn cell_type_label(automaton: &GPUNChemicalsCellularAutomaton3D, cell_type: usize) -> String {
+    if cell_type == automaton.chemicals.len() { String::from("undif.") } else { cell_type.to_string() }
+}
 
 
 fn write_types(automaton: &GPUNChemicalsCellularAutomaton3D, experiment: &BatchExperiment, file: &mut File) {
@@ -308,6 +606,14 @@ fn write_types(automaton: &GPUNChemicalsCellularAutomaton3D, experiment: &BatchE
             line.push_str("Epsilon undif.");
             line.push(';');
 
+        } else if export_entry.attribute == "shape-descriptors" {
+
+            for i in 0..(automaton.chemicals.len() + 1) {
+                let name = cell_type_label(automaton, i);
+
+                line.push_str(format!("Volume density {};Surface density {};Mean curvature density {};Euler density {};", name, name, name, name).as_str());
+            }
+
         } else if export_entry.attribute == "order-parameter-evolution" {
 
             for i in 0..automaton.chemicals.len() {
@@ -344,6 +650,112 @@ fn write_types(automaton: &GPUNChemicalsCellularAutomaton3D, experiment: &BatchE
                 line.push(';');
             }
 
+        } else if export_entry.attribute == "stability" {
+
+            for i in 0..automaton.chemicals.len() {
+                line.push_str(format!("Kernel integral {};Unstable {};Expected wavelength {};", i, i, i).as_str());
+            }
+
+        } else if export_entry.attribute == "geometry" {
+
+            for i in 0..(automaton.chemicals.len() + 1) {
+                let name = cell_type_label(automaton, i);
+
+                line.push_str(format!("Volume {};Surface area {};S/V {};", name, name, name).as_str());
+            }
+
+        } else if export_entry.attribute == "geometry-evolution" {
+
+            // The geometry is recorded once at the start and once after every iteration
+            for iteration in 0..(experiment.iterations + 1) {
+                for i in 0..(automaton.chemicals.len() + 1) {
+                    let name = cell_type_label(automaton, i);
+
+                    line.push_str(format!("Volume {} it. {};Surface area {} it. {};S/V {} it. {};", name, iteration, name, iteration, name, iteration).as_str());
+                }
+            }
+
+        } else if export_entry.attribute == "components" {
+
+            for i in 0..(automaton.chemicals.len() + 1) {
+                let name = cell_type_label(automaton, i);
+
+                line.push_str(format!("Components {};Largest fraction {};Percolates x {};Percolates y {};Percolates z {};", name, name, name, name, name).as_str());
+            }
+
+        } else if export_entry.attribute == "component-histogram" {
+
+            // Bin b counts the components with a size in [2^b, 2^(b+1))
+            let num_bins = (automaton.size().pow(3)).ilog2() + 1;
+
+            for i in 0..(automaton.chemicals.len() + 1) {
+                let name = cell_type_label(automaton, i);
+
+                for bin in 0..num_bins {
+                    line.push_str(format!("Components {} size {}-{};", name, 1u64 << bin, (1u64 << (bin + 1)) - 1).as_str());
+                }
+            }
+
+        } else if export_entry.attribute == "topology" {
+
+            for i in 0..(automaton.chemicals.len() + 1) {
+                let name = cell_type_label(automaton, i);
+
+                line.push_str(format!("Euler char. {};Betti 0 {};Betti 1 {};Betti 2 {};", name, name, name, name).as_str());
+            }
+
+        } else if export_entry.attribute == "structure-factor" {
+
+            // Shell n contains the wavenumbers around 2πn / size
+            for i in 0..(automaton.chemicals.len() + 1) {
+                let name = cell_type_label(automaton, i);
+
+                for n in 0..(automaton.size() / 2 + 1) {
+                    line.push_str(format!("S {} shell {};", name, n).as_str());
+                }
+            }
+
+        } else if export_entry.attribute == "wavelength" {
+
+            for i in 0..(automaton.chemicals.len() + 1) {
+                let name = cell_type_label(automaton, i);
+
+                line.push_str(format!("Wavelength {};Peak width {};", name, name).as_str());
+            }
+
+        } else if export_entry.attribute == "wavelength-evolution" {
+
+            // The structure factor is measured once at the start and once after every iteration
+            for iteration in 0..(experiment.iterations + 1) {
+                for i in 0..(automaton.chemicals.len() + 1) {
+                    let name = cell_type_label(automaton, i);
+
+                    line.push_str(format!("Wavelength {} it. {};", name, iteration).as_str());
+                }
+            }
+
+        } else if export_entry.attribute == "interface-matrix" {
+
+            for a in 0..(automaton.chemicals.len() + 1) {
+                for b in 0..(automaton.chemicals.len() + 1) {
+                    line.push_str(format!("Interface {}-{};", a, b).as_str());
+                }
+            }
+
+        } else if export_entry.attribute == "damage-spreading" {
+
+            for i in 1..=export_entry.damage_iterations.unwrap_or(10) {
+                line.push_str(format!("Damage {};", i).as_str());
+            }
+
+        } else if export_entry.attribute == "segregation-matrix" {
+
+            for a in 0..(automaton.chemicals.len() + 1) {
+                for b in 0..(automaton.chemicals.len() + 1) {
+                    line.push_str(format!("Segregation {}-{};", a, b).as_str());
+                }
+            }
+
         }
 
     }
@@ -364,39 +776,31 @@ fn write_types(automaton: &GPUNChemicalsCellularAutomaton3D, experiment: &BatchE
 
 
 #[post("/batch/run-experiment")]
-async fn batch_run_experiment(state: web::Data<Mutex<CAAppData>>, experiment: web::Json<BatchExperiment>) -> Result<impl Responder> {
+async fn batch_run_experiment(state: Session, experiment: web::Json<BatchExperiment>) -> Result<impl Responder> {
 
     println!("Running batch experiment");
 
     // During the entire time-span of this experiment, the server state will be locked.
     // This is done to prevent any other interaction with the server interfere with this experiment.
+    // A batch that's submitted as a job runs in the background instead.
 
-    let mut state_mod = state.lock().unwrap();
-
-
-    // Create the specified file
-    let mut file_name = String::from(&experiment.file_name);
-    file_name.push_str(".csv");
-
-    let path = Path::new(&file_name);
-
-    let mut file = match File::create(&path) {
-        Err(e) => panic!("Error when creating file {}: {}", file_name, e),
-        Ok(file) => file
-    };
-
-    // The first row of the file will indicate the type of values in the column
-    write_types(&state_mod.nchem_ca, &experiment, &mut file);
+    if batch_iterations(&experiment).is_none() {
+        return Err(error::ErrorBadRequest(INVALID_EXPERIMENT));
+    }
 
-    // Run the batch
-    run_experiment(&mut state_mod.nchem_ca, &experiment, &mut file);
+    let mut state_mod = state.lock().unwrap();
 
+    let result = run_batch(&mut state_mod.nchem_ca, &experiment, &mut |_| true);
 
     // Drop the lock on the state
     drop(state_mod);
 
+    if let Err(e) = result {
+        return Err(error::ErrorInternalServerError(e));
+    }
+
     println!("Finished batch experiment.");
 
     Ok("")
 
-}
\ No newline at end of file
+}
@@ -1,51 +1,68 @@
-use std::sync::Mutex;
+use actix_web::{post, web, error, Responder, Result};
+use serde::{Deserialize, Serialize};
+use crate::analysis::difference::{compare_automata, StateDifference};
+use crate::{appdata::dim3d::automata::{automaton::CellularAutomaton3D, automaton_cpu::CPUCellularAutomaton3D, automaton_gpu::GPUCellularAutomaton3D}};
+use crate::appdata::sessions::Session;
 
-use actix_web::{post, web, Responder, Result};
-use crate::{CAAppData, appdata::dim3d::automata::{automaton::CellularAutomaton3D, automaton_cpu::CPUCellularAutomaton3D, automaton_gpu::GPUCellularAutomaton3D}};
+
+#[derive(Deserialize)]
+pub struct InfoCompareCpuGpu {
+    // Whether the volume of differences should be included, false by default
+    volume: Option<bool>
+}
+
+#[derive(Serialize)]
+pub struct ResponseCompareCpuGpu {
+    message: String,
+    cpu_iterations: u32,
+    gpu_iterations: u32,
+    difference: StateDifference
+}
 
 
 /**
- * Helper function: run correctness benchmark and produce human-readable feedback
+ * Helper function: run correctness benchmark and produce human-readable feedback along with the differences
  */
-fn compare_with_human_feedback(cpu_ca: &CPUCellularAutomaton3D, gpu_ca: &GPUCellularAutomaton3D) -> String {
-    
+fn compare_with_human_feedback(cpu_ca: &CPUCellularAutomaton3D, gpu_ca: &GPUCellularAutomaton3D, include_volume: bool) -> Result<ResponseCompareCpuGpu> {
+
+    let difference = compare_automata(cpu_ca, gpu_ca, include_volume).map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+
     let message: String;
 
     // If cpu and gpu at a different number of iterations, make notice
     if cpu_ca.get_iteration_count() != gpu_ca.get_iteration_count() {
 
         message = format!("Generation mismatch: CPU[{}] versus GPU[{}]\nYou can request to run additional iterations until both simulations live in the same generation and compare them automatically.", cpu_ca.get_iteration_count(), gpu_ca.get_iteration_count());
-   
-    } else {
-
-        let comparison = cpu_ca.compare(gpu_ca);
-
-        if comparison {
-            message = format!("Complete match");
-        } else {
-            message = format!("Outcome mismatch");
-        }
 
+    } else if difference.mismatches == 0 {
+        message = format!("Complete match");
+    } else {
+        message = format!("Outcome mismatch: {} cells differ", difference.mismatches);
     }
 
-    message
+    Ok(ResponseCompareCpuGpu {
+        message,
+        cpu_iterations: cpu_ca.get_iteration_count(),
+        gpu_iterations: gpu_ca.get_iteration_count(),
+        difference
+    })
 }
 
 
 #[post("/benchmarks/compare-cpu-gpu")]
-async fn benchmarks_compare_cpu_gpu(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn benchmarks_compare_cpu_gpu(state: Session, info: web::Query<InfoCompareCpuGpu>) -> Result<impl Responder> {
 
     let state_mod = state.lock().unwrap();
 
-    let message = compare_with_human_feedback(&state_mod.cpu_ca, &state_mod.gpu_ca);
+    let result = compare_with_human_feedback(&state_mod.cpu_ca, &state_mod.gpu_ca, info.volume.unwrap_or(false));
 
     drop(state_mod);
 
-    Ok(message)
+    Ok(web::Json(result?))
 }
 
 #[post("/benchmarks/compare-cpu-gpu-catch-up")]
-async fn benchmarks_compare_cpu_gpu_catch_up(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn benchmarks_compare_cpu_gpu_catch_up(state: Session, info: web::Query<InfoCompareCpuGpu>) -> Result<impl Responder> {
 
     let mut state_mod = state.lock().unwrap();
 
@@ -59,9 +76,9 @@ async fn benchmarks_compare_cpu_gpu_catch_up(state: web::Data<Mutex<CAAppData>>)
         state_mod.gpu_ca.run_iteration();
     }
 
-    let message = compare_with_human_feedback(&state_mod.cpu_ca, &state_mod.gpu_ca);
+    let result = compare_with_human_feedback(&state_mod.cpu_ca, &state_mod.gpu_ca, info.volume.unwrap_or(false));
 
     drop(state_mod);
 
-    Ok(message)
-}
\ No newline at end of file
+    Ok(web::Json(result?))
+}
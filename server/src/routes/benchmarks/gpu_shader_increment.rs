@@ -1,10 +1,9 @@
-use std::sync::Mutex;
-
-use actix_web::{post, web, Responder, Result};
-use crate::{CAAppData, appdata::dim3d::automata::{automaton::CellularAutomaton3D, automaton_cpu::CPUCellularAutomaton3D, automaton_gpu::GPUCellularAutomaton3D}, AUTOMATON_SIZE};
+use actix_web::{post, Responder, Result};
+use crate::{appdata::dim3d::automata::{automaton::CellularAutomaton3D, automaton_cpu::CPUCellularAutomaton3D, automaton_gpu::GPUCellularAutomaton3D}, AUTOMATON_SIZE};
+use crate::appdata::sessions::Session;
 
 #[post("/benchmarks/gpu-shader-increment")]
-async fn benchmarks_gpu_shader_increment(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn benchmarks_gpu_shader_increment(state: Session) -> Result<impl Responder> {
 
     let mut state_mod = state.lock().unwrap();
 
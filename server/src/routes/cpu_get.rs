@@ -1,13 +1,48 @@
-use std::sync::Mutex;
+use actix_web::{get, web, error, HttpResponse, Responder, Result};
+use serde::Deserialize;
+use crate::{appdata::dim3d::automata::automaton::CellularAutomaton3D, meshgeneration::{extractor::ExtractorKind, mesh_format::MeshFormat, mesh_options::MeshOptions, scalar_field::ScalarFieldKind, smoothing::SmoothingKind}};
+use crate::appdata::sessions::Session;
 
-use actix_web::{get, web, Responder, Result};
-use crate::{CAAppData, appdata::dim3d::automata::automaton::CellularAutomaton3D};
+
+#[derive(Deserialize)]
+pub struct InfoGetTriangles {
+    pub format: Option<MeshFormat>,
+    pub periodic: Option<bool>,
+    pub tile: Option<bool>,
+    pub extractor: Option<ExtractorKind>,
+    pub field: Option<ScalarFieldKind>,
+    pub radius: Option<f32>,
+    pub iso_level: Option<f32>,
+    pub resolution: Option<usize>,
+    pub smoothing: Option<SmoothingKind>,
+    pub smoothing_iterations: Option<usize>,
+    pub target_triangles: Option<usize>,
+    pub max_error: Option<f32>
+}
+
+impl InfoGetTriangles {
+    pub fn mesh_options(&self) -> MeshOptions {
+        MeshOptions {
+            periodic: self.periodic.unwrap_or(false),
+            tile: self.tile.unwrap_or(false),
+            extractor: self.extractor.unwrap_or_default(),
+            field: self.field.unwrap_or_default(),
+            radius: self.radius,
+            iso_level: self.iso_level,
+            resolution: self.resolution,
+            smoothing: self.smoothing,
+            smoothing_iterations: self.smoothing_iterations,
+            target_triangles: self.target_triangles,
+            max_error: self.max_error
+        }
+    }
+}
 
 
 
 
 #[get("/cpu/get-current-state")]
-async fn cpu_get_current_state(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn cpu_get_current_state(state: Session) -> Result<impl Responder> {
     let state_mod = state.lock().unwrap();
     let response = web::Json(state_mod.cpu_ca.clone());
     drop(state_mod);
@@ -16,19 +51,29 @@ async fn cpu_get_current_state(state: web::Data<Mutex<CAAppData>>) -> Result<imp
 }
 
 #[get("/cpu/get-current-state-triangles")]
-async fn cpu_get_current_state_triangles(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn cpu_get_current_state_triangles(state: Session, info: web::Query<InfoGetTriangles>) -> Result<impl Responder> {
+    // Without a format, the mesh is sent as glTF
+    let format = info.format.unwrap_or(MeshFormat::Gltf);
+
+    let options = info.mesh_options();
+
     let state_mod = state.lock().unwrap();
 
-    // Create a list of triangles according to the marching cubes algorithm
-    let triangles = state_mod.cpu_ca.get_marching_cubes_mesh();
+    options.validate(state_mod.cpu_ca.size()).map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+
+    // Create a mesh according to the marching cubes algorithm
+    let mesh = state_mod.cpu_ca.get_marching_cubes_mesh_as(format, &options).map_err(|e| error::ErrorInternalServerError(e.to_string()))?;
 
     drop(state_mod);
 
-    Ok(triangles)
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", format.file_name())))
+        .body(mesh))
 }
 
 #[get("/cpu/get-iterations")]
-async fn cpu_get_iterations(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn cpu_get_iterations(state: Session) -> Result<impl Responder> {
     let state_mod = state.lock().unwrap();
     let iterations = state_mod.cpu_ca.get_iteration_count();
     drop(state_mod);
@@ -1,8 +1,18 @@
-
-use std::sync::Mutex;
-
-use actix_web::{get, web, Responder, Result};
-use crate::{CAAppData, AUTOMATON_SIZE};
+use actix_web::{get, web, error, Responder, Result};
+use serde::Deserialize;
+use crate::analysis::difference::compare_automata;
+use crate::appdata::dim3d::automata::automaton::CellularAutomaton3D;
+use crate::AUTOMATON_SIZE;
+use crate::appdata::sessions::Session;
+
+
+#[derive(Deserialize)]
+pub struct InfoGetCompareAutomata {
+    // "cpu", "gpu" or "nchem"
+    first: String,
+    second: String,
+    volume: Option<bool>
+}
 
 
 
@@ -11,11 +21,35 @@ use crate::{CAAppData, AUTOMATON_SIZE};
 
 
 #[get("/general/get-automaton-size")]
-async fn general_get_automaton_size(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn general_get_automaton_size(state: Session) -> Result<impl Responder> {
 
     Ok(web::Json(AUTOMATON_SIZE))
 
 }
 
 
+/**
+ * Method: the differences between the states of two of the automata ("cpu", "gpu" or "nchem"), which must have the same size.
+ * The volume of differences is only included if requested.
+ */
+#[get("/general/compare-automata")]
+async fn general_compare_automata(state: Session, info: web::Query<InfoGetCompareAutomata>) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    let automaton = |name: &str| -> Result<&dyn CellularAutomaton3D> {
+        match name {
+            "cpu" => Ok(&state_mod.cpu_ca),
+            "gpu" => Ok(&state_mod.gpu_ca),
+            "nchem" => Ok(&state_mod.nchem_ca),
+            other => Err(error::ErrorBadRequest(format!("Unknown automaton '{}', expected cpu, gpu or nchem", other)))
+        }
+    };
 
+    let result = compare_automata(automaton(&info.first)?, automaton(&info.second)?, info.volume.unwrap_or(false));
+
+    drop(state_mod);
+
+    Ok(web::Json(result.map_err(|e| error::ErrorBadRequest(e.to_string()))?))
+
+}
@@ -1,8 +1,20 @@
-use std::sync::Mutex;
-
-use actix_web::{post, web, Responder, Result};
+use actix_web::{post, web, error, Responder, Result};
 use serde::{Serialize, Deserialize};
-use crate::{CAAppData, CAChemical, CAChemicalGroup, appdata::dim3d::automata::automaton::CellularAutomaton3D, routes::cpu_post::{InfoPostSpreadChemicals, ResponsePostGeneral}};
+use crate::{CAChemical, CAChemicalGroup, appdata::dim3d::automata::automaton::CellularAutomaton3D, routes::cpu_post::{InfoPostSpreadChemicals, ResponsePostGeneral}};
+use crate::appdata::dim3d::automata::{counter_rng::StochasticUpdate, stencil::EvaluationMode};
+use crate::appdata::sessions::Session;
+
+
+#[derive(Deserialize)]
+pub struct InfoPostSetEvaluationMode {
+    mode: EvaluationMode
+}
+
+#[derive(Deserialize)]
+pub struct InfoPostSetStochasticUpdate {
+    // Null to let every cell apply the rules during every iteration again
+    stochastic_update: Option<StochasticUpdate>
+}
 
 
 
@@ -18,7 +30,7 @@ use crate::{CAAppData, CAChemical, CAChemicalGroup, appdata::dim3d::automata::au
  * Method: randomly spread chemicals and make sure the CPU and GPU models get the same random state
  */
 #[post("/general/spread-chemicals-randomly")]
-async fn general_spread_chemicals_randomly(state: web::Data<Mutex<CAAppData>>, info: web::Json<InfoPostSpreadChemicals>) -> Result<impl Responder> {
+async fn general_spread_chemicals_randomly(state: Session, info: web::Json<InfoPostSpreadChemicals>) -> Result<impl Responder> {
 
     let mut state_mod = state.lock().unwrap();
 
@@ -41,7 +53,7 @@ async fn general_spread_chemicals_randomly(state: web::Data<Mutex<CAAppData>>, i
  * Method:
  */
 #[post("/general/create-activator-patch")]
-async fn general_create_activator_patch(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn general_create_activator_patch(state: Session) -> Result<impl Responder> {
 
     let mut state_mod = state.lock().unwrap();
 
@@ -66,4 +78,50 @@ async fn general_create_activator_patch(state: web::Data<Mutex<CAAppData>>) -> R
     drop(state_mod);
 
     Ok(web::Json(ResponsePostGeneral{status: 0}))
-}
\ No newline at end of file
+}
+
+
+/**
+ * Method: set how the influences are evaluated by all automata: "float" or "exact".
+ * In the exact mode, the CPU and GPU models produce bit-identical results.
+ */
+#[post("/general/set-evaluation-mode")]
+async fn general_set_evaluation_mode(state: Session, info: web::Json<InfoPostSetEvaluationMode>) -> Result<impl Responder> {
+
+    let mut state_mod = state.lock().unwrap();
+
+    state_mod.cpu_ca.evaluation_mode = info.mode;
+    state_mod.gpu_ca.evaluation_mode = info.mode;
+    state_mod.nchem_ca.evaluation_mode = info.mode;
+
+    drop(state_mod);
+
+    Ok(web::Json(ResponsePostGeneral{status: 0}))
+
+}
+
+
+/**
+ * Method: let the cells of all automata apply the rules with a probability during every iteration, or always (null).
+ * The random numbers only depend on the seed, the iteration and the cell, so that every backend draws the same ones.
+ */
+#[post("/general/set-stochastic-update")]
+async fn general_set_stochastic_update(state: Session, info: web::Json<InfoPostSetStochasticUpdate>) -> Result<impl Responder> {
+
+    if let Some(stochastic) = &info.stochastic_update {
+        if !(0.0..=1.0).contains(&stochastic.update_probability) {
+            return Err(error::ErrorBadRequest("The update probability should lie between 0 and 1"));
+        }
+    }
+
+    let mut state_mod = state.lock().unwrap();
+
+    state_mod.cpu_ca.stochastic_update = info.stochastic_update;
+    state_mod.gpu_ca.stochastic_update = info.stochastic_update;
+    state_mod.nchem_ca.stochastic_update = info.stochastic_update;
+
+    drop(state_mod);
+
+    Ok(web::Json(ResponsePostGeneral{status: 0}))
+
+}
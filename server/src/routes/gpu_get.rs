@@ -1,15 +1,16 @@
-use std::sync::Mutex;
-
-use actix_web::{get, web, Responder, Result};
+use actix_web::{get, web, error, HttpResponse, Responder, Result};
+use crate::analysis::stability::analyse_single_kernel;
 use crate::appdata::dim3d::automata::automaton::CellularAutomaton3D;
+use crate::meshgeneration::mesh_format::MeshFormat;
+use crate::routes::cpu_get::InfoGetTriangles;
 use crate::appdata::dim3d::automata::automaton_cpu::CPUCellularAutomaton3D;
 use crate::appdata::dim3d::automata::automaton_gpu::GPUCellularAutomaton3D;
-use crate::CAAppData;
+use crate::appdata::sessions::Session;
 
 
 
 #[get("/gpu/get-current-state")]
-async fn gpu_get_current_state(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn gpu_get_current_state(state: Session) -> Result<impl Responder> {
     let state_mod = state.lock().unwrap();
     let response = web::Json(state_mod.gpu_ca.clone());
     drop(state_mod);
@@ -18,22 +19,45 @@ async fn gpu_get_current_state(state: web::Data<Mutex<CAAppData>>) -> Result<imp
 }
 
 #[get("/gpu/get-current-state-triangles")]
-async fn gpu_get_current_state_triangles(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn gpu_get_current_state_triangles(state: Session, info: web::Query<InfoGetTriangles>) -> Result<impl Responder> {
+    // Without a format, the mesh is sent as glTF
+    let format = info.format.unwrap_or(MeshFormat::Gltf);
+
+    let options = info.mesh_options();
+
     let state_mod = state.lock().unwrap();
 
-    // Create a list of triangles according to the marching cubes algorithm
-    let triangles = state_mod.gpu_ca.get_marching_cubes_mesh();
+    options.validate(state_mod.gpu_ca.size()).map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+
+    // Create a mesh according to the marching cubes algorithm
+    let mesh = state_mod.gpu_ca.get_marching_cubes_mesh_as(format, &options).map_err(|e| error::ErrorInternalServerError(e.to_string()))?;
 
     drop(state_mod);
 
-    Ok(triangles)
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", format.file_name())))
+        .body(mesh))
 }
 
 #[get("/gpu/get-iterations")]
-async fn gpu_get_iterations(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn gpu_get_iterations(state: Session) -> Result<impl Responder> {
     let state_mod = state.lock().unwrap();
     let iterations = state_mod.gpu_ca.get_iteration_count();
     drop(state_mod);
 
     Ok(u32::to_string(&iterations))
-}
\ No newline at end of file
+}
+
+/**
+ * Method: predict whether the DC/UC kernel forms a pattern, and report its exact discrete integral
+ */
+#[get("/gpu/get-stability-analysis")]
+async fn gpu_get_stability_analysis(state: Session) -> Result<impl Responder> {
+    let state_mod = state.lock().unwrap();
+    let ca = &state_mod.gpu_ca;
+    let result = analyse_single_kernel(ca.dc_range, ca.dc_influence, ca.uc_range, ca.uc_influence, ca.size());
+    drop(state_mod);
+
+    Ok(web::Json(result))
+}
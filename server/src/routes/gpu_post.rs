@@ -1,9 +1,9 @@
-use std::{sync::Mutex, time::Instant};
+use std::time::Instant;
 
 use actix_web::{post, web, Responder, Result};
 use serde::{Deserialize, Serialize};
 use crate::appdata::dim3d::automata::{automaton::CellularAutomaton3D, automaton_cpu::CPUCellularAutomaton3D, automaton_gpu::GPUCellularAutomaton3D};
-use crate::CAAppData;
+use crate::appdata::sessions::Session;
 
 #[derive(Deserialize)]
 pub struct InfoPostInitialise {
@@ -35,7 +35,7 @@ pub struct ResponsePostRunIteration {
 }
 
 #[post("/gpu/initialise")]
-pub async fn gpu_post_initialise(state: web::Data<Mutex<CAAppData>>, info: web::Json<InfoPostInitialise>) -> Result<impl Responder> {
+pub async fn gpu_post_initialise(state: Session, info: web::Json<InfoPostInitialise>) -> Result<impl Responder> {
     let mut state_mod = state.lock().unwrap();
     state_mod.gpu_ca.reset(
         info.size,
@@ -50,7 +50,7 @@ pub async fn gpu_post_initialise(state: web::Data<Mutex<CAAppData>>, info: web::
 }
 
 #[post("/gpu/clear-all-voxels")]
-pub async fn gpu_post_clear_all_voxels(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+pub async fn gpu_post_clear_all_voxels(state: Session) -> Result<impl Responder> {
     let mut state_mod = state.lock().unwrap();
     state_mod.gpu_ca.clear_all_voxels();
     drop(state_mod);
@@ -59,7 +59,7 @@ pub async fn gpu_post_clear_all_voxels(state: web::Data<Mutex<CAAppData>>) -> Re
 }
 
 #[post("/gpu/spread-chemicals-randomly")]
-pub async fn gpu_post_spread_chemicals_randomly(state: web::Data<Mutex<CAAppData>>, info: web::Json<InfoPostSpreadChemicals>) -> Result<impl Responder> {
+pub async fn gpu_post_spread_chemicals_randomly(state: Session, info: web::Json<InfoPostSpreadChemicals>) -> Result<impl Responder> {
     let mut state_mod = state.lock().unwrap();
     state_mod.gpu_ca.spread_chemicals_randomly(info.chemicals);
     drop(state_mod);
@@ -68,7 +68,7 @@ pub async fn gpu_post_spread_chemicals_randomly(state: web::Data<Mutex<CAAppData
 }
 
 #[post("/gpu/run-iteration")]
-pub async fn gpu_post_run_iteration(state: web::Data<Mutex<CAAppData>>, info: web::Json<InfoPostRunIteration>) -> Result<impl Responder> {
+pub async fn gpu_post_run_iteration(state: Session, info: web::Json<InfoPostRunIteration>) -> Result<impl Responder> {
     let mut state_mod = state.lock().unwrap();
     
     let start = Instant::now();
@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use actix_web::{get, post, delete, web, error, HttpResponse, Responder, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::appdata::jobs::{JobManager, JobRequest, JobResult, JobState};
+use crate::appdata::sessions::Session;
+use crate::routes::cpu_post::ResponsePostGeneral;
+
+
+// The path of a job. It's deserialised by name, since routes under /sessions/{session_id} have the session id in their path as well.
+#[derive(Deserialize)]
+pub struct InfoJobPath {
+    id: u64
+}
+
+#[derive(Serialize)]
+pub struct ResponsePostJob {
+    id: u64
+}
+
+
+/**
+ * Method: queue a job, {"kind": "run-iteration", "num_iterations": n} or {"kind": "batch", "experiment": {...}},
+ * and return its id. Jobs run in the background, one after the other, on the automaton of the session they were queued in.
+ */
+#[post("/jobs")]
+async fn jobs_post(jobs: web::Data<JobManager>, state: Session, request: web::Json<JobRequest>) -> Result<impl Responder> {
+
+    let id = jobs.submit(&state, request.into_inner()).map_err(error::ErrorBadRequest)?;
+
+    Ok(web::Json(ResponsePostJob{id}))
+}
+
+#[get("/jobs")]
+async fn jobs_get_all(jobs: web::Data<JobManager>, state: Session) -> Result<impl Responder> {
+
+    Ok(web::Json(jobs.list(&state)))
+}
+
+/**
+ * Method: the state and progress of a job: the iterations done, the estimated time remaining and the latest metrics
+ */
+#[get("/jobs/{id}")]
+async fn jobs_get(jobs: web::Data<JobManager>, state: Session, path: web::Path<InfoJobPath>) -> Result<impl Responder> {
+
+    let id = path.id;
+
+    match jobs.status(&state, id) {
+        Some(status) => Ok(web::Json(status)),
+        None => Err(error::ErrorNotFound(format!("There is no job with id {} in this session", id)))
+    }
+}
+
+/**
+ * Method: the result of a job that has completed. A batch job returns the csv-file with its results.
+ */
+#[get("/jobs/{id}/result")]
+async fn jobs_get_result(jobs: web::Data<JobManager>, state: Session, path: web::Path<InfoJobPath>) -> Result<impl Responder> {
+
+    let id = path.id;
+
+    let status = jobs.status(&state, id).ok_or_else(|| error::ErrorNotFound(format!("There is no job with id {} in this session", id)))?;
+
+    match (status.state, status.result) {
+        (JobState::Completed, Some(JobResult::Batch { file_name, csv, .. })) => {
+            let file_name = Path::new(&file_name).file_name().map_or(String::from("results.csv"), |f| f.to_string_lossy().to_string());
+
+            Ok(HttpResponse::Ok()
+                .content_type("text/csv")
+                .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", file_name)))
+                .body(csv.to_vec()))
+        },
+        (JobState::Completed, Some(result)) => Ok(HttpResponse::Ok().json(result)),
+        _ => Err(error::ErrorConflict(format!("Job {} hasn't completed", id)))
+    }
+}
+
+/**
+ * Method: remove a job that has finished, together with its result. Only the last 100 finished jobs are kept anyway.
+ */
+#[delete("/jobs/{id}")]
+async fn jobs_delete(jobs: web::Data<JobManager>, state: Session, path: web::Path<InfoJobPath>) -> Result<impl Responder> {
+
+    let id = path.id;
+
+    match jobs.delete(&state, id) {
+        Some(true) => Ok(web::Json(ResponsePostGeneral{status: 0})),
+        Some(false) => Err(error::ErrorConflict(format!("Job {} hasn't finished, cancel it first", id))),
+        None => Err(error::ErrorNotFound(format!("There is no job with id {} in this session", id)))
+    }
+}
+
+/**
+ * Method: cancel a job. A running job stops after its current iteration.
+ */
+#[post("/jobs/{id}/cancel")]
+async fn jobs_post_cancel(jobs: web::Data<JobManager>, state: Session, path: web::Path<InfoJobPath>) -> Result<impl Responder> {
+
+    let id = path.id;
+
+    match jobs.cancel(&state, id) {
+        Some(status) => Ok(web::Json(status)),
+        None => Err(error::ErrorNotFound(format!("There is no job with id {} in this session", id)))
+    }
+}
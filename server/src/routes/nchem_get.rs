@@ -1,13 +1,80 @@
-use std::sync::Mutex;
+use actix_web::{get, web, error, HttpResponse, Responder, Result};
+use serde::{Deserialize, Serialize};
+use crate::analysis::components::{find_components, Connectivity};
+use crate::analysis::damage_spreading::{damage_spreading, random_cells, MAX_DAMAGE_ITERATIONS, MAX_DAMAGED_CELLS};
+use crate::analysis::geometry::measure_geometry;
+use crate::analysis::minkowski::measure_minkowski_functionals;
+use crate::analysis::order_parameter::{multiscale_order_parameter, MIN_RADIUS_STEP};
+use crate::analysis::stability::analyse_stability;
+use crate::analysis::structure_factor::measure_structure_factor;
+use crate::analysis::topology::measure_topology;
+use crate::appdata::dim3d::automata::{automaton::CellularAutomaton3D, stencil::Neighbourhood};
+use crate::appdata::palette::{cell_type_colour, hex_to_rgb, parse_palette};
+use crate::gltfgeneration::gltf_conversion::{generate_gltf, generate_glb, SceneNode};
+use crate::meshgeneration::cell_type_source::extract_cell_type_mesh_with;
+use crate::meshgeneration::mesh_format::MeshFormat;
+use crate::routes::cpu_get::InfoGetTriangles;
+use crate::volumeio::{npy::{NpyArray, NpyData}, npz::write_npz, vox::write_vox};
+use crate::appdata::sessions::Session;
+
+
+#[derive(Deserialize)]
+pub struct InfoGetStateNpy {
+    dtype: Option<String>
+}
+
+#[derive(Deserialize)]
+pub struct InfoGetStateVox {
+    skip_undifferentiated: Option<bool>
+}
+
+// The format and mesh options of a species scene are read from the same query as InfoGetTriangles
+#[derive(Deserialize)]
+pub struct InfoGetSpeciesScene {
+    palette: Option<String>,
+    include_undifferentiated: Option<bool>
+}
 
-use actix_web::{get, web, Responder, Result};
-use crate::appdata::dim3d::automata::automaton::CellularAutomaton3D;
-use crate::CAAppData;
+#[derive(Deserialize)]
+pub struct InfoGetMetrics {
+    name: String,
+    from: Option<u32>,
+    to: Option<u32>
+}
+
+#[derive(Deserialize)]
+pub struct InfoGetDamageSpreading {
+    iterations: Option<u32>,
+    // Either the number of random cells to perturb, or the position of a single cell
+    cells: Option<usize>,
+    x: Option<usize>,
+    y: Option<usize>,
+    z: Option<usize>,
+    seed: Option<u64>
+}
+
+#[derive(Serialize)]
+pub struct ResponseGetObserver {
+    name: String,
+    interval: u32
+}
+
+#[derive(Deserialize)]
+pub struct InfoGetMultiscaleOrderParameter {
+    min_radius: Option<f32>,
+    max_radius: Option<f32>,
+    step: Option<f32>
+}
+
+#[derive(Deserialize)]
+pub struct InfoGetComponents {
+    connectivity: Option<Connectivity>
+}
 
 
 
 #[get("/nchem/get-current-state")]
-async fn nchem_get_current_state(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn nchem_get_current_state(state: Session) -> Result<impl Responder> {
     let state_mod = state.lock().unwrap();
     let response = web::Json(state_mod.nchem_ca.clone());
     drop(state_mod);
@@ -16,19 +83,29 @@ async fn nchem_get_current_state(state: web::Data<Mutex<CAAppData>>) -> Result<i
 }
 
 #[get("/nchem/get-current-state-triangles")]
-async fn nchem_get_current_state_triangles(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn nchem_get_current_state_triangles(state: Session, info: web::Query<InfoGetTriangles>) -> Result<impl Responder> {
+    // Without a format, the mesh is sent as glTF
+    let format = info.format.unwrap_or(MeshFormat::Gltf);
+
+    let options = info.mesh_options();
+
     let state_mod = state.lock().unwrap();
 
-    // Create a list of triangles according to the marching cubes algorithm
-    let triangles = state_mod.nchem_ca.get_marching_cubes_mesh();
+    options.validate(state_mod.nchem_ca.size()).map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+
+    // Create a mesh according to the marching cubes algorithm
+    let mesh = state_mod.nchem_ca.get_marching_cubes_mesh_as(format, &options).map_err(|e| error::ErrorInternalServerError(e.to_string()))?;
 
     drop(state_mod);
 
-    Ok(triangles)
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", format.file_name())))
+        .body(mesh))
 }
 
 #[get("/nchem/get-iterations")]
-async fn nchem_get_iterations(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn nchem_get_iterations(state: Session) -> Result<impl Responder> {
     let state_mod = state.lock().unwrap();
     let iterations = state_mod.nchem_ca.get_iteration_count();
     drop(state_mod);
@@ -38,7 +115,7 @@ async fn nchem_get_iterations(state: web::Data<Mutex<CAAppData>>) -> Result<impl
 
 
 #[get("/nchem/get-chemical-capture")]
-async fn nchem_get_chemical_capture(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn nchem_get_chemical_capture(state: Session) -> Result<impl Responder> {
 
     let state_mod = state.lock().unwrap();
     let chemical_capture = state_mod.nchem_ca.get_captured_chemical();
@@ -48,8 +125,11 @@ async fn nchem_get_chemical_capture(state: web::Data<Mutex<CAAppData>>) -> Resul
 
 }
 
+/**
+ * Method: the order parameter of every cell-type at every iteration that its observer sampled
+ */
 #[get("/nchem/get-order-parameter")]
-async fn nchem_get_order_parameter(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn nchem_get_order_parameter(state: Session) -> Result<impl Responder> {
 
     let state_mod = state.lock().unwrap();
 
@@ -60,8 +140,152 @@ async fn nchem_get_order_parameter(state: web::Data<Mutex<CAAppData>>) -> Result
     Ok(web::Json(result))
 }
 
+/**
+ * Method: the samples of an observed metric with from <= iteration <= to (all iterations by default)
+ */
+#[get("/nchem/metrics")]
+async fn nchem_get_metrics(state: Session, info: web::Query<InfoGetMetrics>) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    let result = state_mod.nchem_ca.get_metrics().query(&info.name, info.from.unwrap_or(0), info.to.unwrap_or(u32::MAX));
+    let names = state_mod.nchem_ca.get_metrics().names();
+
+    drop(state_mod);
+
+    match result {
+        Some(samples) => Ok(web::Json(samples)),
+        None => Err(error::ErrorBadRequest(format!("The metric '{}' hasn't been observed, the observed metrics are: {}", info.name, names.join(", "))))
+    }
+}
+
+#[get("/nchem/get-observers")]
+async fn nchem_get_observers(state: Session) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    let result: Vec<ResponseGetObserver> = state_mod.nchem_ca.get_observers().into_iter()
+        .map(|(name, interval)| ResponseGetObserver { name, interval })
+        .collect();
+
+    drop(state_mod);
+
+    Ok(web::Json(result))
+}
+
+/**
+ * Method: the number of cells that changed their type during every iteration, with a (K+1) x (K+1) matrix
+ * of the transitions from one cell-type (row) to another (column)
+ */
+#[get("/nchem/get-flip-activity")]
+async fn nchem_get_flip_activity(state: Session) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    let result = state_mod.nchem_ca.get_flip_activity().clone();
+
+    drop(state_mod);
+
+    Ok(web::Json(result))
+}
+
+/**
+ * Method: perturb a copy of the automaton by giving a single cell (x, y, z) or a number of random cells (1 by default)
+ * another cell-type, run both for a number of iterations (10 by default) and report how the differences spread.
+ * At most MAX_DAMAGE_ITERATIONS iterations and MAX_DAMAGED_CELLS cells can be asked for.
+ * The state of the automaton itself is left untouched.
+ */
+#[get("/nchem/get-damage-spreading")]
+async fn nchem_get_damage_spreading(state: Session, info: web::Query<InfoGetDamageSpreading>) -> Result<impl Responder> {
+
+    let iterations = info.iterations.unwrap_or(10);
+    let num_cells = info.cells.unwrap_or(1);
+
+    if iterations > MAX_DAMAGE_ITERATIONS || num_cells > MAX_DAMAGED_CELLS {
+        return Err(error::ErrorBadRequest(format!("Expected at most {} iterations and {} cells", MAX_DAMAGE_ITERATIONS, MAX_DAMAGED_CELLS)));
+    }
+
+    let state_mod = state.lock().unwrap();
+
+    let automaton = state_mod.nchem_ca.clone();
+
+    drop(state_mod);
+
+    let size = automaton.size();
+
+    let cells = match (info.x, info.y, info.z) {
+        (Some(x), Some(y), Some(z)) if x < size && y < size && z < size => vec![[x, y, z]],
+        (None, None, None) => random_cells(size, num_cells, info.seed.unwrap_or(1)),
+        _ => return Err(error::ErrorBadRequest(format!("Expected x, y and z to be given together and below {}", size)))
+    };
+
+    let result = damage_spreading(&automaton, automaton.chemicals.len() + 1, &cells, iterations, info.seed.unwrap_or(1))
+        .map_err(|e| error::ErrorInternalServerError(e.to_string()))?;
+
+    Ok(web::Json(result))
+}
+
+/**
+ * Method: the segregation matrix of the current state, with the neighbour co-occurrences of every pair of cell-types
+ * relative to a well-mixed state (1 for well-mixed, below 1 for segregated and above 1 for attracting cell-types)
+ */
+#[get("/nchem/get-segregation-matrix")]
+async fn nchem_get_segregation_matrix(state: Session) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    let result = state_mod.nchem_ca.get_segregation_matrix();
+
+    drop(state_mod);
+
+    Ok(web::Json(result))
+}
+
+/**
+ * Method: the segregation matrices of every iteration since recording was enabled
+ */
+#[get("/nchem/get-segregation-history")]
+async fn nchem_get_segregation_history(state: Session) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    let result = state_mod.nchem_ca.get_segregation_history().clone();
+
+    drop(state_mod);
+
+    Ok(web::Json(result))
+}
+
+/**
+ * Method: the order parameter of every cell-type over spheres with radii from min_radius to max_radius (1 to 10 by default)
+ */
+#[get("/nchem/get-multiscale-order-parameter")]
+async fn nchem_get_multiscale_order_parameter(state: Session, info: web::Query<InfoGetMultiscaleOrderParameter>) -> Result<impl Responder> {
+
+    let min_radius = info.min_radius.unwrap_or(1.0);
+    let max_radius = info.max_radius.unwrap_or(10.0);
+    let step = info.step.unwrap_or(1.0);
+
+    let state_mod = state.lock().unwrap();
+
+    // A sphere with a radius below 1 doesn't contain any neighbours, and one with a radius above half the size wraps around
+    let size = state_mod.nchem_ca.size();
+
+    if !(min_radius >= 1.0 && max_radius >= min_radius && Neighbourhood::Sphere(max_radius).fits(size) && step >= MIN_RADIUS_STEP) {
+        return Err(error::ErrorBadRequest(format!("Expected 1 <= min_radius <= max_radius <= {} and a step of at least {}", size / 2, MIN_RADIUS_STEP)));
+    }
+
+    let radii: Vec<f32> = (0..).map(|i| min_radius + i as f32 * step).take_while(|r| *r <= max_radius).collect();
+
+    let result = multiscale_order_parameter(&state_mod.nchem_ca, state_mod.nchem_ca.chemicals.len() + 1, &radii);
+
+    drop(state_mod);
+
+    Ok(web::Json(result))
+}
+
 #[get("/nchem/get-species-configuration")]
-async fn nchem_get_species_configuration(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn nchem_get_species_configuration(state: Session) -> Result<impl Responder> {
 
     let state_mod = state.lock().unwrap();
 
@@ -74,7 +298,7 @@ async fn nchem_get_species_configuration(state: web::Data<Mutex<CAAppData>>) ->
 }
 
 #[get("/nchem/state-has-converged")]
-async fn nchem_state_has_converged(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+async fn nchem_state_has_converged(state: Session) -> Result<impl Responder> {
 
     let state_mod = state.lock().unwrap();
 
@@ -83,4 +307,318 @@ async fn nchem_state_has_converged(state: web::Data<Mutex<CAAppData>>) -> Result
     drop(state_mod);
 
     Ok(web::Json(converged))
-}
\ No newline at end of file
+}
+
+
+/**
+ * Method: export the grid of cell-types as a NumPy .npy array of shape (size, size, size) in C order
+ */
+#[get("/nchem/get-current-state-npy")]
+async fn nchem_get_current_state_npy(state: Session, info: web::Query<InfoGetStateNpy>) -> Result<impl Responder> {
+
+    // The cell-types are written as uint8 unless uint16 is requested explicitly
+    let uint16 = match info.dtype.as_deref() {
+        None | Some("uint8") => false,
+        Some("uint16") => true,
+        Some(other) => return Err(error::ErrorBadRequest(format!("Unsupported dtype '{}', expected uint8 or uint16", other)))
+    };
+
+    let state_mod = state.lock().unwrap();
+    let array = state_mod.nchem_ca.get_npy_state(uint16);
+    drop(state_mod);
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .insert_header(("Content-Disposition", "attachment; filename=\"state.npy\""))
+        .body(array.to_bytes()))
+}
+
+
+/**
+ * Method: export the iteration at which every cell changed its type last (0 if it hasn't changed since the state was set)
+ * as a NumPy .npy array of uint32 with shape (size, size, size) in C order
+ */
+#[get("/nchem/get-last-changed-npy")]
+async fn nchem_get_last_changed_npy(state: Session) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    let size = state_mod.nchem_ca.size();
+    let last_changed = state_mod.nchem_ca.get_last_changed();
+
+    drop(state_mod);
+
+    let array = NpyArray::new(vec![size, size, size], NpyData::U32(last_changed));
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .insert_header(("Content-Disposition", "attachment; filename=\"last_changed.npy\""))
+        .body(array.to_bytes()))
+}
+
+
+/**
+ * Method: export the grid, the species configuration and the order parameter history as a NumPy .npz bundle
+ */
+#[get("/nchem/get-current-state-npz")]
+async fn nchem_get_current_state_npz(state: Session) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    let grid = state_mod.nchem_ca.get_npy_state(false);
+
+    // Every species is described by one row: promotor range, promotor influence, demotor range, demotor influence
+    let mut species: Vec<f32> = vec![];
+
+    for group in &state_mod.nchem_ca.chemicals {
+        species.push(group.promote.range);
+        species.push(group.promote.influence);
+        species.push(group.demote.range);
+        species.push(group.demote.influence);
+    }
+
+    let num_species = state_mod.nchem_ca.chemicals.len();
+
+    // The order parameter is stored with one row per cell-type (K+1) and one column per sampled iteration
+    let order_parameters = state_mod.nchem_ca.get_order_parameters();
+    let order_parameter_iterations = state_mod.nchem_ca.get_order_parameter_iterations();
+    let num_iterations = order_parameter_iterations.len();
+
+    drop(state_mod);
+
+    let bundle = write_npz(vec![
+        ("grid", grid),
+        ("species", NpyArray::new(vec![num_species, 4], NpyData::F32(species))),
+        ("order_parameter", NpyArray::new(vec![order_parameters.len(), num_iterations], NpyData::F32(order_parameters.concat()))),
+        ("order_parameter_iterations", NpyArray::new(vec![num_iterations], NpyData::U32(order_parameter_iterations)))
+    ]).map_err(|e| error::ErrorInternalServerError(e.to_string()))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .insert_header(("Content-Disposition", "attachment; filename=\"state.npz\""))
+        .body(bundle))
+}
+
+
+/**
+ * Method: export the grid of cell-types as a MagicaVoxel .vox scene with one palette colour per cell-type
+ */
+#[get("/nchem/get-current-state-vox")]
+async fn nchem_get_current_state_vox(state: Session, info: web::Query<InfoGetStateVox>) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    // The undifferentiated cell-type (K) can be left empty, so that only the species remain visible
+    let empty_cell_type = match info.skip_undifferentiated {
+        Some(true) => Some(state_mod.nchem_ca.chemicals.len() as u32),
+        _ => None
+    };
+
+    let bytes = write_vox(&state_mod.nchem_ca, empty_cell_type);
+
+    drop(state_mod);
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/octet-stream")
+        .insert_header(("Content-Disposition", "attachment; filename=\"state.vox\""))
+        .body(bytes))
+}
+
+
+/**
+ * Method: export one isosurface per species as a single glTF scene, with one named node and material per species.
+ * Unlike get-current-state-triangles, this doesn't depend on the captured chemical.
+ */
+#[get("/nchem/get-current-state-species-scene")]
+async fn nchem_get_current_state_species_scene(state: Session, info: web::Query<InfoGetSpeciesScene>, mesh_info: web::Query<InfoGetTriangles>) -> Result<impl Responder> {
+
+    // Only glTF supports a scene of multiple named and coloured objects
+    let format = mesh_info.format.unwrap_or(MeshFormat::Gltf);
+
+    if format != MeshFormat::Gltf && format != MeshFormat::Glb {
+        return Err(error::ErrorBadRequest("A species scene can only be exported as gltf or glb"));
+    }
+
+    // The palette is cycled through if there are more cell-types than colours
+    let palette = match &info.palette {
+        Some(palette) => Some(parse_palette(palette).map_err(|e| error::ErrorBadRequest(e.to_string()))?),
+        None => None
+    };
+
+    let colour = |cell_type: usize| match &palette {
+        Some(palette) => hex_to_rgb(palette[cell_type % palette.len()]),
+        None => cell_type_colour(cell_type)
+    };
+
+    let options = mesh_info.mesh_options();
+
+    let state_mod = state.lock().unwrap();
+
+    options.validate(state_mod.nchem_ca.size()).map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+
+    let num_species = state_mod.nchem_ca.chemicals.len();
+
+    // The undifferentiated cell-type (K) fills most of the grid, so it's only meshed on request
+    let num_cell_types = match info.include_undifferentiated {
+        Some(true) => num_species + 1,
+        _ => num_species
+    };
+
+    let meshes: Result<Vec<_>, _> = (0..num_cell_types).map(|cell_type| extract_cell_type_mesh_with(&state_mod.nchem_ca, cell_type as u32, &options)).collect();
+
+    drop(state_mod);
+
+    let meshes = meshes.map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+
+    let nodes: Vec<SceneNode> = meshes.iter().enumerate().map(|(cell_type, mesh)| SceneNode {
+        name: if cell_type == num_species { String::from("Undifferentiated") } else { format!("Species {}", cell_type) },
+        mesh,
+        colour: colour(cell_type)
+    }).collect();
+
+    let scene = match format {
+        MeshFormat::Gltf => generate_gltf(&nodes).map(|s| s.into_bytes()),
+        _ => generate_glb(&nodes)
+    }.map_err(|e| error::ErrorInternalServerError(e.to_string()))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", format.file_name().replace("mesh", "species"))))
+        .body(scene))
+}
+
+
+/**
+ * Method: measure the volume, surface area and surface-to-volume ratio of every cell-type, and the interface matrix
+ */
+#[get("/nchem/get-geometry")]
+async fn nchem_get_geometry(state: Session) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    let result = measure_geometry(&state_mod.nchem_ca, state_mod.nchem_ca.chemicals.len() + 1);
+
+    drop(state_mod);
+
+    Ok(web::Json(result))
+
+}
+
+
+/**
+ * Method: the geometry measurements of every iteration since recording was enabled
+ */
+#[get("/nchem/get-geometry-history")]
+async fn nchem_get_geometry_history(state: Session) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    let result = state_mod.nchem_ca.get_geometry_history().clone();
+
+    drop(state_mod);
+
+    Ok(web::Json(result))
+
+}
+
+
+/**
+ * Method: label the connected components of every cell-type, merging them across the periodic boundaries
+ */
+#[get("/nchem/get-components")]
+async fn nchem_get_components(state: Session, info: web::Query<InfoGetComponents>) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    let result = find_components(&state_mod.nchem_ca, state_mod.nchem_ca.chemicals.len() + 1, info.connectivity.unwrap_or_default());
+
+    drop(state_mod);
+
+    Ok(web::Json(result))
+
+}
+
+
+/**
+ * Method: the Euler characteristic and Betti numbers of every cell-type, on the periodic voxel complex
+ */
+#[get("/nchem/get-topology")]
+async fn nchem_get_topology(state: Session) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    let result = measure_topology(&state_mod.nchem_ca, state_mod.nchem_ca.chemicals.len() + 1);
+
+    drop(state_mod);
+
+    Ok(web::Json(result))
+
+}
+
+
+/**
+ * Method: the Minkowski functionals of every cell-type: volume, surface area, integrated mean curvature and Euler characteristic
+ */
+#[get("/nchem/get-minkowski-functionals")]
+async fn nchem_get_minkowski_functionals(state: Session) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    let result = measure_minkowski_functionals(&state_mod.nchem_ca, state_mod.nchem_ca.chemicals.len() + 1);
+
+    drop(state_mod);
+
+    Ok(web::Json(result))
+
+}
+
+
+/**
+ * Method: the radially averaged structure factor of every cell-type, with its dominant wavelength and peak width
+ */
+#[get("/nchem/get-structure-factor")]
+async fn nchem_get_structure_factor(state: Session) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    let result = measure_structure_factor(&state_mod.nchem_ca, state_mod.nchem_ca.chemicals.len() + 1);
+
+    drop(state_mod);
+
+    Ok(web::Json(result))
+
+}
+
+
+/**
+ * Method: the structure factor measurements of every iteration since recording was enabled
+ */
+#[get("/nchem/get-structure-factor-history")]
+async fn nchem_get_structure_factor_history(state: Session) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    let result = state_mod.nchem_ca.get_structure_factor_history().clone();
+
+    drop(state_mod);
+
+    Ok(web::Json(result))
+
+}
+
+
+/**
+ * Method: predict from the kernels of the current species configuration whether patterns form, and at which wavelength
+ */
+#[get("/nchem/get-stability-analysis")]
+async fn nchem_get_stability_analysis(state: Session) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    let result = analyse_stability(&state_mod.nchem_ca.chemicals, state_mod.nchem_ca.size());
+
+    drop(state_mod);
+
+    Ok(web::Json(result))
+
+}
@@ -1,9 +1,12 @@
-use std::{sync::Mutex, time::Instant};
+use std::time::Instant;
 
-use actix_web::{post, web, Responder, Result};
+use actix_web::{post, web, error, Responder, Result};
 use serde::{Deserialize, Serialize};
-use crate::appdata::dim3d::automata::automaton::CellularAutomaton3D;
-use crate::{CAAppData, CAChemical, CAChemicalGroup};
+use crate::analysis::difference::{cell_types_of, compare_cell_types};
+use crate::appdata::dim3d::automata::{automaton::CellularAutomaton3D, observer::Metric, stencil::Neighbourhood};
+use crate::volumeio::{npy::read_npy_integers, vox::read_vox};
+use crate::{CAChemical, CAChemicalGroup};
+use crate::appdata::sessions::Session;
 
 #[derive(Deserialize)]
 pub struct InfoPostInitialise {
@@ -29,6 +32,33 @@ pub struct InfoPostSetChemicalCapture {
     chemical_capture: usize
 }
 
+#[derive(Deserialize)]
+pub struct InfoPostSetRecording {
+    record: bool
+}
+
+#[derive(Deserialize)]
+pub struct InfoPostSetNeighbourhood {
+    neighbourhood: Neighbourhood
+}
+
+#[derive(Deserialize)]
+pub struct InfoPostCompareState {
+    // Whether the volume of differences should be included, false by default
+    volume: Option<bool>
+}
+
+#[derive(Deserialize)]
+pub struct InfoPostAddObserver {
+    metric: Metric,
+    interval: u32
+}
+
+#[derive(Deserialize)]
+pub struct InfoPostRemoveObserver {
+    name: String
+}
+
 #[derive(Serialize)]
 pub struct ResponsePostGeneral {
     status: u32
@@ -61,7 +91,7 @@ pub struct InfoPostSetSpeciesConfiguration {
 
 
 #[post("/nchem/initialise")]
-pub async fn nchem_post_initialise(state: web::Data<Mutex<CAAppData>>, info: web::Json<InfoPostInitialise>) -> Result<impl Responder> {
+pub async fn nchem_post_initialise(state: Session, info: web::Json<InfoPostInitialise>) -> Result<impl Responder> {
     let mut state_mod = state.lock().unwrap();
     state_mod.nchem_ca.reset(
         info.size,
@@ -76,7 +106,7 @@ pub async fn nchem_post_initialise(state: web::Data<Mutex<CAAppData>>, info: web
 }
 
 #[post("/nchem/clear-all-voxels")]
-pub async fn nchem_post_clear_all_voxels(state: web::Data<Mutex<CAAppData>>) -> Result<impl Responder> {
+pub async fn nchem_post_clear_all_voxels(state: Session) -> Result<impl Responder> {
     let mut state_mod = state.lock().unwrap();
     state_mod.nchem_ca.clear_all_voxels();
     drop(state_mod);
@@ -85,7 +115,7 @@ pub async fn nchem_post_clear_all_voxels(state: web::Data<Mutex<CAAppData>>) ->
 }
 
 #[post("/nchem/spread-chemicals-randomly")]
-pub async fn nchem_post_spread_chemicals_randomly(state: web::Data<Mutex<CAAppData>>, info: web::Json<InfoPostSpreadChemicals>) -> Result<impl Responder> {
+pub async fn nchem_post_spread_chemicals_randomly(state: Session, info: web::Json<InfoPostSpreadChemicals>) -> Result<impl Responder> {
     let mut state_mod = state.lock().unwrap();
     state_mod.nchem_ca.spread_chemicals_randomly(info.chemicals);
     drop(state_mod);
@@ -94,7 +124,7 @@ pub async fn nchem_post_spread_chemicals_randomly(state: web::Data<Mutex<CAAppDa
 }
 
 #[post("/nchem/run-iteration")]
-pub async fn nchem_post_run_iteration(state: web::Data<Mutex<CAAppData>>, info: web::Json<InfoPostRunIteration>) -> Result<impl Responder> {
+pub async fn nchem_post_run_iteration(state: Session, info: web::Json<InfoPostRunIteration>) -> Result<impl Responder> {
     let mut state_mod = state.lock().unwrap();
     
     let start = Instant::now();
@@ -112,7 +142,7 @@ pub async fn nchem_post_run_iteration(state: web::Data<Mutex<CAAppData>>, info:
 
 
 #[post("/nchem/set-chemical-capture")]
-pub async fn nchem_post_set_chemical_capture(state: web::Data<Mutex<CAAppData>>, info: web::Json<InfoPostSetChemicalCapture>) -> Result<impl Responder> {
+pub async fn nchem_post_set_chemical_capture(state: Session, info: web::Json<InfoPostSetChemicalCapture>) -> Result<impl Responder> {
 
     let mut state_mod = state.lock().unwrap();
 
@@ -128,7 +158,7 @@ pub async fn nchem_post_set_chemical_capture(state: web::Data<Mutex<CAAppData>>,
 
 
 #[post("/nchem/set-species-configuration")]
-async fn nchem_set_species_configuration(state: web::Data<Mutex<CAAppData>>, info: web::Json<InfoPostSetSpeciesConfiguration>) -> Result<impl Responder> {
+async fn nchem_set_species_configuration(state: Session, info: web::Json<InfoPostSetSpeciesConfiguration>) -> Result<impl Responder> {
 
     // Construct the correct list of chemicals
     let mut chemicals: Vec<CAChemicalGroup> = vec![];
@@ -152,4 +182,222 @@ async fn nchem_set_species_configuration(state: web::Data<Mutex<CAAppData>>, inf
     drop(state_mod);
 
     Ok("")
-}
\ No newline at end of file
+}
+
+
+/**
+ * Method: set the state of the automaton from an uploaded NumPy .npy array of cell-types
+ */
+#[post("/nchem/set-state-npy")]
+async fn nchem_post_set_state_npy(state: Session, body: web::Bytes) -> Result<impl Responder> {
+
+    let (shape, cell_types) = read_npy_integers(&body).map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+
+    let mut state_mod = state.lock().unwrap();
+
+    // The volume must match the automaton exactly
+    let size = state_mod.nchem_ca.size();
+
+    if shape != vec![size, size, size] {
+        return Err(error::ErrorBadRequest(format!("Expected an array of shape ({}, {}, {}), got {:?}", size, size, size, shape)));
+    }
+
+    // Every value must be one of the K species or the undifferentiated cell-type (K)
+    let num_cell_types = state_mod.nchem_ca.chemicals.len() as u32 + 1;
+
+    if let Some(invalid) = cell_types.iter().find(|c| **c >= num_cell_types) {
+        return Err(error::ErrorBadRequest(format!("Cell-type {} does not exist, expected values below {}", invalid, num_cell_types)));
+    }
+
+    state_mod.nchem_ca.load_state(&cell_types);
+
+    drop(state_mod);
+
+    Ok(web::Json(ResponsePostGeneral{status: 0}))
+}
+
+
+/**
+ * Method: compare the state of the automaton with a snapshot, uploaded as a .npy array of shape (size, size, size) in C order.
+ * The automaton is the first state and the snapshot the second one.
+ */
+#[post("/nchem/compare-state-npy")]
+async fn nchem_post_compare_state_npy(state: Session, info: web::Query<InfoPostCompareState>, body: web::Bytes) -> Result<impl Responder> {
+
+    let (shape, snapshot) = read_npy_integers(&body).map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+
+    let state_mod = state.lock().unwrap();
+
+    let size = state_mod.nchem_ca.size();
+    let cell_types = cell_types_of(&state_mod.nchem_ca);
+
+    drop(state_mod);
+
+    if shape != vec![size, size, size] {
+        return Err(error::ErrorBadRequest(format!("Expected an array of shape ({}, {}, {}), got {:?}", size, size, size, shape)));
+    }
+
+    // The automaton stores its cell-types as bytes, so that a larger value can't be a cell-type
+    if let Some(invalid) = snapshot.iter().find(|c| **c > u8::MAX as u32) {
+        return Err(error::ErrorBadRequest(format!("Cell-type {} does not exist, expected values below 256", invalid)));
+    }
+
+    Ok(web::Json(compare_cell_types(size, &cell_types, &snapshot, info.volume.unwrap_or(false))))
+}
+
+
+/**
+ * Method: set the state of the automaton from an uploaded MagicaVoxel .vox model.
+ * Colour index c+1 becomes cell-type c, empty voxels become undifferentiated.
+ */
+#[post("/nchem/set-state-vox")]
+async fn nchem_post_set_state_vox(state: Session, body: web::Bytes) -> Result<impl Responder> {
+
+    let state_mod = state.lock().unwrap();
+
+    let size = state_mod.nchem_ca.size();
+    let num_cell_types = state_mod.nchem_ca.chemicals.len() as u32 + 1;
+
+    drop(state_mod);
+
+    // The file is parsed without holding the lock
+    let cell_types = read_vox(&body, size, num_cell_types - 1).map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+
+    let mut state_mod = state.lock().unwrap();
+
+    // The species may have been reconfigured in the meantime
+    let num_cell_types = state_mod.nchem_ca.chemicals.len() as u32 + 1;
+
+    // Every colour index must correspond to one of the K species or the undifferentiated cell-type (K)
+    if let Some(invalid) = cell_types.iter().find(|c| **c >= num_cell_types) {
+        return Err(error::ErrorBadRequest(format!("Colour index {} does not correspond to a cell-type, expected indices up to {}", invalid + 1, num_cell_types)));
+    }
+
+    state_mod.nchem_ca.load_state(&cell_types);
+
+    drop(state_mod);
+
+    Ok(web::Json(ResponsePostGeneral{status: 0}))
+}
+
+
+/**
+ * Method: enable or disable measuring the geometry of all cell-types after every iteration
+ */
+#[post("/nchem/set-geometry-recording")]
+pub async fn nchem_post_set_geometry_recording(state: Session, info: web::Json<InfoPostSetRecording>) -> Result<impl Responder> {
+
+    let mut state_mod = state.lock().unwrap();
+
+    state_mod.nchem_ca.record_geometry = info.record;
+
+    drop(state_mod);
+
+    Ok(web::Json(ResponsePostGeneral{status: 0}))
+
+}
+
+
+/**
+ * Method: enable or disable measuring the structure factor of all cell-types after every iteration
+ */
+#[post("/nchem/set-structure-factor-recording")]
+pub async fn nchem_post_set_structure_factor_recording(state: Session, info: web::Json<InfoPostSetRecording>) -> Result<impl Responder> {
+
+    let mut state_mod = state.lock().unwrap();
+
+    state_mod.nchem_ca.record_structure_factor = info.record;
+
+    drop(state_mod);
+
+    Ok(web::Json(ResponsePostGeneral{status: 0}))
+
+}
+
+
+/**
+ * Method: enable or disable measuring the segregation matrix of the cell-types after every iteration
+ */
+#[post("/nchem/set-segregation-recording")]
+pub async fn nchem_post_set_segregation_recording(state: Session, info: web::Json<InfoPostSetRecording>) -> Result<impl Responder> {
+
+    let mut state_mod = state.lock().unwrap();
+
+    state_mod.nchem_ca.record_segregation = info.record;
+
+    drop(state_mod);
+
+    Ok(web::Json(ResponsePostGeneral{status: 0}))
+
+}
+
+
+/**
+ * Method: set the neighbourhood over which the order parameter is computed: "face", "edge", "corner" or {"sphere": radius}.
+ * It applies to the order parameters that are computed from now on.
+ */
+#[post("/nchem/set-order-parameter-neighbourhood")]
+pub async fn nchem_post_set_order_parameter_neighbourhood(state: Session, info: web::Json<InfoPostSetNeighbourhood>) -> Result<impl Responder> {
+
+    let mut state_mod = state.lock().unwrap();
+
+    let size = state_mod.nchem_ca.size();
+
+    if !info.neighbourhood.fits(size) {
+        return Err(error::ErrorBadRequest(format!("The radius of the neighbourhood should be at most {}", size / 2)));
+    }
+
+    if info.neighbourhood.offsets().is_empty() {
+        return Err(error::ErrorBadRequest("The neighbourhood should contain at least one neighbour"));
+    }
+
+    state_mod.nchem_ca.order_parameter_neighbourhood = info.neighbourhood;
+
+    drop(state_mod);
+
+    Ok(web::Json(ResponsePostGeneral{status: 0}))
+
+}
+
+
+/**
+ * Method: observe one of the built-in metrics ("order-parameter", "volume-fractions", "flip-count", "interface-area"
+ * or "wavelength") every 'interval' iterations, replacing the observer of that metric if there was one.
+ */
+#[post("/nchem/add-observer")]
+pub async fn nchem_post_add_observer(state: Session, info: web::Json<InfoPostAddObserver>) -> Result<impl Responder> {
+
+    if info.interval == 0 {
+        return Err(error::ErrorBadRequest("The interval should be at least 1"));
+    }
+
+    let mut state_mod = state.lock().unwrap();
+
+    state_mod.nchem_ca.add_observer(info.metric.observer(info.interval));
+
+    drop(state_mod);
+
+    Ok(web::Json(ResponsePostGeneral{status: 0}))
+
+}
+
+
+/**
+ * Method: stop observing a metric. The samples that were already measured remain available.
+ */
+#[post("/nchem/remove-observer")]
+pub async fn nchem_post_remove_observer(state: Session, info: web::Json<InfoPostRemoveObserver>) -> Result<impl Responder> {
+
+    let mut state_mod = state.lock().unwrap();
+
+    let removed = state_mod.nchem_ca.remove_observer(&info.name);
+
+    drop(state_mod);
+
+    if !removed {
+        return Err(error::ErrorBadRequest(format!("There is no observer named '{}'", info.name)));
+    }
+
+    Ok(web::Json(ResponsePostGeneral{status: 0}))
+
+}
@@ -0,0 +1,121 @@
+use actix_web::{get, post, delete, web, error, Responder, Result};
+use serde::{Serialize, Deserialize};
+
+use crate::{CAAppData, CAChemicalGroup};
+use crate::appdata::dim3d::automata::automaton::CellularAutomaton3D;
+use crate::appdata::jobs::JobManager;
+use crate::appdata::sessions::{Sessions, DEFAULT_SESSION};
+use crate::routes::cpu_post::ResponsePostGeneral;
+
+
+#[derive(Deserialize)]
+pub struct InfoPostSessionParameters {
+    dc_range: f32,
+    dc_influence: f32,
+    uc_range: f32,
+    uc_influence: f32,
+    chemicals: Vec<CAChemicalGroup>
+}
+
+#[derive(Deserialize)]
+pub struct InfoPostSession {
+    id: String,
+    // The session to copy the automata, parameters and history from
+    copy_from: Option<String>,
+    // The parameters of new automata. Without them, the parameters of the default session are used.
+    parameters: Option<InfoPostSessionParameters>
+}
+
+#[derive(Serialize)]
+pub struct ResponseSession {
+    id: String,
+    cpu_iterations: u32,
+    gpu_iterations: u32,
+    nchem_iterations: u32
+}
+
+
+/**
+ * Method: create a session with its own automata, which are addressed by the routes under /sessions/{id}/...
+ */
+#[post("/sessions")]
+async fn sessions_post(sessions: web::Data<Sessions>, info: web::Json<InfoPostSession>) -> Result<impl Responder> {
+
+    let info = info.into_inner();
+
+    // The id is part of the paths of the session's routes
+    if info.id.is_empty() || !info.id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(error::ErrorBadRequest("A session id should only consist of letters, digits, '-' and '_'"));
+    }
+
+    let state = match (info.copy_from, info.parameters) {
+        (Some(_), Some(_)) => return Err(error::ErrorBadRequest("Either copy a session or give new parameters, not both")),
+        (Some(source), None) => {
+            let source = sessions.get(&source).ok_or_else(|| error::ErrorNotFound(format!("There is no session '{}'", source)))?;
+            let state_mod = source.lock().unwrap();
+            let state = state_mod.clone();
+            drop(state_mod);
+
+            state
+        },
+        (None, Some(p)) => CAAppData::new(p.dc_range, p.dc_influence, p.uc_range, p.uc_influence, p.chemicals),
+        (None, None) => {
+            let default = sessions.get(DEFAULT_SESSION).unwrap();
+            let state_mod = default.lock().unwrap();
+            let (cpu_ca, chemicals) = (&state_mod.cpu_ca, state_mod.nchem_ca.chemicals.clone());
+            let (dc_range, dc_influence, uc_range, uc_influence) = (cpu_ca.dc_range, cpu_ca.dc_influence, cpu_ca.uc_range, cpu_ca.uc_influence);
+            drop(state_mod);
+
+            CAAppData::new(dc_range, dc_influence, uc_range, uc_influence, chemicals)
+        }
+    };
+
+    if !sessions.create(&info.id, state) {
+        return Err(error::ErrorConflict(format!("There already is a session '{}'", info.id)));
+    }
+
+    Ok(web::Json(ResponsePostGeneral{status: 0}))
+}
+
+#[get("/sessions")]
+async fn sessions_get(sessions: web::Data<Sessions>) -> Result<impl Responder> {
+
+    let mut result: Vec<ResponseSession> = vec![];
+
+    for id in sessions.ids() {
+        // The session may have been deleted in the meantime
+        if let Some(state) = sessions.get(&id) {
+            let state_mod = state.lock().unwrap();
+
+            result.push(ResponseSession {
+                id,
+                cpu_iterations: state_mod.cpu_ca.get_iteration_count(),
+                gpu_iterations: state_mod.gpu_ca.get_iteration_count(),
+                nchem_iterations: state_mod.nchem_ca.get_iteration_count()
+            });
+
+            drop(state_mod);
+        }
+    }
+
+    Ok(web::Json(result))
+}
+
+/**
+ * Method: delete a session, cancelling and removing its jobs. The default session can't be deleted.
+ */
+#[delete("/sessions/{id}")]
+async fn sessions_delete(sessions: web::Data<Sessions>, jobs: web::Data<JobManager>, path: web::Path<String>) -> Result<impl Responder> {
+
+    let id = path.into_inner();
+
+    if id == DEFAULT_SESSION {
+        return Err(error::ErrorBadRequest("The default session can't be deleted"));
+    }
+
+    let session = sessions.delete(&id).ok_or_else(|| error::ErrorNotFound(format!("There is no session '{}'", id)))?;
+
+    jobs.remove_session(&session);
+
+    Ok(web::Json(ResponsePostGeneral{status: 0}))
+}
@@ -0,0 +1,3 @@
+pub mod npy;
+pub mod npz;
+pub mod vox;
\ No newline at end of file
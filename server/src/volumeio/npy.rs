@@ -0,0 +1,324 @@
+use miette::{miette, Result};
+
+//
+// Reading and writing of NumPy's .npy format (version 1.0 for writing, 1.0-3.0 for reading).
+// Spec: https://numpy.org/doc/stable/reference/generated/numpy.lib.format.html
+//
+
+const NPY_MAGIC: &[u8] = b"\x93NUMPY";
+
+// NumPy aligns the start of the data to a multiple of 64 bytes
+const NPY_ALIGNMENT: usize = 64;
+
+
+//
+// The element types that this server writes
+//
+pub enum NpyData {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+    F32(Vec<f32>)
+}
+
+impl NpyData {
+    fn descr(&self) -> &'static str {
+        match self {
+            NpyData::U8(_) => "|u1",
+            NpyData::U16(_) => "<u2",
+            NpyData::U32(_) => "<u4",
+            NpyData::F32(_) => "<f4"
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            NpyData::U8(v) => v.len(),
+            NpyData::U16(v) => v.len(),
+            NpyData::U32(v) => v.len(),
+            NpyData::F32(v) => v.len()
+        }
+    }
+}
+
+
+//
+// An n-dimensional array, stored in C (row-major) order
+//
+pub struct NpyArray {
+    pub shape: Vec<usize>,
+    pub data: NpyData
+}
+
+impl NpyArray {
+
+    pub fn new(shape: Vec<usize>, data: NpyData) -> Self {
+        // The shape must describe exactly the number of elements in the data
+        assert_eq!(shape.iter().product::<usize>(), data.len(), "NpyArray: shape does not match the number of elements");
+
+        NpyArray {
+            shape,
+            data
+        }
+    }
+
+    //
+    // Serialise this array into the bytes of a .npy file
+    //
+    pub fn to_bytes(&self) -> Vec<u8> {
+
+        // 1. Construct the header dictionary
+        let shape = match self.shape.len() {
+            // Python writes one-dimensional tuples with a trailing comma
+            1 => format!("({},)", self.shape[0]),
+            _ => format!("({})", self.shape.iter().map(|s| s.to_string()).collect::<Vec<String>>().join(", "))
+        };
+
+        let mut header = format!("{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}", self.data.descr(), shape);
+
+        // 2. Pad the header with spaces and a newline so that the data is aligned.
+        // The preamble consists of the magic string (6), the version (2) and the header length (2).
+        let preamble_length = NPY_MAGIC.len() + 2 + 2;
+
+        while (preamble_length + header.len() + 1) % NPY_ALIGNMENT != 0 {
+            header.push(' ');
+        }
+        header.push('\n');
+
+        // 3. Write the preamble, the header and the data
+        let mut bytes: Vec<u8> = Vec::with_capacity(preamble_length + header.len() + self.data.len() * 4);
+
+        bytes.extend_from_slice(NPY_MAGIC);
+        bytes.push(1);
+        bytes.push(0);
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+
+        match &self.data {
+            NpyData::U8(v) => bytes.extend_from_slice(v),
+            NpyData::U16(v) => v.iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes())),
+            NpyData::U32(v) => v.iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes())),
+            NpyData::F32(v) => v.iter().for_each(|x| bytes.extend_from_slice(&x.to_le_bytes()))
+        }
+
+        bytes
+    }
+
+}
+
+
+
+//
+// Parse a .npy file that contains an integer array, such as a grid of cell-types.
+// The returned values are always in C (row-major) order, regardless of the order in the file.
+//
+pub fn read_npy_integers(bytes: &[u8]) -> Result<(Vec<usize>, Vec<u32>)> {
+
+    // 1. Check the magic string and read the header length, which depends on the version
+    if bytes.len() < 10 || &bytes[0..6] != NPY_MAGIC {
+        return Err(miette!("Not a .npy file: magic string missing"));
+    }
+
+    let major_version = bytes[6];
+
+    let (header_start, header_length) = match major_version {
+        1 => (10, u16::from_le_bytes([bytes[8], bytes[9]]) as usize),
+        2 | 3 => {
+            if bytes.len() < 12 {
+                return Err(miette!("Truncated .npy header"));
+            }
+            (12, u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize)
+        },
+        _ => return Err(miette!("Unsupported .npy version {}", major_version))
+    };
+
+    if bytes.len() < header_start + header_length {
+        return Err(miette!("Truncated .npy header"));
+    }
+
+    let header = String::from_utf8_lossy(&bytes[header_start..(header_start + header_length)]).to_string();
+    let data = &bytes[(header_start + header_length)..];
+
+    // 2. Extract 'descr', 'fortran_order' and 'shape' from the header dictionary
+    let descr = header_value(&header, "descr")?;
+    let descr = descr.trim_matches(|c| c == '\'' || c == '"');
+
+    let fortran_order = header_value(&header, "fortran_order")? == "True";
+
+    let shape_str = header_value(&header, "shape")?;
+    let mut shape: Vec<usize> = vec![];
+
+    for dim in shape_str.trim_matches(|c| c == '(' || c == ')').split(',') {
+        let dim = dim.trim();
+        if dim.is_empty() {
+            continue;
+        }
+
+        match dim.parse::<usize>() {
+            Ok(d) => shape.push(d),
+            Err(_) => return Err(miette!("Invalid dimension '{}' in .npy shape", dim))
+        }
+    }
+
+    // 3. Decode the elements according to their type
+    let count = shape.iter().try_fold(1usize, |count, dim| count.checked_mul(*dim))
+        .ok_or_else(|| miette!("The .npy shape {:?} is too large", shape))?;
+
+    // The byte-order character is irrelevant for single-byte types ('|'), otherwise only little-endian is accepted
+    let (order, kind) = match (descr.get(..1), descr.get(1..)) {
+        (Some(order), Some(kind)) => (order, kind),
+        _ => return Err(miette!("Invalid .npy element type '{}'", descr))
+    };
+
+    if order == ">" {
+        return Err(miette!("Big-endian .npy arrays are not supported"));
+    }
+
+    let element_size: usize = match kind {
+        "u1" | "i1" | "b1" => 1,
+        "u2" | "i2" => 2,
+        "u4" | "i4" => 4,
+        "u8" | "i8" => 8,
+        _ => return Err(miette!("Unsupported .npy element type '{}', expected an integer type", descr))
+    };
+
+    if !count.checked_mul(element_size).is_some_and(|length| data.len() >= length) {
+        return Err(miette!("The .npy data is shorter than its shape {:?} requires", shape));
+    }
+
+    let mut values: Vec<u32> = Vec::with_capacity(count);
+
+    for i in 0..count {
+        let b = &data[(i * element_size)..((i + 1) * element_size)];
+
+        let value: i64 = match kind {
+            "u1" | "b1" => b[0] as i64,
+            "i1" => b[0] as i8 as i64,
+            "u2" => u16::from_le_bytes([b[0], b[1]]) as i64,
+            "i2" => i16::from_le_bytes([b[0], b[1]]) as i64,
+            "u4" => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as i64,
+            "i4" => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as i64,
+            "u8" => u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]) as i64,
+            _ => i64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]])
+        };
+
+        if value < 0 || value > u32::MAX as i64 {
+            return Err(miette!("Value {} at element {} cannot be used as a cell-type", value, i));
+        }
+
+        values.push(value as u32);
+    }
+
+    // 4. Fortran-ordered arrays are transposed into C order
+    if fortran_order && shape.len() > 1 {
+        let mut transposed = vec![0u32; count];
+
+        for (f_index, value) in values.iter().enumerate() {
+            // Decompose the Fortran index (first axis varies fastest) and recompose it in C order
+            let mut remainder = f_index;
+            let mut c_index = 0;
+            let mut c_stride = 1;
+            let mut coordinates = vec![0usize; shape.len()];
+
+            for (axis, dim) in shape.iter().enumerate() {
+                coordinates[axis] = remainder % dim;
+                remainder /= dim;
+            }
+
+            for axis in (0..shape.len()).rev() {
+                c_index += coordinates[axis] * c_stride;
+                c_stride *= shape[axis];
+            }
+
+            transposed[c_index] = *value;
+        }
+
+        values = transposed;
+    }
+
+    Ok((shape, values))
+
+}
+
+
+//
+// Helper: find the (unparsed) value that belongs to a key in the header dictionary
+//
+fn header_value<'a>(header: &'a str, key: &str) -> Result<&'a str> {
+
+    let key_position = match header.find(&format!("'{}'", key)) {
+        Some(p) => p,
+        None => return Err(miette!("Key '{}' missing from .npy header", key))
+    };
+
+    // The value starts after the colon that follows the key
+    let rest = &header[(key_position + key.len() + 2)..];
+    let rest = rest.trim_start().trim_start_matches(':').trim_start();
+
+    // Tuples end at the closing parenthesis, other values at the next comma
+    let end = if rest.starts_with('(') {
+        rest.find(')').map(|e| e + 1)
+    } else {
+        rest.find(|c| c == ',' || c == '}')
+    };
+
+    match end {
+        Some(e) => Ok(rest[..e].trim()),
+        None => Err(miette!("Malformed value for key '{}' in .npy header", key))
+    }
+
+}
+
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    // a[i][j][k] = 100*i + 10*j + k for an array of shape (2, 3, 4)
+    fn value(i: usize, j: usize, k: usize) -> u32 {
+        (100*i + 10*j + k) as u32
+    }
+
+    fn c_order() -> Vec<u32> {
+        (0..2).flat_map(|i| (0..3).flat_map(move |j| (0..4).map(move |k| value(i, j, k)))).collect()
+    }
+
+    #[test]
+    fn round_trip_in_c_order() {
+        let types = [
+            NpyData::U8(c_order().iter().map(|v| *v as u8).collect()),
+            NpyData::U16(c_order().iter().map(|v| *v as u16).collect()),
+            NpyData::U32(c_order())
+        ];
+
+        for data in types {
+            let bytes = NpyArray::new(vec![2, 3, 4], data).to_bytes();
+
+            let (shape, values) = read_npy_integers(&bytes).unwrap();
+
+            assert_eq!(shape, vec![2, 3, 4]);
+            assert_eq!(values, c_order());
+        }
+    }
+
+    #[test]
+    fn fortran_order_is_transposed_into_c_order() {
+        // In Fortran order the first axis varies fastest
+        let fortran: Vec<u32> = (0..4).flat_map(|k| (0..3).flat_map(move |j| (0..2).map(move |i| value(i, j, k)))).collect();
+
+        let mut bytes = NpyArray::new(vec![2, 3, 4], NpyData::U32(fortran)).to_bytes();
+
+        // Mark the data as Fortran-ordered, keeping the length of the header
+        let header_end = 10 + u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let header = String::from_utf8(bytes[10..header_end].to_vec()).unwrap().replace("'fortran_order': False", "'fortran_order':  True");
+        bytes.splice(10..header_end, header.into_bytes());
+
+        let (shape, values) = read_npy_integers(&bytes).unwrap();
+
+        assert_eq!(shape, vec![2, 3, 4]);
+        assert_eq!(values, c_order());
+    }
+
+}
@@ -0,0 +1,28 @@
+use std::io::{Cursor, Write};
+
+use miette::{IntoDiagnostic, Result};
+use zip::{ZipWriter, write::FileOptions, CompressionMethod};
+
+use super::npy::NpyArray;
+
+//
+// An .npz file is a zip-archive that contains one .npy file per named array.
+// Like numpy.savez, the arrays are stored without compression.
+//
+pub fn write_npz(arrays: Vec<(&str, NpyArray)>) -> Result<Vec<u8>> {
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+    for (name, array) in arrays {
+        // numpy.load expects every entry to carry the .npy extension
+        zip.start_file(format!("{}.npy", name), options).into_diagnostic()?;
+        zip.write_all(&array.to_bytes()).into_diagnostic()?;
+    }
+
+    let cursor = zip.finish().into_diagnostic()?;
+
+    Ok(cursor.into_inner())
+
+}
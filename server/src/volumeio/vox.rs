@@ -0,0 +1,501 @@
+use std::collections::{HashMap, HashSet};
+
+use miette::{miette, Result};
+
+use crate::appdata::dim3d::automata::automaton::CellularAutomaton3D;
+use crate::appdata::palette::cell_type_colour;
+
+//
+// Reading and writing of MagicaVoxel's .vox format (version 150).
+// Spec: https://github.com/ephtracy/voxel-model/blob/master/MagicaVoxel-file-format-vox.txt
+//
+// Colour index 0 means 'empty' in a .vox model, so cell-type c is stored with colour index c+1.
+//
+
+// A single .vox model can be at most 256 voxels wide along every axis
+pub const VOX_MAX_MODEL_SIZE: usize = 256;
+
+const VOX_VERSION: i32 = 150;
+
+// The deepest scene graph that is imported. MagicaVoxel itself only nests a few levels.
+const MAX_SCENE_DEPTH: usize = 256;
+
+
+
+//
+// WRITING
+//
+
+//
+// Export the cell-types of an automaton as a .vox scene.
+// Grids larger than 256 along an axis are split into several models, each placed at its own offset.
+// If 'empty_cell_type' is given, cells of that type are left empty.
+//
+pub fn write_vox(automaton: &dyn CellularAutomaton3D, empty_cell_type: Option<u32>) -> Vec<u8> {
+
+    let size = automaton.size();
+    let models_per_axis = (size + VOX_MAX_MODEL_SIZE - 1) / VOX_MAX_MODEL_SIZE;
+
+    let mut children: Vec<u8> = vec![];
+
+    // 1. Create a SIZE and an XYZI chunk for every model and remember where it should be placed
+    let mut model_origins: Vec<[usize; 3]> = vec![];
+    let mut model_sizes: Vec<[usize; 3]> = vec![];
+
+    for mx in 0..models_per_axis {
+        for my in 0..models_per_axis {
+            for mz in 0..models_per_axis {
+
+                let origin = [mx * VOX_MAX_MODEL_SIZE, my * VOX_MAX_MODEL_SIZE, mz * VOX_MAX_MODEL_SIZE];
+                let model_size = [
+                    usize::min(VOX_MAX_MODEL_SIZE, size - origin[0]),
+                    usize::min(VOX_MAX_MODEL_SIZE, size - origin[1]),
+                    usize::min(VOX_MAX_MODEL_SIZE, size - origin[2])
+                ];
+
+                let mut size_content: Vec<u8> = vec![];
+                for s in model_size {
+                    push_i32(&mut size_content, s as i32);
+                }
+
+                let mut voxels: Vec<u8> = vec![];
+                let mut num_voxels: i32 = 0;
+
+                for x in 0..model_size[0] {
+                    for y in 0..model_size[1] {
+                        for z in 0..model_size[2] {
+                            let cell_type = automaton.get(origin[0] + x, origin[1] + y, origin[2] + z);
+
+                            if Some(cell_type) == empty_cell_type {
+                                continue;
+                            }
+
+                            voxels.extend_from_slice(&[x as u8, y as u8, z as u8, (cell_type + 1) as u8]);
+                            num_voxels += 1;
+                        }
+                    }
+                }
+
+                let mut xyzi_content: Vec<u8> = vec![];
+                push_i32(&mut xyzi_content, num_voxels);
+                xyzi_content.extend_from_slice(&voxels);
+
+                push_chunk(&mut children, b"SIZE", &size_content, &[]);
+                push_chunk(&mut children, b"XYZI", &xyzi_content, &[]);
+
+                model_origins.push(origin);
+                model_sizes.push(model_size);
+            }
+        }
+    }
+
+    // 2. Create the scene graph: root transform (0) -> group (1) -> (transform -> shape) per model
+    let num_models = model_origins.len();
+
+    let mut root_transform: Vec<u8> = vec![];
+    push_transform(&mut root_transform, 0, 1, None);
+    push_chunk(&mut children, b"nTRN", &root_transform, &[]);
+
+    let mut group: Vec<u8> = vec![];
+    push_i32(&mut group, 1);
+    push_dict(&mut group, &[]);
+    push_i32(&mut group, num_models as i32);
+    for m in 0..num_models {
+        push_i32(&mut group, 2 + 2*m as i32);
+    }
+    push_chunk(&mut children, b"nGRP", &group, &[]);
+
+    for m in 0..num_models {
+        // MagicaVoxel positions a model by its centre, rounded down
+        let translation = [
+            (model_origins[m][0] + model_sizes[m][0] / 2) as i32,
+            (model_origins[m][1] + model_sizes[m][1] / 2) as i32,
+            (model_origins[m][2] + model_sizes[m][2] / 2) as i32
+        ];
+
+        let mut transform: Vec<u8> = vec![];
+        push_transform(&mut transform, 2 + 2*m as i32, 3 + 2*m as i32, Some(translation));
+        push_chunk(&mut children, b"nTRN", &transform, &[]);
+
+        let mut shape: Vec<u8> = vec![];
+        push_i32(&mut shape, 3 + 2*m as i32);
+        push_dict(&mut shape, &[]);
+        push_i32(&mut shape, 1);
+        push_i32(&mut shape, m as i32);
+        push_dict(&mut shape, &[]);
+        push_chunk(&mut children, b"nSHP", &shape, &[]);
+    }
+
+    // 3. Create the palette: entry i holds the colour of colour index i+1, and thus of cell-type i
+    let mut palette: Vec<u8> = vec![];
+
+    for i in 0..256 {
+        let rgb = cell_type_colour(i);
+        palette.extend_from_slice(&[rgb[0], rgb[1], rgb[2], 255]);
+    }
+
+    push_chunk(&mut children, b"RGBA", &palette, &[]);
+
+    // 4. Wrap everything in the MAIN chunk
+    let mut bytes: Vec<u8> = vec![];
+    bytes.extend_from_slice(b"VOX ");
+    push_i32(&mut bytes, VOX_VERSION);
+    push_chunk(&mut bytes, b"MAIN", &[], &children);
+
+    bytes
+
+}
+
+
+fn push_i32(bytes: &mut Vec<u8>, value: i32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_string(bytes: &mut Vec<u8>, value: &str) {
+    push_i32(bytes, value.len() as i32);
+    bytes.extend_from_slice(value.as_bytes());
+}
+
+fn push_dict(bytes: &mut Vec<u8>, entries: &[(&str, String)]) {
+    push_i32(bytes, entries.len() as i32);
+    for (key, value) in entries {
+        push_string(bytes, key);
+        push_string(bytes, value);
+    }
+}
+
+fn push_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    bytes.extend_from_slice(id);
+    push_i32(bytes, content.len() as i32);
+    push_i32(bytes, children.len() as i32);
+    bytes.extend_from_slice(content);
+    bytes.extend_from_slice(children);
+}
+
+fn push_transform(bytes: &mut Vec<u8>, node_id: i32, child_id: i32, translation: Option<[i32; 3]>) {
+    push_i32(bytes, node_id);
+    push_dict(bytes, &[]);
+    push_i32(bytes, child_id);
+
+    // Reserved id (must be -1), layer id and the number of frames
+    push_i32(bytes, -1);
+    push_i32(bytes, if translation.is_some() { 0 } else { -1 });
+    push_i32(bytes, 1);
+
+    match translation {
+        Some(t) => push_dict(bytes, &[("_t", format!("{} {} {}", t[0], t[1], t[2]))]),
+        None => push_dict(bytes, &[])
+    }
+}
+
+
+
+//
+// READING
+//
+
+struct VoxModel {
+    size: [i32; 3],
+    voxels: Vec<[u8; 4]>
+}
+
+enum VoxNode {
+    Transform { child: i32, translation: [i32; 3] },
+    Group { children: Vec<i32> },
+    Shape { models: Vec<i32> }
+}
+
+//
+// Import a .vox file as a grid of cell-types, indexed as [x][y][z] in row-major (C) order.
+// All models in the scene are placed according to the scene graph, the lowest corner of the
+// scene ends up at (0, 0, 0). Cells that are not covered by a voxel get the 'fill' cell-type.
+//
+pub fn read_vox(bytes: &[u8], size: usize, fill: u32) -> Result<Vec<u32>> {
+
+    if bytes.len() < 8 || &bytes[0..4] != b"VOX " {
+        return Err(miette!("Not a .vox file: magic string missing"));
+    }
+
+    let mut reader = VoxReader { bytes, position: 8 };
+
+    let mut models: Vec<VoxModel> = vec![];
+    let mut nodes: HashMap<i32, VoxNode> = HashMap::new();
+    let mut pending_size: Option<[i32; 3]> = None;
+
+    // The MAIN chunk has no content of its own, so all chunks can simply be read one after another
+    while reader.position < bytes.len() {
+        let id = reader.bytes(4)?.to_vec();
+        let content_size = reader.length()?;
+        let _children_size = reader.i32()?;
+
+        let content_end = reader.position.checked_add(content_size).ok_or_else(|| miette!("Invalid chunk size in the .vox file"))?;
+
+        match &id[..] {
+            b"MAIN" => {},
+            b"SIZE" => {
+                pending_size = Some([reader.i32()?, reader.i32()?, reader.i32()?]);
+            },
+            b"XYZI" => {
+                let model_size = match pending_size.take() {
+                    Some(s) => s,
+                    None => return Err(miette!("XYZI chunk without a preceding SIZE chunk"))
+                };
+
+                let num_voxels = reader.i32()?;
+                let mut voxels: Vec<[u8; 4]> = vec![];
+
+                for _ in 0..num_voxels {
+                    let v = reader.bytes(4)?;
+                    voxels.push([v[0], v[1], v[2], v[3]]);
+                }
+
+                models.push(VoxModel { size: model_size, voxels });
+            },
+            b"nTRN" => {
+                let node_id = reader.i32()?;
+                reader.dict()?;
+                let child = reader.i32()?;
+                let _reserved = reader.i32()?;
+                let _layer = reader.i32()?;
+                let num_frames = reader.i32()?;
+
+                // Only the first frame is used for the translation
+                let mut translation = [0i32; 3];
+
+                for frame in 0..num_frames {
+                    let attributes = reader.dict()?;
+
+                    if frame == 0 {
+                        if let Some(t) = attributes.get("_t") {
+                            let parts: Vec<i32> = t.split_whitespace().filter_map(|p| p.parse().ok()).collect();
+                            if parts.len() == 3 {
+                                translation = [parts[0], parts[1], parts[2]];
+                            }
+                        }
+                    }
+                }
+
+                nodes.insert(node_id, VoxNode::Transform { child, translation });
+            },
+            b"nGRP" => {
+                let node_id = reader.i32()?;
+                reader.dict()?;
+                let num_children = reader.i32()?;
+                let mut children: Vec<i32> = vec![];
+
+                for _ in 0..num_children {
+                    children.push(reader.i32()?);
+                }
+
+                nodes.insert(node_id, VoxNode::Group { children });
+            },
+            b"nSHP" => {
+                let node_id = reader.i32()?;
+                reader.dict()?;
+                let num_models = reader.i32()?;
+                let mut shape_models: Vec<i32> = vec![];
+
+                for _ in 0..num_models {
+                    shape_models.push(reader.i32()?);
+                    reader.dict()?;
+                }
+
+                nodes.insert(node_id, VoxNode::Shape { models: shape_models });
+            },
+            // Palettes, materials, layers, cameras etc. do not influence the cell-types
+            _ => {}
+        }
+
+        // MAIN is the parent of all other chunks, so its children are read in this same loop
+        if &id[..] != b"MAIN" {
+            reader.position = content_end;
+        }
+    }
+
+    // Determine the lowest corner of every model in the scene.
+    // Without a scene graph (older files), every model is placed at the origin.
+    // The corners are kept in i64, so that the translations in the file can't overflow.
+    let mut placements: Vec<(usize, [i64; 3])> = vec![];
+
+    if nodes.contains_key(&0) {
+        place_node(&nodes, &models, 0, [0, 0, 0], &mut placements, &mut HashSet::new(), 0)?;
+    } else {
+        for m in 0..models.len() {
+            placements.push((m, [0, 0, 0]));
+        }
+    }
+
+    if placements.is_empty() {
+        return Err(miette!("The .vox file does not contain any models"));
+    }
+
+    // Shift the scene so that its lowest corner is at (0, 0, 0)
+    let mut scene_min = placements[0].1;
+
+    for (_, corner) in &placements {
+        for axis in 0..3 {
+            scene_min[axis] = i64::min(scene_min[axis], corner[axis]);
+        }
+    }
+
+    let mut cell_types = vec![fill; size*size*size];
+
+    for (m, corner) in &placements {
+        for v in &models[*m].voxels {
+            let x = (corner[0] - scene_min[0]) as usize + v[0] as usize;
+            let y = (corner[1] - scene_min[1]) as usize + v[1] as usize;
+            let z = (corner[2] - scene_min[2]) as usize + v[2] as usize;
+
+            if x >= size || y >= size || z >= size {
+                return Err(miette!("Voxel ({}, {}, {}) lies outside of the automaton of size {}", x, y, z, size));
+            }
+
+            if v[3] == 0 {
+                return Err(miette!("Voxel ({}, {}, {}) uses the reserved colour index 0", x, y, z));
+            }
+
+            cell_types[(x*size + y)*size + z] = v[3] as u32 - 1;
+        }
+    }
+
+    Ok(cell_types)
+
+}
+
+
+//
+// Helper: walk the scene graph and record the lowest corner of every model.
+// Every node may only be visited once: a node that is shared by several parents (or part of a cycle)
+// could otherwise multiply the number of placements exponentially.
+//
+fn place_node(nodes: &HashMap<i32, VoxNode>, models: &[VoxModel], node_id: i32, offset: [i64; 3], placements: &mut Vec<(usize, [i64; 3])>, visited: &mut HashSet<i32>, depth: usize) -> Result<()> {
+
+    if !visited.insert(node_id) {
+        return Err(miette!("The .vox scene graph visits node {} more than once", node_id));
+    }
+
+    if depth > MAX_SCENE_DEPTH {
+        return Err(miette!("The .vox scene graph is nested deeper than {} levels", MAX_SCENE_DEPTH));
+    }
+
+    match nodes.get(&node_id) {
+        Some(VoxNode::Transform { child, translation }) => {
+            let offset = [offset[0] + translation[0] as i64, offset[1] + translation[1] as i64, offset[2] + translation[2] as i64];
+            place_node(nodes, models, *child, offset, placements, visited, depth + 1)?;
+        },
+        Some(VoxNode::Group { children }) => {
+            for child in children {
+                place_node(nodes, models, *child, offset, placements, visited, depth + 1)?;
+            }
+        },
+        Some(VoxNode::Shape { models: shape_models }) => {
+            for m in shape_models {
+                let model = match models.get(*m as usize) {
+                    Some(model) => model,
+                    None => return Err(miette!("Shape node {} references the unknown model {}", node_id, m))
+                };
+
+                // A transform positions the centre of the model (rounded down), not its corner
+                let corner = [
+                    offset[0] - model.size[0] as i64 / 2,
+                    offset[1] - model.size[1] as i64 / 2,
+                    offset[2] - model.size[2] as i64 / 2
+                ];
+
+                placements.push((*m as usize, corner));
+            }
+        },
+        None => return Err(miette!("The .vox scene graph references the unknown node {}", node_id))
+    }
+
+    Ok(())
+
+}
+
+
+struct VoxReader<'a> {
+    bytes: &'a [u8],
+    position: usize
+}
+
+impl<'a> VoxReader<'a> {
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = match self.position.checked_add(n) {
+            Some(end) if end <= self.bytes.len() => end,
+            _ => return Err(miette!("Unexpected end of the .vox file"))
+        };
+
+        let slice = &self.bytes[self.position..end];
+        self.position = end;
+
+        Ok(slice)
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        let b = self.bytes(4)?;
+        Ok(i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    // A chunk or string length, which must not be negative
+    fn length(&mut self) -> Result<usize> {
+        let length = self.i32()?;
+        usize::try_from(length).map_err(|_| miette!("Negative length {} in the .vox file", length))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let length = self.length()?;
+        Ok(String::from_utf8_lossy(self.bytes(length)?).to_string())
+    }
+
+    fn dict(&mut self) -> Result<HashMap<String, String>> {
+        let num_entries = self.i32()?;
+        let mut dict = HashMap::new();
+
+        for _ in 0..num_entries {
+            let key = self.string()?;
+            let value = self.string()?;
+            dict.insert(key, value);
+        }
+
+        Ok(dict)
+    }
+
+}
+
+
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::appdata::dim3d::automata::automaton_cpu::CPUCellularAutomaton3D;
+
+    #[test]
+    fn round_trip() {
+        // A grid of 300 is split into 2 x 2 x 2 models
+        for size in [10, 300] {
+            let mut automaton = CPUCellularAutomaton3D::new(size, 1.0, 1.0, 2.0, -0.1);
+
+            for x in 0..size {
+                for y in 0..size {
+                    for z in 0..size {
+                        automaton.set(x, y, z, ((7*x + 3*y + z) % 4) as u32);
+                    }
+                }
+            }
+
+            // Cells of type 3 are left empty and filled in again when reading
+            let cell_types = read_vox(&write_vox(&automaton, Some(3)), size, 3).unwrap();
+
+            for x in 0..size {
+                for y in 0..size {
+                    for z in 0..size {
+                        assert_eq!(cell_types[(x*size + y)*size + z], automaton.get(x, y, z), "cell ({}, {}, {})", x, y, z);
+                    }
+                }
+            }
+        }
+    }
+
+}